@@ -0,0 +1,109 @@
+//! Time to create repository trees of increasing sizes, to check that it scales linearly with
+//! the number of files, the time per file staying the same.
+//!
+//! Each size is benchmarked with three layouts: nested directories of 100 files, all the files
+//! in a single directory, and directories of 10 files with up to 1 000 subdirectories each.
+//!
+//! Walking with several workers reports its speedup over a single worker, which should be
+//! above 1 with more than one core.
+//!
+//! Run with `cargo bench --bench create_repository_tree`, optionally followed by the numbers of
+//! files to benchmark, 1 000, 10 000 and 100 000 files by default.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dit_id_generator::traits::generator::Generator;
+use repository_tree_creator::features::create_repository_tree::{create_repository_tree_with_options, CreateOptions};
+use repository_tree_creator::models::node::Node::BlobNode;
+use repository_tree_creator::models::tree::Tree;
+
+/// Shape of the benchmarked directories
+struct Layout {
+    name: &'static str,
+    files_per_directory: usize,
+    directories_per_directory: usize,
+}
+
+const LAYOUTS: [Layout; 3] = [
+    Layout { name: "nested", files_per_directory: 100, directories_per_directory: 10 },
+    Layout { name: "flat", files_per_directory: usize::MAX, directories_per_directory: 1 },
+    Layout { name: "wide", files_per_directory: 10, directories_per_directory: 1_000 },
+];
+
+/// Write `files` small files under `root`, `layout.files_per_directory` per directory,
+/// directories being nested so that each one has at most `layout.directories_per_directory`
+/// subdirectories
+fn write_files(root: &Path, files: usize, layout: &Layout) -> Vec<PathBuf> {
+    let mut file_paths = Vec::with_capacity(files);
+    for directory_number in 0..files.div_ceil(layout.files_per_directory) {
+        let mut directory = root.to_path_buf();
+        let mut rest = directory_number;
+        loop {
+            directory.push(format!("d{}", rest % layout.directories_per_directory));
+            rest /= layout.directories_per_directory;
+            if rest == 0 {
+                break;
+            }
+        }
+        fs::create_dir_all(&directory).unwrap();
+        let first_file = directory_number * layout.files_per_directory;
+        for file_number in first_file..files.min(first_file.saturating_add(layout.files_per_directory)) {
+            let file_path = directory.join(format!("f{}.txt", file_number));
+            fs::write(&file_path, format!("content of file {}\n", file_number)).unwrap();
+            file_paths.push(file_path);
+        }
+    }
+    file_paths
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+fn report(layout: &Layout, name: &str, files: usize, duration: Duration) {
+    println!("{:<8} {:<40} {:>8} files {:>10.1} ms {:>8.2} µs/file",
+             layout.name, name, files, duration.as_secs_f64() * 1000.0, duration.as_secs_f64() * 1_000_000.0 / files as f64);
+}
+
+fn main() {
+    let mut sizes: Vec<usize> = env::args().skip(1).filter_map(|arg| arg.parse().ok()).collect();
+    if sizes.is_empty() {
+        sizes = vec![1_000, 10_000, 100_000];
+    }
+    let workers = thread::available_parallelism().map(|workers| workers.get()).unwrap_or(1).max(2);
+
+    for files in sizes {
+        for layout in LAYOUTS.iter() {
+            let root = env::temp_dir().join(format!("dit_bench_{}_{}_{}", std::process::id(), layout.name, files));
+            let file_paths = write_files(&root, files, layout);
+
+            let (tree, single_worker) = time(|| create_repository_tree_with_options(Tree::default(), vec![root.clone()], &CreateOptions::default(), None).unwrap());
+            report(layout, "walk, 1 worker", files, single_worker);
+            let options = CreateOptions { workers, ..CreateOptions::default() };
+            let (_, duration) = time(|| create_repository_tree_with_options(Tree::default(), vec![root.clone()], &options, None).unwrap());
+            report(layout, &format!("walk, {} workers ({:.2}x)", workers, single_worker.as_secs_f64() / duration.as_secs_f64()), files, duration);
+            let options = CreateOptions { recursive: false, ..CreateOptions::default() };
+            let (_, duration) = time(|| create_repository_tree_with_options(Tree::default(), file_paths.clone(), &options, None).unwrap());
+            report(layout, "explicit file list", files, duration);
+
+            // Changing a file only regenerates the ids of the trees on its path
+            let mut tree = tree;
+            let changed_path = file_paths[files / 2].strip_prefix(tree.get_path()).unwrap().to_path_buf();
+            let (_, duration) = time(|| {
+                if let Some(BlobNode(blob)) = tree.get_mut_node_at_path(&changed_path) {
+                    blob.set_content("changed\n");
+                }
+                tree.generate_id()
+            });
+            report(layout, "regenerate ids after changing one file", files, duration);
+
+            fs::remove_dir_all(&root).unwrap();
+        }
+    }
+}
@@ -5,7 +5,12 @@ use std::fmt::Formatter;
 pub enum RepTreeError {
     IoError(std::io::Error),
     UnexpectedComportment(String),
-    Encryptor(String)
+    Encryptor(String),
+    ReferenceNotFound(String),
+    ReferenceConflict(String),
+    InvalidIndex(String),
+    LocalChanges(Vec<std::path::PathBuf>),
+    InterruptedCheckout(std::path::PathBuf)
 }
 
 impl fmt::Display for RepTreeError {
@@ -14,6 +19,14 @@ impl fmt::Display for RepTreeError {
             RepTreeError::IoError(e) => write!(f, "IO error: {}",e),
             RepTreeError::UnexpectedComportment(message) => write!(f, "{}", message),
             RepTreeError::Encryptor(message) => write!(f, "{}", message),
+            RepTreeError::ReferenceNotFound(name) => write!(f, "Reference not found: {}", name),
+            RepTreeError::ReferenceConflict(message) => write!(f, "Reference conflict: {}", message),
+            RepTreeError::InvalidIndex(message) => write!(f, "Invalid index: {}", message),
+            RepTreeError::LocalChanges(paths) => {
+                let paths: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+                write!(f, "Local changes would be overwritten: {}", paths.join(", "))
+            },
+            RepTreeError::InterruptedCheckout(journal) => write!(f, "A checkout was interrupted, resume or roll it back with its journal at {}", journal.display()),
         }
     }
 }
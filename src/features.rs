@@ -3,4 +3,10 @@ pub mod transcript_repository_tree_to_object_files;
 pub mod transcript_repository_to_files;
 pub mod get_repository_tree_from_object_files;
 pub mod merge_repository_trees;
-pub mod remove_element_from_repository_tree;
\ No newline at end of file
+pub mod remove_element_from_repository_tree;
+pub mod manage_references;
+pub mod diff_repository_trees;
+pub mod create_patch;
+pub mod apply_patch;
+pub mod merge_drivers;
+pub mod get_repository_status;
\ No newline at end of file
@@ -0,0 +1,579 @@
+use std::path::{Path, PathBuf};
+
+use dit_id_generator::traits::generator::Generator;
+
+use crate::error::RepTreeError;
+use crate::features::create_patch::{DEV_NULL, is_binary, NO_NEWLINE_MARKER};
+use crate::models::entry_mode::EntryMode;
+use crate::models::node::{Node, path_names};
+use crate::models::node::Node::BlobNode;
+
+/// Hunk of a file patch, `lines` keeping their `' '`, `'-'` or `'+'` prefix apart from the text
+#[derive(Clone, Debug)]
+struct Hunk {
+    old_start: usize,
+    lines: Vec<(char, String)>,
+}
+
+impl Hunk {
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines.iter().filter(|(tag, _)| *tag != '+').map(|(_, line)| line.as_str()).collect()
+    }
+
+    fn new_lines(&self) -> Vec<&str> {
+        self.lines.iter().filter(|(tag, _)| *tag != '-').map(|(_, line)| line.as_str()).collect()
+    }
+}
+
+/// Patch of a single file, `old_path` is `None` for a creation and `new_path` for a deletion
+///
+/// `new_mode` is the mode given by a `new mode` or `new file mode` header, the file keeping
+/// its mode otherwise.
+#[derive(Clone, Debug, Default)]
+struct FilePatch {
+    old_path: Option<PathBuf>,
+    new_path: Option<PathBuf>,
+    new_mode: Option<EntryMode>,
+    hunks: Vec<Hunk>,
+    binary: bool,
+}
+
+impl FilePatch {
+    fn get_path(&self) -> PathBuf {
+        self.new_path.clone().or(self.old_path.clone()).unwrap_or_default()
+    }
+}
+
+/// Hunk or file which couldn't be applied.
+///
+/// `hunk` is the position of the rejected hunk in the file patch, `None` when the whole file
+/// was rejected (missing file, binary patch...).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rejection {
+    pub path: PathBuf,
+    pub hunk: Option<usize>,
+    pub reason: String,
+}
+
+/// Result of applying a patch: files changed in the tree and rejected hunks.
+///
+/// A file with at least one rejected hunk is left untouched.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PatchReport {
+    pub applied: Vec<PathBuf>,
+    pub rejected: Vec<Rejection>,
+}
+
+impl PatchReport {
+    /// Check if every file of the patch was applied
+    pub fn is_clean(&self) -> bool {
+        self.rejected.is_empty()
+    }
+}
+
+/// Apply a multi-file unified patch to a repository tree
+///
+/// Files are created, deleted, renamed or modified in memory, their modes being set from the
+/// `new mode` and `new file mode` headers, then the ids of the tree are generated again. Each
+/// hunk is looked up at the line given by its header, then at the closest position where its
+/// context matches. Files whose hunks don't all match are left untouched and reported in
+/// `PatchReport::rejected`.
+///
+/// # Arguments
+///
+/// * `root` - root `TreeNode`, paths of the patch being relative to it
+/// * `patch` - patch in unified format, as produced by `create_patch`
+///
+/// # Returns
+///
+/// Result with the `PatchReport`, or `RepTreeError` if the patch can't be parsed
+pub fn apply_patch(root: &mut Node, patch: &str) -> Result<PatchReport, RepTreeError> {
+    if !root.is_tree() {
+        return Err(RepTreeError::UnexpectedComportment("A patch can only be applied to a tree".to_string()));
+    }
+
+    let mut report = PatchReport::default();
+    for file_patch in parse_patch(patch)?.iter() {
+        match apply_file_patch(root, file_patch) {
+            Ok(()) => report.applied.push(file_patch.get_path()),
+            Err(mut rejections) => report.rejected.append(&mut rejections),
+        }
+    }
+    root.generate_id();
+    Ok(report)
+}
+
+fn reject(path: PathBuf, hunk: Option<usize>, reason: &str) -> Vec<Rejection> {
+    vec![Rejection { path, hunk, reason: reason.to_string() }]
+}
+
+fn apply_file_patch(root: &mut Node, file_patch: &FilePatch) -> Result<(), Vec<Rejection>> {
+    let path = file_patch.get_path();
+    if file_patch.binary {
+        return Err(reject(path, None, "binary patches can't be applied"));
+    }
+
+    let old_content: Vec<u8> = match &file_patch.old_path {
+        Some(old_path) => match root.get_node_at_path(old_path) {
+            Some(node) if node.is_blob() => node.get_content().map_err(|e| reject(old_path.clone(), None, &e.to_string()))?,
+            _ => return Err(reject(old_path.clone(), None, "file doesn't exist")),
+        },
+        None => vec![]
+    };
+    if let Some(new_path) = &file_patch.new_path {
+        check_new_path(root, new_path, file_patch.old_path.as_deref()).map_err(|reason| reject(new_path.clone(), None, reason))?;
+    }
+    // Without hunks, as for a mode change, the content is kept even if it is binary
+    let new_content: Vec<u8> = if file_patch.hunks.is_empty() {
+        old_content
+    } else if is_binary(&old_content) {
+        return Err(reject(path, None, "file is binary"));
+    } else {
+        let old_text = String::from_utf8_lossy(&old_content);
+        apply_hunks(&old_text, &file_patch.hunks).map_err(|(hunk, reason)| {
+            reject(path.clone(), Some(hunk), &reason)
+        })?.into_bytes()
+    };
+
+    if let (Some(old_path), None) = (&file_patch.old_path, &file_patch.new_path) {
+        if !new_content.is_empty() {
+            return Err(reject(old_path.clone(), None, "deleted file doesn't match the patch"));
+        }
+    }
+
+    // A modified or renamed file keeps its mode unless the patch changes it
+    let mut mode = EntryMode::Regular;
+    if let Some(old_path) = &file_patch.old_path {
+        if let Some(BlobNode(old_blob)) = root.remove_node_at_path(old_path) {
+            mode = old_blob.get_mode();
+        }
+        prune_empty_trees(root, old_path);
+    }
+    let mode = file_patch.new_mode.unwrap_or(mode);
+    if let Some(new_path) = &file_patch.new_path {
+        let mut blob = Node::create_blob_node(String::new(), new_content, PathBuf::new());
+        if let BlobNode(new_blob) = &mut blob {
+            new_blob.set_mode(mode);
+        }
+        if !root.insert_node_at_path(new_path, blob) {
+            return Err(reject(new_path.clone(), None, "path goes through a file"));
+        }
+    }
+    Ok(())
+}
+
+/// Check that a file can be written at `new_path` once the file at `old_path` is removed, so
+/// that nothing is changed when it can't
+///
+/// # Returns
+///
+/// The reason why the file can't be written, when `new_path` is taken by another file or a
+/// directory, or goes through a file
+fn check_new_path(root: &Node, new_path: &Path, old_path: Option<&Path>) -> Result<(), &'static str> {
+    let names = path_names(new_path);
+    if names.is_empty() {
+        return Err("invalid path");
+    }
+    let mut parent = PathBuf::new();
+    for name in &names[..names.len() - 1] {
+        parent.push(name);
+        let is_file = root.get_node_at_path(&parent).is_some_and(|node| node.is_blob());
+        if is_file && Some(parent.as_path()) != old_path {
+            return Err("path goes through a file");
+        }
+    }
+    match root.get_node_at_path(new_path) {
+        Some(node) if node.is_tree() => Err("path is a directory"),
+        Some(_) if old_path != Some(new_path) => Err("file already exists"),
+        _ => Ok(())
+    }
+}
+
+/// Remove the directories left empty on the way to a deleted file
+fn prune_empty_trees(root: &mut Node, path: &Path) {
+    let mut parent = path.to_path_buf();
+    while parent.pop() && !path_names(&parent).is_empty() {
+        let is_empty = root
+            .get_node_at_path(&parent)
+            .and_then(|node| node.get_children_ref())
+            .is_some_and(|children| children.is_empty());
+        if !is_empty {
+            break;
+        }
+        root.remove_node_at_path(&parent);
+    }
+}
+
+/// Apply hunks to a text
+///
+/// # Returns
+///
+/// The patched text, or the position of the first rejected hunk with the reason
+fn apply_hunks(text: &str, hunks: &[Hunk]) -> Result<String, (usize, String)> {
+    let mut lines: Vec<String> = text.split_inclusive('\n').map(|line| line.to_string()).collect();
+    // Difference between the positions announced by the headers and the actual ones,
+    // due to the lines added or removed by previous hunks
+    let mut offset: isize = 0;
+    let mut minimum_position = 0;
+
+    for (position, hunk) in hunks.iter().enumerate() {
+        let old_lines = hunk.old_lines();
+        let expected = if old_lines.is_empty() { hunk.old_start } else { hunk.old_start.saturating_sub(1) };
+        let expected = (expected as isize + offset).max(0) as usize;
+
+        let found = find_hunk(&lines, &old_lines, expected, minimum_position)
+            .ok_or((position, format!("hunk @@ -{} @@ doesn't match", hunk.old_start)))?;
+
+        let new_lines: Vec<String> = hunk.new_lines().iter().map(|line| line.to_string()).collect();
+        let inserted = new_lines.len();
+        lines.splice(found..found + old_lines.len(), new_lines);
+
+        offset += inserted as isize - old_lines.len() as isize;
+        minimum_position = found + inserted;
+    }
+    Ok(lines.concat())
+}
+
+/// Find where a hunk applies, trying `expected` first then positions moving away from it
+fn find_hunk(lines: &[String], old_lines: &[&str], expected: usize, minimum_position: usize) -> Option<usize> {
+    let matches_at = |start: usize| {
+        start >= minimum_position
+            && start + old_lines.len() <= lines.len()
+            && lines[start..start + old_lines.len()].iter().zip(old_lines.iter()).all(|(line, old)| line == old)
+    };
+
+    let last_start = lines.len().saturating_sub(old_lines.len());
+    let expected = expected.min(last_start);
+    for distance in 0..=lines.len() {
+        if expected >= distance && matches_at(expected - distance) {
+            return Some(expected - distance);
+        }
+        if expected + distance <= last_start && matches_at(expected + distance) {
+            return Some(expected + distance);
+        }
+    }
+    None
+}
+
+/// Parse a file path from a `---` or `+++` header, dropping the `a/` or `b/` prefix
+fn parse_header_path(header: &str, prefix: &str) -> Option<PathBuf> {
+    let path = header.split('\t').next().unwrap_or(header).trim_end();
+    if path == DEV_NULL {
+        return None;
+    }
+    Some(PathBuf::from(path.strip_prefix(prefix).unwrap_or(path)))
+}
+
+/// Parse `-start[,count]` or `+start[,count]`
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let range = &range[1..];
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+fn parse_hunk_header(line: &str) -> Result<(usize, usize, usize), RepTreeError> {
+    let invalid = || RepTreeError::UnexpectedComportment(format!("Invalid hunk header: {line}"));
+    let mut parts = line.split(' ');
+    let (Some("@@"), Some(old), Some(new)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+    let (old_start, old_count) = parse_range(old).ok_or_else(invalid)?;
+    let (_, new_count) = parse_range(new).ok_or_else(invalid)?;
+    Ok((old_start, old_count, new_count))
+}
+
+/// Split a multi-file patch in file patches
+/// Parse the mode following `prefix` in a header line, `None` if the line has no mode
+fn parse_mode(line: &str, prefix: &str) -> Result<Option<EntryMode>, RepTreeError> {
+    match line.strip_prefix(prefix) {
+        Some(mode) => EntryMode::parse(mode.trim())
+            .map(Some)
+            .ok_or_else(|| RepTreeError::UnexpectedComportment(format!("Invalid mode: {line}"))),
+        None => Ok(None)
+    }
+}
+
+fn parse_patch(patch: &str) -> Result<Vec<FilePatch>, RepTreeError> {
+    let lines: Vec<&str> = patch.split_inclusive('\n').collect();
+    let mut file_patches: Vec<FilePatch> = Vec::new();
+    let mut position = 0;
+
+    while position < lines.len() {
+        let line = lines[position].trim_end_matches('\n');
+
+        if let Some(names) = line.strip_prefix("diff --git ") {
+            // Paths are taken from `---`/`+++` when present, from this line otherwise
+            let (old_name, new_name) = names.split_once(" b/").unwrap_or((names, names));
+            let old_path = PathBuf::from(old_name.strip_prefix("a/").unwrap_or(old_name));
+            file_patches.push(FilePatch {
+                old_path: Some(old_path),
+                new_path: Some(PathBuf::from(new_name)),
+                ..FilePatch::default()
+            });
+            position += 1;
+        } else if line == "new file" || line.starts_with("new file mode") {
+            if let Some(file_patch) = file_patches.last_mut() {
+                file_patch.old_path = None;
+                file_patch.new_mode = parse_mode(line, "new file mode ")?;
+            }
+            position += 1;
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            if let Some(file_patch) = file_patches.last_mut() {
+                file_patch.new_mode = parse_mode(mode, "")?;
+            }
+            position += 1;
+        } else if line == "deleted file" || line.starts_with("deleted file mode") {
+            if let Some(file_patch) = file_patches.last_mut() {
+                file_patch.new_path = None;
+            }
+            position += 1;
+        } else if line.starts_with("Binary files ") {
+            if let Some(file_patch) = file_patches.last_mut() {
+                file_patch.binary = true;
+            }
+            position += 1;
+        } else if let Some(old_header) = line.strip_prefix("--- ") {
+            let new_header = lines
+                .get(position + 1)
+                .and_then(|next| next.trim_end_matches('\n').strip_prefix("+++ "))
+                .ok_or_else(|| RepTreeError::UnexpectedComportment(format!("Missing +++ header after: {line}")))?;
+            let old_path = parse_header_path(old_header, "a/");
+            let new_path = parse_header_path(new_header, "b/");
+            let starts_new_file = file_patches.last().is_none_or(|file_patch| !file_patch.hunks.is_empty());
+            if starts_new_file {
+                file_patches.push(FilePatch { old_path, new_path, ..FilePatch::default() });
+            } else if let Some(file_patch) = file_patches.last_mut() {
+                file_patch.old_path = old_path;
+                file_patch.new_path = new_path;
+            }
+            position += 2;
+        } else if line.starts_with("@@ ") {
+            let (old_start, old_count, new_count) = parse_hunk_header(line)?;
+            let file_patch = file_patches
+                .last_mut()
+                .ok_or_else(|| RepTreeError::UnexpectedComportment("Hunk without file header".to_string()))?;
+            let mut hunk = Hunk { old_start, lines: vec![] };
+            let (mut old_seen, mut new_seen) = (0, 0);
+            position += 1;
+
+            while position < lines.len() && (old_seen < old_count || new_seen < new_count || lines[position].starts_with('\\')) {
+                let hunk_line = lines[position];
+                if hunk_line.starts_with('\\') {
+                    // The previous line has no newline at the end of the file
+                    if hunk_line.trim_end_matches('\n') == NO_NEWLINE_MARKER {
+                        if let Some((_, previous)) = hunk.lines.last_mut() {
+                            if previous.ends_with('\n') {
+                                previous.pop();
+                            }
+                        }
+                    }
+                    position += 1;
+                    continue;
+                }
+                let (tag, text) = match hunk_line.chars().next() {
+                    Some(tag @ (' ' | '-' | '+')) => (tag, &hunk_line[1..]),
+                    // Some tools strip the space of empty context lines
+                    Some('\n') => (' ', hunk_line),
+                    _ => return Err(RepTreeError::UnexpectedComportment(format!("Invalid hunk line: {hunk_line}"))),
+                };
+                if tag != '+' { old_seen += 1; }
+                if tag != '-' { new_seen += 1; }
+                hunk.lines.push((tag, text.to_string()));
+                position += 1;
+            }
+            if old_seen != old_count || new_seen != new_count {
+                return Err(RepTreeError::UnexpectedComportment(format!("Truncated hunk: {line}")));
+            }
+            file_patch.hunks.push(hunk);
+        } else {
+            position += 1;
+        }
+    }
+    Ok(file_patches)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use dit_id_generator::traits::generator::Generator;
+
+    use crate::features::apply_patch::apply_patch;
+    use crate::features::create_patch::{create_patch, PatchOptions};
+    use crate::models::entry_mode::EntryMode;
+    use crate::models::node::Node;
+    use crate::models::node::Node::BlobNode;
+
+    fn blob(name: &str, content: &str) -> Node {
+        Node::create_blob_node(name.to_string(), content, PathBuf::from(name))
+    }
+
+    fn root(children: Vec<Node>) -> Node {
+        let mut node = Node::create_tree_node("".to_string(), children, PathBuf::new());
+        node.generate_id();
+        node
+    }
+
+    fn content(root: &Node, path: &str) -> Option<String> {
+        root.get_node_at_path(Path::new(path))
+            .and_then(|node| node.get_content().ok())
+            .map(|content| String::from_utf8(content).unwrap())
+    }
+
+    #[test]
+    fn should_apply_created_patch() {
+        let mut old = root(vec![
+            blob("a.txt", "1\n2\n3\n4\n5\n6\n7\n8\n9\n"),
+            blob("gone.txt", "bye\n"),
+            Node::create_tree_node("docs".to_string(), vec![blob("guide.md", "# Guide")], PathBuf::from("docs")),
+        ]);
+        let new = root(vec![
+            blob("a.txt", "1\ntwo\n3\n4\n5\n6\n7\neight\n9\n"),
+            Node::create_tree_node("docs".to_string(), vec![blob("guide.md", "# Guide\n\nHello")], PathBuf::from("docs")),
+            Node::create_tree_node("src".to_string(), vec![blob("main.rs", "fn main() {}\n")], PathBuf::from("src")),
+        ]);
+        let patch = create_patch(&old, &new, &PatchOptions { context_lines: 1 }).unwrap();
+
+        let report = apply_patch(&mut old, &patch).unwrap();
+
+        assert!(report.is_clean());
+        assert_eq!(report.applied.len(), 4);
+        assert_eq!(old.get_id(), new.get_id());
+        assert_eq!(content(&old, "src/main.rs").unwrap(), "fn main() {}\n");
+        assert!(content(&old, "gone.txt").is_none());
+    }
+
+    #[test]
+    fn should_apply_hunk_at_shifted_position() {
+        let mut tree = root(vec![blob("a.txt", "header\nheader\n1\n2\n3\n")]);
+        let patch = "--- a/a.txt\n+++ b/a.txt\n@@ -1,3 +1,3 @@\n 1\n-2\n+two\n 3\n";
+
+        let report = apply_patch(&mut tree, patch).unwrap();
+
+        assert!(report.is_clean());
+        assert_eq!(content(&tree, "a.txt").unwrap(), "header\nheader\n1\ntwo\n3\n");
+    }
+
+    #[test]
+    fn should_reject_file_when_a_hunk_does_not_match() {
+        let mut tree = root(vec![blob("a.txt", "1\n2\n3\n"), blob("b.txt", "b\n")]);
+        let id = tree.get_id();
+        let patch = "diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-1
++one
+@@ -3 +3 @@
+-three
++THREE
+diff --git a/c.txt b/c.txt
+--- a/c.txt
++++ b/c.txt
+@@ -1 +1 @@
+-c
++C
+";
+
+        let report = apply_patch(&mut tree, patch).unwrap();
+
+        assert!(report.applied.is_empty());
+        assert_eq!(report.rejected.len(), 2);
+        assert_eq!(report.rejected[0].path, PathBuf::from("a.txt"));
+        assert_eq!(report.rejected[0].hunk, Some(1));
+        assert_eq!(report.rejected[1].hunk, None);
+        assert_eq!(content(&tree, "a.txt").unwrap(), "1\n2\n3\n");
+        assert_eq!(tree.get_id(), id);
+    }
+
+    #[test]
+    fn should_handle_missing_newline_at_end_of_file() {
+        let mut tree = root(vec![blob("a.txt", "hello")]);
+        let patch = "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-hello\n\\ No newline at end of file\n+hello\n";
+
+        let report = apply_patch(&mut tree, patch).unwrap();
+
+        assert!(report.is_clean());
+        assert_eq!(content(&tree, "a.txt").unwrap(), "hello\n");
+    }
+
+    #[test]
+    fn should_leave_files_untouched_when_renamed_file_can_not_be_written() {
+        let mut tree = root(vec![
+            blob("a.txt", "a\n"),
+            blob("b.txt", "b\n"),
+            blob("c.txt", "c\n"),
+            blob("file", "f\n"),
+        ]);
+        let id = tree.get_id();
+        let patch = "diff --git a/a.txt b/b.txt
+--- a/a.txt
++++ b/b.txt
+@@ -1 +1 @@
+-a
++A
+diff --git a/c.txt b/file/c.txt
+--- a/c.txt
++++ b/file/c.txt
+@@ -1 +1 @@
+-c
++C
+";
+
+        let report = apply_patch(&mut tree, patch).unwrap();
+        let reasons: Vec<(PathBuf, String)> = report.rejected.iter().map(|rejection| (rejection.path.clone(), rejection.reason.clone())).collect();
+
+        assert!(report.applied.is_empty());
+        assert_eq!(reasons, vec![
+            (PathBuf::from("b.txt"), "file already exists".to_string()),
+            (PathBuf::from("file/c.txt"), "path goes through a file".to_string()),
+        ]);
+        assert_eq!([content(&tree, "a.txt"), content(&tree, "b.txt"), content(&tree, "c.txt")],
+                   [Some("a\n".to_string()), Some("b\n".to_string()), Some("c\n".to_string())]);
+        assert_eq!(tree.get_id(), id);
+    }
+
+    #[test]
+    fn should_rename_file_to_a_path_under_itself() {
+        let mut tree = root(vec![blob("a", "a\n")]);
+        let patch = "--- a/a\n+++ b/a/a.txt\n@@ -1 +1 @@\n-a\n+A\n";
+
+        let report = apply_patch(&mut tree, patch).unwrap();
+
+        assert!(report.is_clean());
+        assert_eq!(content(&tree, "a/a.txt").unwrap(), "A\n");
+        assert!(tree.get_node_at_path(Path::new("a")).is_some_and(|node| node.is_tree()));
+    }
+
+    #[test]
+    fn should_apply_mode_changes() {
+        let mode = |node: Node, mode: EntryMode| match node {
+            BlobNode(mut blob) => {
+                blob.set_mode(mode);
+                BlobNode(blob)
+            },
+            node => node
+        };
+        let mut old = root(vec![
+            blob("run.sh", "#!/bin/sh\n"),
+            blob("image.png", "\u{0}binary"),
+            mode(blob("tool", "#!/bin/sh\n"), EntryMode::Executable),
+        ]);
+        let new = root(vec![
+            mode(blob("run.sh", "#!/bin/sh\n"), EntryMode::Executable),
+            mode(blob("image.png", "\u{0}binary"), EntryMode::Executable),
+            mode(blob("link", "run.sh"), EntryMode::Symlink),
+        ]);
+        let patch = create_patch(&old, &new, &PatchOptions::default()).unwrap();
+
+        let report = apply_patch(&mut old, &patch).unwrap();
+
+        assert!(patch.contains("diff --git a/run.sh b/run.sh\nold mode 100644\nnew mode 100755\n"));
+        assert!(patch.contains("new file mode 120000\n"));
+        assert!(patch.contains("deleted file mode 100755\n"));
+        assert!(report.is_clean());
+        assert_eq!(old.get_id(), new.get_id());
+    }
+}
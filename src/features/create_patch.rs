@@ -0,0 +1,373 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::RepTreeError;
+use crate::features::diff_repository_trees::{Change, ChangeKind, diff_repository_trees};
+use crate::features::get_repository_tree_from_object_files::get_repository_tree_from_object_store;
+use crate::models::entry_mode::EntryMode;
+use crate::models::node::Node;
+use crate::models::tree::Tree;
+use crate::store::object_store::ObjectStore;
+
+pub const NO_NEWLINE_MARKER: &str = "\\ No newline at end of file";
+pub const DEV_NULL: &str = "/dev/null";
+
+/// Options of the unified diff output.
+///
+/// - `context_lines`: number of unchanged lines shown around each change, 3 by default.
+#[derive(Clone, Debug)]
+pub struct PatchOptions {
+    pub context_lines: usize,
+}
+
+impl Default for PatchOptions {
+    fn default() -> Self {
+        PatchOptions { context_lines: 3 }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LineTag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Check if a content can't be rendered as text: invalid UTF-8 or containing a NUL byte
+pub fn is_binary(content: &[u8]) -> bool {
+    content.contains(&0) || std::str::from_utf8(content).is_err()
+}
+
+/// Render the unified diff between two versions of a file
+///
+/// # Arguments
+///
+/// * `old_path` - path of the old version, `None` when the file is created
+/// * `new_path` - path of the new version, `None` when the file is deleted
+/// * `old` - old content, empty when the file is created
+/// * `new` - new content, empty when the file is deleted
+/// * `options` - output options
+///
+/// # Returns
+///
+/// The file patch with its `diff`, `---` and `+++` headers followed by the `@@` hunks, a
+/// "Binary files differ" line for binary content, or an empty string if both versions are equal
+pub fn create_blob_patch(old_path: Option<&Path>, new_path: Option<&Path>, old: &[u8], new: &[u8], options: &PatchOptions) -> String {
+    create_blob_patch_with_modes(old_path, new_path, old, new, EntryMode::Regular, EntryMode::Regular, options)
+}
+
+/// Render the unified diff between two versions of a file with their modes, see `EntryMode`
+///
+/// Created and deleted files get a `new file mode` or `deleted file mode` header, and a file
+/// whose mode changed gets `old mode` and `new mode` headers, with no hunks if its content is
+/// the same.
+///
+/// # Arguments
+///
+/// * `old_path` - path of the old version, `None` when the file is created
+/// * `new_path` - path of the new version, `None` when the file is deleted
+/// * `old` - old content, empty when the file is created
+/// * `new` - new content, empty when the file is deleted
+/// * `old_mode` - mode of the old version, ignored when the file is created
+/// * `new_mode` - mode of the new version, ignored when the file is deleted
+/// * `options` - output options
+///
+/// # Returns
+///
+/// The file patch, or an empty string if both versions are equal
+pub fn create_blob_patch_with_modes(old_path: Option<&Path>, new_path: Option<&Path>, old: &[u8], new: &[u8], old_mode: EntryMode, new_mode: EntryMode, options: &PatchOptions) -> String {
+    if old_path.is_some() && new_path.is_some() && old == new && old_mode == new_mode {
+        return String::new();
+    }
+
+    let display = |path: Option<&Path>| path.map(|path| path.to_string_lossy().replace('\\', "/"));
+    let (old_name, new_name) = (display(old_path), display(new_path));
+    let a_name = old_name.clone().or(new_name.clone()).unwrap_or_default();
+    let b_name = new_name.clone().or(old_name.clone()).unwrap_or_default();
+    let old_label = old_name.map(|name| format!("a/{name}")).unwrap_or(DEV_NULL.to_string());
+    let new_label = new_name.map(|name| format!("b/{name}")).unwrap_or(DEV_NULL.to_string());
+
+    let mut patch = format!("diff --git a/{a_name} b/{b_name}\n");
+    if old_path.is_none() {
+        patch += &format!("new file mode {}\n", new_mode.as_str());
+    } else if new_path.is_none() {
+        patch += &format!("deleted file mode {}\n", old_mode.as_str());
+    } else if old_mode != new_mode {
+        patch += &format!("old mode {}\nnew mode {}\n", old_mode.as_str(), new_mode.as_str());
+        if old == new {
+            return patch;
+        }
+    }
+
+    if is_binary(old) || is_binary(new) {
+        patch += &format!("Binary files {old_label} and {new_label} differ\n");
+        return patch;
+    }
+
+    patch += &format!("--- {old_label}\n+++ {new_label}\n");
+    // Both contents are valid UTF-8 as checked by `is_binary`
+    let old_text = String::from_utf8_lossy(old);
+    let new_text = String::from_utf8_lossy(new);
+    patch += &create_hunks(&old_text, &new_text, options.context_lines);
+    patch
+}
+
+/// Render the `@@` hunks between two texts
+fn create_hunks(old: &str, new: &str, context_lines: usize) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+    let lines: Vec<(LineTag, &str)> = diff::slice(&old_lines, &new_lines)
+        .into_iter()
+        .map(|result| match result {
+            diff::Result::Left(line) => (LineTag::Delete, *line),
+            diff::Result::Right(line) => (LineTag::Insert, *line),
+            diff::Result::Both(line, _) => (LineTag::Equal, *line),
+        })
+        .collect();
+
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, (tag, _))| *tag != LineTag::Equal)
+        .map(|(position, _)| position)
+        .collect();
+    if changed.is_empty() {
+        return String::new();
+    }
+
+    // Group changes whose context overlaps in the same hunk
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut start = changed[0].saturating_sub(context_lines);
+    let mut end = changed[0] + 1;
+    for &position in changed.iter().skip(1) {
+        if position - end > 2 * context_lines {
+            ranges.push((start, (end + context_lines).min(lines.len())));
+            start = position - context_lines;
+        }
+        end = position + 1;
+    }
+    ranges.push((start, (end + context_lines).min(lines.len())));
+
+    let mut hunks = String::new();
+    for (start, end) in ranges {
+        let old_before = lines[..start].iter().filter(|(tag, _)| *tag != LineTag::Insert).count();
+        let new_before = lines[..start].iter().filter(|(tag, _)| *tag != LineTag::Delete).count();
+        let old_count = lines[start..end].iter().filter(|(tag, _)| *tag != LineTag::Insert).count();
+        let new_count = lines[start..end].iter().filter(|(tag, _)| *tag != LineTag::Delete).count();
+
+        hunks += &format!("@@ -{} +{} @@\n", hunk_range(old_before, old_count), hunk_range(new_before, new_count));
+        for (tag, line) in lines[start..end].iter() {
+            let prefix = match tag {
+                LineTag::Equal => ' ',
+                LineTag::Delete => '-',
+                LineTag::Insert => '+',
+            };
+            hunks.push(prefix);
+            hunks += line;
+            if !line.ends_with('\n') {
+                hunks += &format!("\n{NO_NEWLINE_MARKER}\n");
+            }
+        }
+    }
+    hunks
+}
+
+/// Format a hunk range as `start,count`, the count being omitted when it is 1
+fn hunk_range(before: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", before),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{}", before + 1, count),
+    }
+}
+
+/// Collect every blob of a node with its path and mode, `path` being the path of the node itself
+fn collect_blobs(node: &Node, path: &Path, blobs: &mut Vec<(PathBuf, Vec<u8>, EntryMode)>) -> Result<(), RepTreeError> {
+    match node.get_children_ref() {
+        Some(children) => {
+            let mut children: Vec<&Node> = children.iter().collect();
+            children.sort_by_key(|child| child.get_name());
+            for child in children {
+                collect_blobs(child, &path.join(child.get_name()), blobs)?;
+            }
+        },
+        None => blobs.push((path.to_path_buf(), node.get_content()?, node.get_mode())),
+    }
+    Ok(())
+}
+
+fn create_change_patch(change: &Change, old: &Node, new: &Node, options: &PatchOptions) -> Result<String, RepTreeError> {
+    let old_node = old.get_node_at_path(&change.path);
+    let new_node = new.get_node_at_path(&change.path);
+    let mut old_blobs = Vec::new();
+    let mut new_blobs = Vec::new();
+    if change.kind != ChangeKind::Added {
+        if let Some(node) = old_node {
+            collect_blobs(node, &change.path, &mut old_blobs)?;
+        }
+    }
+    if change.kind != ChangeKind::Deleted {
+        if let Some(node) = new_node {
+            collect_blobs(node, &change.path, &mut new_blobs)?;
+        }
+    }
+
+    let patch = match (change.kind, old_blobs.as_slice(), new_blobs.as_slice()) {
+        (ChangeKind::Modified, [(path, old_content, old_mode)], [(_, new_content, new_mode)]) => {
+            create_blob_patch_with_modes(Some(path), Some(path), old_content, new_content, *old_mode, *new_mode, options)
+        },
+        _ => {
+            let mut patch = String::new();
+            for (path, content, mode) in old_blobs.iter() {
+                patch += &create_blob_patch_with_modes(Some(path), None, content, &[], *mode, *mode, options);
+            }
+            for (path, content, mode) in new_blobs.iter() {
+                patch += &create_blob_patch_with_modes(None, Some(path), &[], content, *mode, *mode, options);
+            }
+            patch
+        }
+    };
+    Ok(patch)
+}
+
+/// Render the unified diff between two repository trees
+///
+/// Type changes are rendered as the deletion of the old files followed by the creation of
+/// the new ones. Empty directories don't appear in the patch.
+///
+/// # Arguments
+///
+/// * `old` - root `Node` of the old tree
+/// * `new` - root `Node` of the new tree
+/// * `options` - output options
+///
+/// # Returns
+///
+/// Result with the multi-file patch, paths being relative to the roots, or `RepTreeError` if
+/// the content of a file can't be read
+pub fn create_patch(old: &Node, new: &Node, options: &PatchOptions) -> Result<String, RepTreeError> {
+    diff_repository_trees(old, new)
+        .iter()
+        .map(|change| create_change_patch(change, old, new, options))
+        .collect()
+}
+
+/// Render the unified diff between two trees stored in an `ObjectStore`
+///
+/// # Returns
+///
+/// Result with the multi-file patch, or `RepTreeError` if an object can't be read
+pub fn create_patch_from_object_store(old_id: &str, new_id: &str, store: &dyn ObjectStore, options: &PatchOptions) -> Result<String, RepTreeError> {
+    let mut old = Tree::default();
+    get_repository_tree_from_object_store(&mut old, old_id, store)?;
+    old.set_id(old_id.to_string());
+    let mut new = Tree::default();
+    get_repository_tree_from_object_store(&mut new, new_id, store)?;
+    new.set_id(new_id.to_string());
+    create_patch(&Node::TreeNode(old), &Node::TreeNode(new), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use dit_id_generator::traits::generator::Generator;
+
+    use crate::features::create_patch::{create_blob_patch, create_patch, PatchOptions};
+    use crate::models::node::Node;
+
+    #[test]
+    fn should_create_hunks_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\ntwelve\n";
+        let path = Path::new("numbers.txt");
+
+        let patch = create_blob_patch(Some(path), Some(path), old.as_bytes(), new.as_bytes(), &PatchOptions { context_lines: 2 });
+
+        assert_eq!(patch, "diff --git a/numbers.txt b/numbers.txt
+--- a/numbers.txt
++++ b/numbers.txt
+@@ -1,5 +1,5 @@
+ 1
+ 2
+-3
++three
+ 4
+ 5
+@@ -10,3 +10,3 @@
+ 10
+ 11
+-12
++twelve
+");
+    }
+
+    #[test]
+    fn should_merge_close_changes_in_one_hunk() {
+        let path = Path::new("a");
+        let patch = create_blob_patch(Some(path), Some(path), b"a\nb\nc\nd\n", b"A\nb\nc\nD\n", &PatchOptions::default());
+
+        assert_eq!(patch.matches("@@ -").count(), 1);
+        assert!(patch.contains("@@ -1,4 +1,4 @@\n"));
+    }
+
+    #[test]
+    fn should_mark_missing_newline_and_created_files() {
+        let patch = create_blob_patch(None, Some(Path::new("new.txt")), b"", b"hello", &PatchOptions::default());
+
+        assert_eq!(patch, "diff --git a/new.txt b/new.txt
+new file mode 100644
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++hello
+\\ No newline at end of file
+");
+    }
+
+    #[test]
+    fn should_mark_binary_files() {
+        let path = Path::new("logo.png");
+        let patch = create_blob_patch(Some(path), Some(path), &[0x89, 0x00], &[0x89, 0x01], &PatchOptions::default());
+
+        assert_eq!(patch, "diff --git a/logo.png b/logo.png\nBinary files a/logo.png and b/logo.png differ\n");
+    }
+
+    #[test]
+    fn should_create_repository_patch() {
+        let mut old = Node::create_tree_node("".to_string(), vec![
+            Node::create_blob_node("a.txt".to_string(), "a\n", PathBuf::new()),
+            Node::create_blob_node("b.txt".to_string(), "b\n", PathBuf::new()),
+        ], PathBuf::new());
+        let mut new = Node::create_tree_node("".to_string(), vec![
+            Node::create_blob_node("a.txt".to_string(), "A\n", PathBuf::new()),
+            Node::create_tree_node("c".to_string(), vec![
+                Node::create_blob_node("d.txt".to_string(), "d\n", PathBuf::new()),
+            ], PathBuf::new()),
+        ], PathBuf::new());
+        old.generate_id();
+        new.generate_id();
+
+        let patch = create_patch(&old, &new, &PatchOptions::default()).unwrap();
+
+        assert_eq!(patch, "diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-a
++A
+diff --git a/b.txt b/b.txt
+deleted file mode 100644
+--- a/b.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-b
+diff --git a/c/d.txt b/c/d.txt
+new file mode 100644
+--- /dev/null
++++ b/c/d.txt
+@@ -0,0 +1 @@
++d
+");
+    }
+}
@@ -1,15 +1,108 @@
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::fs::{self, File};
+use std::fs::{self, File, Metadata};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 use dit_id_generator::traits::generator::Generator;
 use crate::error::RepTreeError;
 use crate::models::blob::Blob;
+use crate::models::entry_mode::EntryMode;
+use crate::models::ignore_rules::IgnoreRules;
+use crate::models::index::Index;
 use crate::models::node::Node;
 use crate::models::node::Node::{BlobNode, TreeNode};
 use crate::models::tree::Tree;
 
+/// How symbolic links are ingested.
+///
+/// - `Follow`: the file or directory the link points to is added, broken links and links to
+///   a directory being walked being skipped.
+/// - `Record`: the link is added as a file whose content is the path it points to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymlinkMode {
+    Follow,
+    Record,
+}
+
+/// Options of `create_repository_tree_with_options`.
+///
+/// With `recursive`, a directory passed in the paths is added with everything under it,
+/// `max_depth` limiting how many levels of directories are walked: with `Some(1)`, only the
+/// direct children of the directory are added, its subdirectories being added empty.
+///
+/// Paths matched by `ignore_rules` are skipped, whether they are passed or found while walking
+/// directories. The rules are looked up with the paths of the files on the file system, a file
+/// outside of the root of the rules being never ignored. By default, the `.ditignore` files
+/// found from the current directory are honored, the global exclude file being only used when
+/// `ignore_rules` come from `IgnoreRules::with_global_exclude_file`. With `force`, the paths
+/// passed are added even if they are ignored, as `git add -f` does, the files found under them
+/// still being skipped.
+///
+/// With more than one `workers`, files are read and hashed concurrently by that many threads
+/// once all the paths are walked, the tree and its ids being the same as with a single worker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreateOptions {
+    pub recursive: bool,
+    pub max_depth: Option<usize>,
+    pub symlinks: SymlinkMode,
+    pub ignore_rules: Option<IgnoreRules>,
+    pub force: bool,
+    pub workers: usize,
+}
+
+impl Default for CreateOptions {
+    fn default() -> Self {
+        CreateOptions {
+            recursive: true,
+            max_depth: None,
+            symlinks: SymlinkMode::Record,
+            ignore_rules: Some(IgnoreRules::new(Path::new(""))),
+            force: false,
+            workers: 1,
+        }
+    }
+}
+
+impl CreateOptions {
+    /// Options adding only the exact paths given, as `create_repository_tree` does
+    fn explicit_paths() -> CreateOptions {
+        CreateOptions {
+            recursive: false,
+            max_depth: None,
+            symlinks: SymlinkMode::Follow,
+            ignore_rules: Some(IgnoreRules::new(Path::new(""))),
+            force: false,
+            workers: 1,
+        }
+    }
+}
+
+/// State shared while adding paths to a repository tree
+#[derive(Default)]
+struct Ingestion<'a> {
+    options: CreateOptions,
+    index: Option<&'a mut Index>,
+    // Canonical paths of the directories being walked, to detect symbolic link cycles
+    walked_directories: Vec<PathBuf>,
+    // Files left to read by the workers, their blobs being empty until then
+    pending_blobs: Vec<PendingBlob>,
+}
+
+/// File to read into the blob at `blob_path`
+struct PendingBlob {
+    name: String,
+    blob_path: PathBuf,
+    file_path: PathBuf,
+    mode: EntryMode,
+    // Metadata to record in the index once the file is hashed
+    metadata: Option<Metadata>,
+}
 
 /// Create a repository tree
 ///
+/// Paths ignored by the `.ditignore` files found from the current directory are skipped,
+/// see `CreateOptions`.
+///
 /// # Arguments
 ///
 /// `paths`- vector of `PathBuf` to add
@@ -18,20 +111,135 @@ use crate::models::tree::Tree;
 ///
 /// Result with root `node`, or `RepTreeError` if error
 pub fn create_repository_tree(tree: Tree, paths: Vec<PathBuf>) -> Result<Node,RepTreeError> {
+    create_repository_tree_with_options(tree, paths, &CreateOptions::explicit_paths(), None)
+}
+
+/// Create a repository tree, reading only the files changed since they were recorded in `index`
+///
+/// Files whose stat data matches their entry in `index` get an unloaded `Blob` with the
+/// recorded id, their content being read only when needed. Other files are read and hashed,
+/// and their entries are updated. Paths in `index` are the paths of the blobs in the tree.
+///
+/// # Arguments
+///
+/// * `tree` - root tree
+/// * `paths` - vector of `PathBuf` to add
+/// * `index` - index of the working directory, to write back once the tree is created
+///
+/// # Returns
+///
+/// Result with root `node`, or `RepTreeError` if error
+pub fn create_repository_tree_with_index(tree: Tree, paths: Vec<PathBuf>, index: &mut Index) -> Result<Node,RepTreeError> {
+    create_repository_tree_with_options(tree, paths, &CreateOptions::explicit_paths(), Some(index))
+}
+
+/// Create a repository tree, walking the directories passed in `paths` if requested
+///
+/// # Arguments
+///
+/// * `tree` - root tree
+/// * `paths` - vector of `PathBuf` to add
+/// * `options` - how directories and symbolic links are ingested
+/// * `index` - index of the working directory, see `create_repository_tree_with_index`
+///
+/// # Returns
+///
+/// Result with root `node`, or `RepTreeError` if error
+pub fn create_repository_tree_with_options(tree: Tree, paths: Vec<PathBuf>, options: &CreateOptions, index: Option<&mut Index>) -> Result<Node,RepTreeError> {
     let mut root = TreeNode(tree);
+    let mut ingestion = Ingestion { options: options.clone(), index, ..Ingestion::default() };
 
     for path in paths.iter() {
+        if !options.force {
+            if let Some(ignore_rules) = ingestion.options.ignore_rules.as_mut() {
+                if ignore_rules.is_ignored_file(path, path.is_dir())? {
+                    continue;
+                }
+            }
+        }
         let mut directories_and_file = get_elements(path);
         if !directories_and_file.is_empty() {
-            add_node_to_repository_tree(&mut root, &mut directories_and_file)?;
+            add_node_to_repository_tree(&mut root, &mut directories_and_file, &mut ingestion)?;
         }
     }
-    
+
+    let pending_blobs = std::mem::take(&mut ingestion.pending_blobs);
+    let blobs = read_pending_blobs(&pending_blobs, options.workers);
+    let mut read_blobs: HashMap<PathBuf, Blob> = HashMap::with_capacity(pending_blobs.len());
+    let mut directories: HashSet<PathBuf> = HashSet::new();
+    for (pending_blob, blob) in pending_blobs.iter().zip(blobs) {
+        let mut blob = blob?;
+        if let (Some(index), Some(metadata)) = (ingestion.index.as_deref_mut(), pending_blob.metadata.as_ref()) {
+            index.update_entry(&pending_blob.blob_path, metadata, &blob.get_id());
+        }
+        blob.set_path(pending_blob.blob_path.clone());
+        blob.set_mode(pending_blob.mode);
+        directories.extend(pending_blob.blob_path.ancestors().skip(1).map(Path::to_path_buf));
+        read_blobs.insert(pending_blob.blob_path.clone(), blob);
+    }
+    if !read_blobs.is_empty() {
+        put_read_blobs(&mut root, &mut read_blobs, &directories);
+    }
+
+    // Blobs are hashed when read, so only tree ids are generated, once all the paths are added
     root.generate_id();
 
     Ok(root)
 }
 
+/// Put the blobs read by the workers in place of the empty blobs at their paths, going once
+/// through the trees containing them only, so that the other trees keep their ids
+///
+/// # Arguments
+///
+/// * `node` - node to go through
+/// * `blobs` - read blobs by path
+/// * `directories` - paths of the trees containing read blobs
+fn put_read_blobs(node: &mut Node, blobs: &mut HashMap<PathBuf, Blob>, directories: &HashSet<PathBuf>) {
+    match node {
+        BlobNode(blob) => {
+            if let Some(read_blob) = blobs.remove(&blob.get_path()) {
+                *blob = read_blob;
+            }
+        },
+        TreeNode(tree) => {
+            if directories.contains(&tree.get_path()) {
+                for child in tree.get_mut_children() {
+                    put_read_blobs(child, blobs, directories);
+                }
+            }
+        }
+    }
+}
+
+/// Read and hash files with `workers` threads
+///
+/// # Returns
+///
+/// The blobs in the order of `pending_blobs`, whatever the order they are read in
+fn read_pending_blobs(pending_blobs: &[PendingBlob], workers: usize) -> Vec<Result<Blob, RepTreeError>> {
+    let next = AtomicUsize::new(0);
+    let mut blobs: Vec<(usize, Result<Blob, RepTreeError>)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers.min(pending_blobs.len()))
+            .map(|_| scope.spawn(|| {
+                let mut blobs = vec![];
+                loop {
+                    let position = next.fetch_add(1, Ordering::Relaxed);
+                    match pending_blobs.get(position) {
+                        Some(pending_blob) => blobs.push((position, read_blob(&pending_blob.name, &pending_blob.file_path))),
+                        None => return blobs
+                    }
+                }
+            }))
+            .collect();
+        handles.into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    });
+    blobs.sort_by_key(|(position, _)| *position);
+    blobs.into_iter().map(|(_, blob)| blob).collect()
+}
+
 /// Return list of elements which composed specified path
 ///
 /// # Arguments
@@ -53,7 +261,7 @@ fn get_elements(path: &PathBuf) -> Vec<&Path>{
 /// # Arguments
 ///
 /// * 'node' - `TreeNode` to which the rest of the tree will be added
-fn add_node_to_repository_tree(node: &mut Node, paths: &mut Vec<&Path>) -> Result<(), RepTreeError>{
+fn add_node_to_repository_tree(node: &mut Node, paths: &mut Vec<&Path>, ingestion: &mut Ingestion) -> Result<(), RepTreeError>{
     if paths.is_empty() {
         return Ok(())
     }
@@ -66,40 +274,171 @@ fn add_node_to_repository_tree(node: &mut Node, paths: &mut Vec<&Path>) -> Resul
             RepTreeError::UnexpectedComportment(format!("Failed to get file name: {:?}", element_path))
         })?;
 
-    if element_path.is_dir() {
-        _add_tree_node_to_repository_tree(node, paths, file_name)?;
+    if paths.len() == 1 && ingestion.options.symlinks == SymlinkMode::Record && element_path.is_symlink() {
+        _add_symlink_node_to_repository_tree(node, element_path, file_name)?;
+    } else if element_path.is_dir() {
+        _add_tree_node_to_repository_tree(node, paths, file_name, ingestion)?;
     } else if element_path.is_file() {
-        _add_blob_node_to_repository_tree(node, element_path, file_name)?;
+        _add_blob_node_to_repository_tree(node, element_path, file_name, ingestion)?;
     }
-    
-    node.generate_id();
 
     Ok(())
 }
 
-fn _add_tree_node_to_repository_tree(node: &mut Node, paths: &mut Vec<&Path>, file_name: &str) -> Result<(), RepTreeError> {
+fn _add_tree_node_to_repository_tree(node: &mut Node, paths: &mut Vec<&Path>, file_name: &str, ingestion: &mut Ingestion) -> Result<(), RepTreeError> {
     let mut tree_node: Node = Node::create_tree_node(file_name.to_string(), vec![], node.get_path().join(file_name));
+    let element_path = paths.remove(0);
+    let walk = paths.is_empty() && ingestion.options.recursive;
 
-    if let Some(mut already_existing_node) = node.find_child(&tree_node) {
-        paths.remove(0);
-        add_node_to_repository_tree(&mut already_existing_node, paths)?;
+    if let Some(already_existing_node) = node.find_child(&tree_node) {
+        add_node_to_repository_tree(already_existing_node, paths, ingestion)?;
+        if walk {
+            add_directory_content(already_existing_node, element_path, 1, ingestion)?;
+        }
     } else {
-        paths.remove(0);
-        add_node_to_repository_tree(&mut tree_node, paths)?;
+        add_node_to_repository_tree(&mut tree_node, paths, ingestion)?;
+        if walk {
+            add_directory_content(&mut tree_node, element_path, 1, ingestion)?;
+        }
         node.add_node_to_tree_node(tree_node);
     }
 
     Ok(())
 }
 
-fn _add_blob_node_to_repository_tree(node: &mut Node, element_path: &Path, file_name: &str) -> Result<(), RepTreeError> {
-    let mut blob: Blob = Blob::new(file_name.to_string(), "".to_string());
-    blob.set_content_from_file(element_path)?;
+/// Add everything under `directory` to `node`, `depth` being the depth of its children below
+/// the directory passed by the caller
+fn add_directory_content(node: &mut Node, directory: &Path, depth: usize, ingestion: &mut Ingestion) -> Result<(), RepTreeError> {
+    if ingestion.options.max_depth.is_some_and(|max_depth| depth > max_depth) {
+        return Ok(());
+    }
+    let canonical_directory = fs::canonicalize(directory).map_err(RepTreeError::IoError)?;
+    if ingestion.walked_directories.contains(&canonical_directory) {
+        return Ok(());
+    }
+    ingestion.walked_directories.push(canonical_directory);
+
+    for entry in read_directory(directory)? {
+        if let Some(ignore_rules) = ingestion.options.ignore_rules.as_mut() {
+            if ignore_rules.is_ignored_file(&entry.path, entry.is_dir)? {
+                continue;
+            }
+        }
+        if entry.is_symlink && ingestion.options.symlinks == SymlinkMode::Record {
+            _add_symlink_node_to_repository_tree(node, &entry.path, &entry.name)?;
+        } else if entry.is_dir {
+            if entry.is_symlink {
+                let target = fs::canonicalize(&entry.path).map_err(RepTreeError::IoError)?;
+                if ingestion.walked_directories.contains(&target) {
+                    continue;
+                }
+            }
+            let mut tree_node: Node = Node::create_tree_node(entry.name.clone(), vec![], node.get_path().join(&entry.name));
+            match node.find_child(&tree_node) {
+                Some(already_existing_node) => add_directory_content(already_existing_node, &entry.path, depth + 1, ingestion)?,
+                None => {
+                    add_directory_content(&mut tree_node, &entry.path, depth + 1, ingestion)?;
+                    node.add_node_to_tree_node(tree_node);
+                }
+            }
+        } else if entry.path.is_file() {
+            _add_blob_node_to_repository_tree(node, &entry.path, &entry.name, ingestion)?;
+        }
+    }
+
+    ingestion.walked_directories.pop();
+    Ok(())
+}
+
+/// Add a symbolic link as a blob whose content is the path it points to
+fn _add_symlink_node_to_repository_tree(node: &mut Node, element_path: &Path, file_name: &str) -> Result<(), RepTreeError> {
+    let target = fs::read_link(element_path).map_err(RepTreeError::IoError)?;
+    let mut blob: Blob = Blob::new(file_name.to_string(), target.to_string_lossy().as_bytes());
+    blob.set_mode(EntryMode::Symlink);
+    blob.generate_id();
     blob.set_path(node.get_path().join(file_name));
     node.add_node_to_tree_node(BlobNode(blob));
     Ok(())
 }
 
+fn _add_blob_node_to_repository_tree(node: &mut Node, element_path: &Path, file_name: &str, ingestion: &mut Ingestion) -> Result<(), RepTreeError> {
+    let blob_path = node.get_path().join(file_name);
+    let file_metadata = fs::metadata(element_path).map_err(RepTreeError::IoError)?;
+    let mode = EntryMode::from_metadata(&file_metadata);
+    let metadata = ingestion.index.as_ref().map(|_| file_metadata);
+    let unchanged_blob_id = ingestion.index.as_deref()
+        .zip(metadata.as_ref())
+        .and_then(|(index, metadata)| index.get_unchanged_blob_id(&blob_path, metadata));
+
+    let mut blob: Blob = match unchanged_blob_id {
+        Some(id) => Blob::new_unloaded(file_name.to_string(), id, element_path.to_path_buf()),
+        None if ingestion.options.workers > 1 => {
+            ingestion.pending_blobs.push(PendingBlob {
+                name: file_name.to_string(),
+                blob_path: blob_path.clone(),
+                file_path: element_path.to_path_buf(),
+                mode,
+                metadata
+            });
+            Blob::new(file_name.to_string(), "".to_string())
+        },
+        None => {
+            let blob = read_blob(file_name, element_path)?;
+            if let (Some(index), Some(metadata)) = (ingestion.index.as_deref_mut(), metadata.as_ref()) {
+                index.update_entry(&blob_path, metadata, &blob.get_id());
+            }
+            blob
+        }
+    };
+    blob.set_path(blob_path);
+    blob.set_mode(mode);
+    node.add_node_to_tree_node(BlobNode(blob));
+    Ok(())
+}
+
+fn read_blob(file_name: &str, file_path: &Path) -> Result<Blob, RepTreeError> {
+    let mut blob: Blob = Blob::new(file_name.to_string(), "".to_string());
+    blob.set_content_from_file(file_path)?;
+    Ok(blob)
+}
+
+/// Entry of a directory on disk
+pub(crate) struct DirectoryEntry {
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+    pub(crate) is_dir: bool,
+    pub(crate) is_symlink: bool,
+}
+
+/// List the files and directories of `directory`, sorted by name
+///
+/// Entries which are neither files nor directories are skipped, as when creating a tree,
+/// except symbolic links which are kept even when broken. `is_dir` tells if the entry is a
+/// directory or a link to a directory.
+///
+/// # Arguments
+///
+/// * `directory` - path of the directory
+///
+/// # Returns
+///
+/// Result with the entries, or `RepTreeError` if the directory can't be read
+pub(crate) fn read_directory(directory: &Path) -> Result<Vec<DirectoryEntry>, RepTreeError> {
+    let mut entries = Vec::new();
+    for dir_entry in fs::read_dir(directory).map_err(RepTreeError::IoError)? {
+        let path = dir_entry.map_err(RepTreeError::IoError)?.path();
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+            continue;
+        };
+        let is_symlink = path.is_symlink();
+        if is_symlink || path.is_dir() || path.is_file() {
+            entries.push(DirectoryEntry { name, is_dir: path.is_dir(), path, is_symlink });
+        }
+    }
+    entries.sort_by(|entry1, entry2| entry1.name.cmp(&entry2.name));
+    Ok(entries)
+}
+
 #[ctor::ctor]
 fn setup(){
     let dir_path = ".tmp/foo/feat";
@@ -122,6 +461,8 @@ mod tests {
     use std::fs;
     use std::fs::File;
     use std::io::Write;
+    use crate::features::transcript_repository_tree_to_object_files::transcript_repository_to_object_store;
+    use crate::store::memory_store::MemoryStore;
     use super::*;
 
 
@@ -144,7 +485,7 @@ mod tests {
             &Path::new(".tmp"),
         ];
 
-        let result = add_node_to_repository_tree(&mut root_node, &mut paths);
+        let result = add_node_to_repository_tree(&mut root_node, &mut paths, &mut Ingestion::default());
 
         let binding = root_node.get_children().unwrap();
         let child: &Node = binding.get(0).unwrap();
@@ -164,7 +505,7 @@ mod tests {
             &Path::new("tmp1"),
         ];
 
-        let result = add_node_to_repository_tree(&mut root_node, &mut paths);
+        let result = add_node_to_repository_tree(&mut root_node, &mut paths, &mut Ingestion::default());
 
         let binding = root_node.get_children().unwrap();
         let child: &Node = binding.get(0).unwrap();
@@ -187,10 +528,10 @@ mod tests {
             &Path::new("tmp2"),
         ];
 
-        let _ = add_node_to_repository_tree(&mut root_node, &mut paths);
+        let _ = add_node_to_repository_tree(&mut root_node, &mut paths, &mut Ingestion::default());
 
         file.write_all(b", Everybody").unwrap();
-        let result = add_node_to_repository_tree(&mut root_node, &mut paths);
+        let result = add_node_to_repository_tree(&mut root_node, &mut paths, &mut Ingestion::default());
 
         let binding = root_node.get_children().unwrap();
         let child: &Node = binding.get(0).unwrap();
@@ -201,6 +542,164 @@ mod tests {
         assert_eq!(root_node.get_children().unwrap().len(), 1);
         assert_eq!(child.get_name(), "tmp2");
         assert!(child.is_blob());
-        assert_eq!(child.get_content().unwrap(), "Hello, World, Everybody");
+        assert_eq!(child.get_content().unwrap(), b"Hello, World, Everybody");
+    }
+
+    #[test]
+    fn should_reuse_blob_ids_from_index() {
+        fs::create_dir_all("tmp_indexed").unwrap();
+        fs::write("tmp_indexed/a.txt", "a").unwrap();
+        // Older than the index, whatever the timestamp granularity of the file system
+        let past = std::time::SystemTime::now() - std::time::Duration::from_secs(10);
+        File::options().write(true).open("tmp_indexed/a.txt").unwrap().set_modified(past).unwrap();
+        let paths = vec![PathBuf::from("tmp_indexed/a.txt")];
+
+        let mut index = Index::new();
+        let first = create_repository_tree_with_index(Tree::default(), paths.clone(), &mut index).unwrap();
+        index.write_to_file(Path::new("tmp_indexed/index")).unwrap();
+        let second = create_repository_tree_with_index(Tree::default(), paths, &mut index).unwrap();
+
+        let blob_path = Path::new("tmp_indexed/a.txt");
+        let is_loaded = |node: &Node| match node.get_node_at_path(blob_path) {
+            Some(BlobNode(blob)) => blob.is_loaded(),
+            _ => panic!("missing blob"),
+        };
+        let loaded = (is_loaded(&first), is_loaded(&second));
+        let content = second.get_node_at_path(blob_path).unwrap().get_content();
+        // Modified after the blob reused its id from the index
+        fs::write("tmp_indexed/a.txt", "b").unwrap();
+        let modified_content = second.get_node_at_path(blob_path).unwrap().get_content();
+        let stored = transcript_repository_to_object_store(&second, &mut MemoryStore::new());
+        fs::remove_dir_all("tmp_indexed").unwrap();
+
+        assert_eq!(index.get_entry(blob_path).unwrap().get_blob_id(), first.get_node_at_path(blob_path).unwrap().get_id());
+        assert_eq!(loaded, (true, false));
+        assert_eq!(first.get_id(), second.get_id());
+        assert_eq!(content.unwrap(), b"a");
+        assert!(matches!(modified_content, Err(RepTreeError::UnexpectedComportment(_))));
+        assert!(stored.is_err());
+    }
+
+    #[test]
+    fn should_add_directory_content_up_to_max_depth() {
+        fs::create_dir_all("tmp_walked/a/b").unwrap();
+        fs::write("tmp_walked/top.txt", "top").unwrap();
+        fs::write("tmp_walked/a/middle.txt", "middle").unwrap();
+        fs::write("tmp_walked/a/b/bottom.txt", "bottom").unwrap();
+        let paths = vec![PathBuf::from("tmp_walked")];
+
+        let complete = create_repository_tree_with_options(Tree::default(), paths.clone(), &CreateOptions::default(), None).unwrap();
+        let options = CreateOptions { max_depth: Some(2), ..CreateOptions::default() };
+        let limited = create_repository_tree_with_options(Tree::default(), paths.clone(), &options, None).unwrap();
+        let explicit = create_repository_tree(Tree::default(), paths).unwrap();
+        fs::remove_dir_all("tmp_walked").unwrap();
+
+        assert_eq!(complete.get_node_at_path(Path::new("tmp_walked/a/b/bottom.txt")).unwrap().get_content().unwrap(), b"bottom");
+        assert!(limited.get_node_at_path(Path::new("tmp_walked/a/middle.txt")).is_some());
+        assert!(limited.get_node_at_path(Path::new("tmp_walked/a/b")).unwrap().is_tree());
+        assert!(limited.get_node_at_path(Path::new("tmp_walked/a/b/bottom.txt")).is_none());
+        assert!(explicit.get_node_at_path(Path::new("tmp_walked/top.txt")).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_record_or_follow_symlinks() {
+        fs::create_dir_all("tmp_linked/dir").unwrap();
+        fs::write("tmp_linked/dir/file.txt", "file").unwrap();
+        std::os::unix::fs::symlink("dir/file.txt", "tmp_linked/link.txt").unwrap();
+        std::os::unix::fs::symlink("..", "tmp_linked/dir/parent").unwrap();
+        let paths = vec![PathBuf::from("tmp_linked")];
+
+        let recorded = create_repository_tree_with_options(Tree::default(), paths.clone(), &CreateOptions::default(), None).unwrap();
+        let options = CreateOptions { symlinks: SymlinkMode::Follow, ..CreateOptions::default() };
+        let followed = create_repository_tree_with_options(Tree::default(), paths, &options, None).unwrap();
+        fs::remove_dir_all("tmp_linked").unwrap();
+
+        assert_eq!(recorded.get_node_at_path(Path::new("tmp_linked/link.txt")).unwrap().get_content().unwrap(), b"dir/file.txt");
+        assert_eq!(recorded.get_node_at_path(Path::new("tmp_linked/dir/parent")).unwrap().get_content().unwrap(), b"..");
+        assert_eq!(followed.get_node_at_path(Path::new("tmp_linked/link.txt")).unwrap().get_content().unwrap(), b"file");
+        assert!(followed.get_node_at_path(Path::new("tmp_linked/dir/parent")).is_none());
+    }
+
+    #[test]
+    fn should_skip_ignored_paths() {
+        fs::create_dir_all("tmp_ignored/target/debug").unwrap();
+        fs::create_dir_all("tmp_ignored/src").unwrap();
+        fs::write("tmp_ignored/.ditignore", "target/\n*.swp\n").unwrap();
+        fs::write("tmp_ignored/target/debug/app", "binary").unwrap();
+        fs::write("tmp_ignored/src/main.rs", "fn main() {}").unwrap();
+        fs::write("tmp_ignored/src/.main.rs.swp", "swap").unwrap();
+        let paths = vec![PathBuf::from("tmp_ignored")];
+
+        let root = create_repository_tree_with_options(Tree::default(), paths.clone(), &CreateOptions::default(), None).unwrap();
+        let options = CreateOptions { ignore_rules: None, ..CreateOptions::default() };
+        let unfiltered = create_repository_tree_with_options(Tree::default(), paths, &options, None).unwrap();
+        let swap_file = PathBuf::from("tmp_ignored/src/.main.rs.swp");
+        let absolute_directory = std::env::current_dir().unwrap().join("tmp_ignored/src");
+        let explicit = create_repository_tree(Tree::default(), vec![swap_file.clone()]).unwrap();
+        let absolute = create_repository_tree_with_options(Tree::default(), vec![absolute_directory.clone()], &CreateOptions::default(), None).unwrap();
+        let options = CreateOptions { force: true, ..CreateOptions::default() };
+        let forced = create_repository_tree_with_options(Tree::default(), vec![swap_file.clone()], &options, None).unwrap();
+        fs::remove_dir_all("tmp_ignored").unwrap();
+
+        assert!(root.get_node_at_path(Path::new("tmp_ignored/.ditignore")).is_some());
+        assert!(root.get_node_at_path(Path::new("tmp_ignored/src/main.rs")).is_some());
+        assert!(root.get_node_at_path(Path::new("tmp_ignored/src/.main.rs.swp")).is_none());
+        assert!(root.get_node_at_path(Path::new("tmp_ignored/target")).is_none());
+        assert!(unfiltered.get_node_at_path(Path::new("tmp_ignored/target/debug/app")).is_some());
+        assert!(explicit.get_children_ref().unwrap().is_empty());
+        // Absolute paths are added below their root, without it
+        let absolute_path: PathBuf = absolute_directory.components()
+            .filter(|component| matches!(component, std::path::Component::Normal(_)))
+            .collect();
+        let absolute_src = absolute.get_node_at_path(&absolute_path).unwrap();
+        assert_eq!(absolute_src.get_children_ref().unwrap().iter().map(|node| node.get_name()).collect::<Vec<_>>(), vec!["main.rs"]);
+        assert!(forced.get_node_at_path(&swap_file).is_some());
+    }
+
+    #[test]
+    fn should_use_global_exclude_file_only_when_given() {
+        fs::create_dir_all("tmp_global_ignored").unwrap();
+        fs::write("tmp_global_ignored/main.rs", "fn main() {}").unwrap();
+        fs::write("tmp_global_ignored/.DS_Store", "finder").unwrap();
+        fs::write("tmp_global_exclude", ".DS_Store\n").unwrap();
+        let paths = vec![PathBuf::from("tmp_global_ignored")];
+
+        let default = create_repository_tree_with_options(Tree::default(), paths.clone(), &CreateOptions::default(), None).unwrap();
+        let mut ignore_rules = IgnoreRules::new(Path::new(""));
+        ignore_rules.add_global_exclude_file(Path::new("tmp_global_exclude")).unwrap();
+        let options = CreateOptions { ignore_rules: Some(ignore_rules), ..CreateOptions::default() };
+        let excluded = create_repository_tree_with_options(Tree::default(), paths, &options, None).unwrap();
+        fs::remove_dir_all("tmp_global_ignored").unwrap();
+        fs::remove_file("tmp_global_exclude").unwrap();
+
+        assert!(default.get_node_at_path(Path::new("tmp_global_ignored/.DS_Store")).is_some());
+        assert!(excluded.get_node_at_path(Path::new("tmp_global_ignored/.DS_Store")).is_none());
+        assert!(excluded.get_node_at_path(Path::new("tmp_global_ignored/main.rs")).is_some());
+    }
+
+    #[test]
+    fn should_build_same_tree_with_workers() {
+        for i in 0..20 {
+            let directory = format!("tmp_parallel/d{}", i % 4);
+            fs::create_dir_all(&directory).unwrap();
+            fs::write(format!("{}/f{}.txt", directory, i), "x".repeat(i * 100)).unwrap();
+        }
+        let paths = vec![PathBuf::from("tmp_parallel")];
+        let options = CreateOptions { workers: 4, ..CreateOptions::default() };
+
+        let sequential = create_repository_tree_with_options(Tree::default(), paths.clone(), &CreateOptions::default(), None).unwrap();
+        let parallel = create_repository_tree_with_options(Tree::default(), paths.clone(), &options, None).unwrap();
+        let mut index = Index::new();
+        let indexed = create_repository_tree_with_options(Tree::default(), paths, &options, Some(&mut index)).unwrap();
+        fs::remove_dir_all("tmp_parallel").unwrap();
+
+        let blob_path = Path::new("tmp_parallel/d3/f7.txt");
+        assert_eq!(parallel.get_id(), sequential.get_id());
+        assert_eq!(indexed.get_id(), sequential.get_id());
+        assert_eq!(parallel.get_node_at_path(blob_path).unwrap().get_content().unwrap(), "x".repeat(700).as_bytes());
+        assert_eq!(parallel.get_node_at_path(blob_path).unwrap().get_path(), blob_path);
+        assert_eq!(index.len(), 20);
+        assert_eq!(index.get_entry(blob_path).unwrap().get_blob_id(), sequential.get_node_at_path(blob_path).unwrap().get_id());
     }
 }
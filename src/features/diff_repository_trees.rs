@@ -0,0 +1,599 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::RepTreeError;
+use crate::features::get_repository_tree_from_object_files::read_tree_entries;
+use crate::models::entry_mode::EntryMode;
+use crate::models::node::Node;
+use crate::models::tree::TREE;
+use crate::store::file_system_store::FileSystemStore;
+use crate::store::object_store::ObjectStore;
+
+/// Kind of change found between two repository trees.
+///
+/// - `Added`: the entry only exists in the new tree.
+/// - `Deleted`: the entry only exists in the old tree.
+/// - `Modified`: the file exists in both trees with a different content or mode.
+/// - `TypeChanged`: a file became a directory or a directory became a file.
+/// - `Renamed`: the file was moved from `old_path`, possibly with modifications.
+/// - `Copied`: the file was added as a copy of `old_path`, possibly with modifications.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    Modified,
+    TypeChanged,
+    Renamed,
+    Copied,
+}
+
+/// Change of a single entry, `path` being relative to the compared roots.
+///
+/// Added and deleted directories are reported file by file, an empty directory being
+/// reported by itself. `old_id` is `None` for an addition, `new_id` is `None` for a deletion.
+/// `old_path` is the source of a rename or a copy, `None` for other changes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub path: PathBuf,
+    pub old_id: Option<String>,
+    pub new_id: Option<String>,
+    pub old_path: Option<PathBuf>,
+}
+
+impl Change {
+    fn new(kind: ChangeKind, path: &Path, old_id: Option<String>, new_id: Option<String>) -> Change {
+        Change { kind, path: path.to_path_buf(), old_id, new_id, old_path: None }
+    }
+}
+
+/// Options of `diff_repository_trees_with_options`.
+///
+/// With `detect_renames`, a deleted file and an added file are reported as a rename when they
+/// have the same id, or when at least `similarity_threshold` percent of their lines are
+/// common. With `detect_copies`, an added file is reported as a copy when it matches a
+/// modified or deleted file the same way. Empty files are never paired.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffOptions {
+    pub detect_renames: bool,
+    pub detect_copies: bool,
+    pub similarity_threshold: u8,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            detect_renames: true,
+            detect_copies: false,
+            similarity_threshold: 50,
+        }
+    }
+}
+
+/// Entry of a tree, either backed by an in-memory `Node` or only known by its id in a store
+pub(crate) struct Entry<'a> {
+    pub(crate) name: String,
+    pub(crate) id: String,
+    pub(crate) is_tree: bool,
+    pub(crate) mode: EntryMode,
+    pub(crate) node: Option<&'a Node>,
+}
+
+impl<'a> Entry<'a> {
+    pub(crate) fn from_node(node: &'a Node) -> Entry<'a> {
+        Entry {
+            name: node.get_name(),
+            id: node.get_id(),
+            is_tree: node.is_tree(),
+            mode: node.get_mode(),
+            node: Some(node),
+        }
+    }
+
+    pub(crate) fn from_object(id: &str) -> Entry<'a> {
+        Entry {
+            name: "".to_string(),
+            id: id.to_string(),
+            is_tree: true,
+            mode: EntryMode::Directory,
+            node: None,
+        }
+    }
+}
+
+/// Where the children of entries which aren't backed by a `Node` are read from
+pub(crate) enum Source<'a> {
+    Nodes,
+    Store(&'a dyn ObjectStore),
+}
+
+/// Entries of a same name in the old and new trees, a name being allowed once per type
+#[derive(Default)]
+struct Slots<'a> {
+    old_blob: Option<Entry<'a>>,
+    old_tree: Option<Entry<'a>>,
+    new_blob: Option<Entry<'a>>,
+    new_tree: Option<Entry<'a>>,
+}
+
+/// Compare two repository trees
+///
+/// Subtrees having the same id in both trees are skipped without being walked.
+///
+/// # Arguments
+///
+/// * `old` - root `Node` of the old tree
+/// * `new` - root `Node` of the new tree
+///
+/// # Returns
+///
+/// Changes sorted by path
+pub fn diff_repository_trees(old: &Node, new: &Node) -> Vec<Change> {
+    let mut changes: Vec<Change> = Vec::new();
+    // Children of in-memory nodes are always available, so walking them can't fail
+    let _ = diff_entries(Some(&Entry::from_node(old)), Some(&Entry::from_node(new)), Path::new(""), &Source::Nodes, &mut changes);
+    changes
+}
+
+/// Compare two repository trees, detecting renamed and copied files
+///
+/// A file whose content can't be read, see `Blob::get_content`, isn't detected as renamed or
+/// copied.
+///
+/// # Arguments
+///
+/// * `old` - root `Node` of the old tree
+/// * `new` - root `Node` of the new tree
+/// * `options` - how renames and copies are detected
+///
+/// # Returns
+///
+/// Changes sorted by path, renames and copies being sorted by their new path
+pub fn diff_repository_trees_with_options(old: &Node, new: &Node, options: &DiffOptions) -> Vec<Change> {
+    let changes = diff_repository_trees(old, new);
+    let read_content = |change: &Change, side: Side| {
+        let root = if side == Side::Old { old } else { new };
+        root.get_node_at_path(&change.path)
+            .filter(|node| node.is_blob())
+            .and_then(|node| node.get_content().ok())
+    };
+    detect_renames(changes, options, &read_content)
+}
+
+/// Compare two trees stored in an `ObjectStore`, detecting renamed and copied files
+///
+/// Blob objects are only read for the files added, deleted or modified.
+pub fn diff_repository_trees_from_object_store_with_options(old_id: &str, new_id: &str, store: &dyn ObjectStore, options: &DiffOptions) -> Result<Vec<Change>, RepTreeError> {
+    let changes = diff_repository_trees_from_object_store(old_id, new_id, store)?;
+    let read_content = |change: &Change, side: Side| {
+        let id = if side == Side::Old { &change.old_id } else { &change.new_id };
+        id.as_ref().and_then(|id| store.get(id).ok())
+    };
+    Ok(detect_renames(changes, options, &read_content))
+}
+
+/// Compare two trees stored in an `ObjectStore`
+///
+/// Tree objects are read lazily, so subtrees having the same id on both sides are never read.
+///
+/// # Arguments
+///
+/// * `old_id` - id of the old root tree
+/// * `new_id` - id of the new root tree
+/// * `store` - store containing the objects
+///
+/// # Returns
+///
+/// Result with the changes sorted by path, or `RepTreeError` if a tree object can't be read
+pub fn diff_repository_trees_from_object_store(old_id: &str, new_id: &str, store: &dyn ObjectStore) -> Result<Vec<Change>, RepTreeError> {
+    let mut changes: Vec<Change> = Vec::new();
+    diff_entries(Some(&Entry::from_object(old_id)), Some(&Entry::from_object(new_id)), Path::new(""), &Source::Store(store), &mut changes)?;
+    Ok(changes)
+}
+
+/// Compare two trees stored in the object files located at `object_path`
+pub fn diff_repository_trees_from_object_files(old_id: &str, new_id: &str, object_path: &Path) -> Result<Vec<Change>, RepTreeError> {
+    let store = FileSystemStore::new(object_path.to_path_buf());
+    diff_repository_trees_from_object_store(old_id, new_id, &store)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Old,
+    New,
+}
+
+/// Percentage of lines two contents have in common, 100 meaning identical
+fn similarity(old: &[u8], new: &[u8]) -> u8 {
+    if old == new {
+        return 100;
+    }
+    let old_lines: Vec<&[u8]> = old.split_inclusive(|c| *c == b'\n').collect();
+    let new_lines: Vec<&[u8]> = new.split_inclusive(|c| *c == b'\n').collect();
+    let common = diff::slice(&old_lines, &new_lines)
+        .iter()
+        .filter(|result| matches!(result, diff::Result::Both(_, _)))
+        .count();
+    // Identical contents were handled above, so the score stays below 100
+    ((common * 200) / (old_lines.len() + new_lines.len())).min(99) as u8
+}
+
+/// Files which may be paired, with their position among changes and their content
+type Candidates = Vec<(usize, Vec<u8>)>;
+
+/// Pair added files with the most similar sources
+///
+/// # Arguments
+///
+/// * `paired_added` - positions of the added files already paired, updated with the new pairs
+/// * `used_sources` - positions of the sources already paired, `None` if a source can be
+///   paired several times
+///
+/// # Returns
+///
+/// Pairs of positions of the added files and of their sources
+fn pair_files(
+    changes: &[Change],
+    added: &Candidates,
+    sources: &Candidates,
+    threshold: u8,
+    paired_added: &mut Vec<usize>,
+    mut used_sources: Option<&mut Vec<usize>>
+) -> Vec<(usize, usize)> {
+    let mut scores: Vec<(u8, usize, usize)> = Vec::new();
+    for (added_position, added_content) in added.iter().filter(|(position, _)| !paired_added.contains(position)) {
+        for (source_position, source_content) in sources.iter() {
+            let new_id = changes[*added_position].new_id.as_deref().unwrap_or_default();
+            let same_id = !new_id.is_empty() && changes[*source_position].old_id.as_deref() == Some(new_id);
+            let score = if same_id { 100 } else { similarity(source_content, added_content) };
+            if score >= threshold {
+                scores.push((score, *added_position, *source_position));
+            }
+        }
+    }
+    // Best scores first, then files keeping their name, then by path for a stable result
+    let same_name = |added: usize, source: usize| changes[added].path.file_name() == changes[source].path.file_name();
+    scores.sort_by(|(score1, added1, source1), (score2, added2, source2)| {
+        score2.cmp(score1)
+            .then(same_name(*added2, *source2).cmp(&same_name(*added1, *source1)))
+            .then(added1.cmp(added2))
+            .then(source1.cmp(source2))
+    });
+
+    let mut pairs = Vec::new();
+    for (_, added_position, source_position) in scores {
+        if paired_added.contains(&added_position) {
+            continue;
+        }
+        if let Some(used_sources) = used_sources.as_mut() {
+            if used_sources.contains(&source_position) {
+                continue;
+            }
+            used_sources.push(source_position);
+        }
+        paired_added.push(added_position);
+        pairs.push((added_position, source_position));
+    }
+    pairs
+}
+
+/// Pair deleted and added files into renames, then added files with their sources into copies
+fn detect_renames(changes: Vec<Change>, options: &DiffOptions, read_content: &dyn Fn(&Change, Side) -> Option<Vec<u8>>) -> Vec<Change> {
+    if !options.detect_renames && !options.detect_copies {
+        return changes;
+    }
+
+    let load = |kind: ChangeKind, side: Side| -> Candidates {
+        changes.iter()
+            .enumerate()
+            .filter(|(_, change)| change.kind == kind)
+            .filter_map(|(position, change)| Some((position, read_content(change, side)?)))
+            .filter(|(_, content)| !content.is_empty())
+            .collect()
+    };
+    let added = load(ChangeKind::Added, Side::New);
+    let deleted = load(ChangeKind::Deleted, Side::Old);
+    let mut paired_added: Vec<usize> = Vec::new();
+    let mut paired_deleted: Vec<usize> = Vec::new();
+
+    let mut pairs: Vec<(usize, usize, ChangeKind)> = Vec::new();
+    if options.detect_renames {
+        let renames = pair_files(&changes, &added, &deleted, options.similarity_threshold, &mut paired_added, Some(&mut paired_deleted));
+        pairs.extend(renames.into_iter().map(|(added, source)| (added, source, ChangeKind::Renamed)));
+    }
+    if options.detect_copies {
+        let mut sources = deleted.clone();
+        sources.extend(load(ChangeKind::Modified, Side::Old));
+        let copies = pair_files(&changes, &added, &sources, options.similarity_threshold, &mut paired_added, None);
+        pairs.extend(copies.into_iter().map(|(added, source)| (added, source, ChangeKind::Copied)));
+    }
+
+    let mut result: Vec<Change> = Vec::new();
+    for (position, change) in changes.iter().enumerate() {
+        if paired_deleted.contains(&position) {
+            continue;
+        }
+        match pairs.iter().find(|(added_position, _, _)| *added_position == position) {
+            Some((_, source_position, kind)) => {
+                let source = &changes[*source_position];
+                result.push(Change {
+                    kind: *kind,
+                    path: change.path.clone(),
+                    old_id: source.old_id.clone(),
+                    new_id: change.new_id.clone(),
+                    old_path: Some(source.path.clone()),
+                });
+            },
+            None => result.push(change.clone()),
+        }
+    }
+    result
+}
+
+pub(crate) fn get_children<'a>(entry: &Entry<'a>, source: &Source<'a>) -> Result<Vec<Entry<'a>>, RepTreeError> {
+    match (entry.node, source) {
+        (Some(node), _) => Ok(node
+            .get_children_ref()
+            .unwrap_or(&[])
+            .iter()
+            .map(Entry::from_node)
+            .collect()),
+        (None, Source::Store(store)) => Ok(read_tree_entries(&entry.id, *store)?
+            .into_iter()
+            .map(|(node_type, mode, id, name)| Entry { name, id, is_tree: node_type == TREE, mode, node: None })
+            .collect()),
+        (None, Source::Nodes) => Ok(vec![]),
+    }
+}
+
+fn is_same_blob(old: &Entry, new: &Entry) -> bool {
+    if !old.id.is_empty() && !new.id.is_empty() {
+        return old.id == new.id;
+    }
+    match (old.node, new.node) {
+        (Some(old_node), Some(new_node)) => matches!((old_node.get_content(), new_node.get_content()), (Ok(old_content), Ok(new_content)) if old_content == new_content),
+        _ => old.id == new.id,
+    }
+}
+
+fn to_option(id: &str) -> Option<String> {
+    Some(id.to_string())
+}
+
+fn diff_entries<'a>(old: Option<&Entry<'a>>, new: Option<&Entry<'a>>, path: &Path, source: &Source<'a>, changes: &mut Vec<Change>) -> Result<(), RepTreeError> {
+    match (old, new) {
+        (Some(old), Some(new)) if old.is_tree && new.is_tree => {
+            if !old.id.is_empty() && old.id == new.id {
+                return Ok(());
+            }
+            diff_children(old, new, path, source, changes)
+        },
+        (Some(old), Some(new)) if !old.is_tree && !new.is_tree => {
+            if !is_same_blob(old, new) || old.mode != new.mode {
+                changes.push(Change::new(ChangeKind::Modified, path, to_option(&old.id), to_option(&new.id)));
+            }
+            Ok(())
+        },
+        (Some(old), Some(new)) => {
+            changes.push(Change::new(ChangeKind::TypeChanged, path, to_option(&old.id), to_option(&new.id)));
+            Ok(())
+        },
+        (Some(old), None) => record_subtree(old, path, ChangeKind::Deleted, source, changes),
+        (None, Some(new)) => record_subtree(new, path, ChangeKind::Added, source, changes),
+        (None, None) => Ok(()),
+    }
+}
+
+fn diff_children<'a>(old: &Entry<'a>, new: &Entry<'a>, path: &Path, source: &Source<'a>, changes: &mut Vec<Change>) -> Result<(), RepTreeError> {
+    let mut names: BTreeMap<String, Slots<'a>> = BTreeMap::new();
+    for child in get_children(old, source)? {
+        let slots = names.entry(child.name.clone()).or_default();
+        if child.is_tree { slots.old_tree = Some(child) } else { slots.old_blob = Some(child) }
+    }
+    for child in get_children(new, source)? {
+        let slots = names.entry(child.name.clone()).or_default();
+        if child.is_tree { slots.new_tree = Some(child) } else { slots.new_blob = Some(child) }
+    }
+
+    for (name, slots) in names.iter() {
+        let child_path = path.join(name);
+        let pair_blobs = slots.old_blob.is_some() && slots.new_blob.is_some();
+        let pair_trees = slots.old_tree.is_some() && slots.new_tree.is_some();
+        if pair_blobs {
+            diff_entries(slots.old_blob.as_ref(), slots.new_blob.as_ref(), &child_path, source, changes)?;
+        }
+        if pair_trees {
+            diff_entries(slots.old_tree.as_ref(), slots.new_tree.as_ref(), &child_path, source, changes)?;
+        }
+
+        let old_left: Vec<&Entry> = [(&slots.old_blob, pair_blobs), (&slots.old_tree, pair_trees)]
+            .into_iter()
+            .filter_map(|(entry, paired)| if paired { None } else { entry.as_ref() })
+            .collect();
+        let new_left: Vec<&Entry> = [(&slots.new_blob, pair_blobs), (&slots.new_tree, pair_trees)]
+            .into_iter()
+            .filter_map(|(entry, paired)| if paired { None } else { entry.as_ref() })
+            .collect();
+
+        if old_left.len() == 1 && new_left.len() == 1 {
+            diff_entries(Some(old_left[0]), Some(new_left[0]), &child_path, source, changes)?;
+        } else {
+            for entry in old_left {
+                diff_entries(Some(entry), None, &child_path, source, changes)?;
+            }
+            for entry in new_left {
+                diff_entries(None, Some(entry), &child_path, source, changes)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn record_subtree<'a>(entry: &Entry<'a>, path: &Path, kind: ChangeKind, source: &Source<'a>, changes: &mut Vec<Change>) -> Result<(), RepTreeError> {
+    let children = if entry.is_tree { get_children(entry, source)? } else { vec![] };
+
+    if children.is_empty() {
+        let (old_id, new_id) = match kind {
+            ChangeKind::Deleted => (to_option(&entry.id), None),
+            _ => (None, to_option(&entry.id)),
+        };
+        changes.push(Change::new(kind, path, old_id, new_id));
+        return Ok(());
+    }
+
+    let mut children = children;
+    children.sort_by(|child1, child2| child1.name.cmp(&child2.name));
+    for child in children.iter() {
+        record_subtree(child, &path.join(&child.name), kind, source, changes)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use dit_id_generator::traits::generator::Generator;
+
+    use crate::features::diff_repository_trees::{Change, ChangeKind, diff_repository_trees, diff_repository_trees_from_object_store, diff_repository_trees_from_object_store_with_options, diff_repository_trees_with_options, DiffOptions};
+    use crate::features::transcript_repository_tree_to_object_files::transcript_repository_to_object_store;
+    use crate::models::entry_mode::EntryMode;
+    use crate::models::node::Node;
+    use crate::store::memory_store::MemoryStore;
+    use crate::store::object_store::ObjectStore;
+
+    fn blob(name: &str, content: &str) -> Node {
+        Node::create_blob_node(name.to_string(), content, PathBuf::from(name))
+    }
+
+    fn tree(name: &str, children: Vec<Node>) -> Node {
+        let mut node = Node::create_tree_node(name.to_string(), children, PathBuf::from(name));
+        node.generate_id();
+        node
+    }
+
+    fn kinds(changes: &[Change]) -> Vec<(ChangeKind, PathBuf)> {
+        changes.iter().map(|change| (change.kind, change.path.clone())).collect()
+    }
+
+    fn snapshots() -> (Node, Node) {
+        let old = tree("", vec![
+            tree("src", vec![blob("main.rs", "fn main() {}"), blob("lib.rs", "pub mod a;")]),
+            tree("docs", vec![blob("guide.md", "# Guide")]),
+            blob("README.md", "Hello"),
+            blob("build", "#!/bin/sh"),
+        ]);
+        let new = tree("", vec![
+            tree("src", vec![blob("main.rs", "fn main() { run() }"), blob("lib.rs", "pub mod a;")]),
+            tree("docs", vec![blob("guide.md", "# Guide")]),
+            tree("build", vec![blob("run.sh", "#!/bin/sh")]),
+            tree("assets", vec![blob("logo.svg", "<svg/>"), tree("empty", vec![])]),
+        ]);
+        (old, new)
+    }
+
+    #[test]
+    fn should_diff_node_trees() {
+        let (old, new) = snapshots();
+
+        let changes = diff_repository_trees(&old, &new);
+
+        assert_eq!(kinds(&changes), vec![
+            (ChangeKind::Deleted, PathBuf::from("README.md")),
+            (ChangeKind::Added, PathBuf::from("assets/empty")),
+            (ChangeKind::Added, PathBuf::from("assets/logo.svg")),
+            (ChangeKind::TypeChanged, PathBuf::from("build")),
+            (ChangeKind::Modified, PathBuf::from("src/main.rs")),
+        ]);
+        assert!(changes[0].new_id.is_none());
+        assert!(changes[2].old_id.is_none());
+        assert_ne!(changes[4].old_id, changes[4].new_id);
+    }
+
+    #[test]
+    fn should_not_read_identical_subtrees_from_store() {
+        let (old, new) = snapshots();
+        let mut store = MemoryStore::new();
+        transcript_repository_to_object_store(&old, &mut store).unwrap();
+        transcript_repository_to_object_store(&new, &mut store).unwrap();
+
+        // `docs` is identical on both sides, diffing must not need its objects
+        let mut trimmed = MemoryStore::new();
+        let docs_id = old.get_children_ref().unwrap()[1].get_id();
+        for id in store.list().unwrap() {
+            if id != docs_id {
+                trimmed.put(&id, &store.get(&id).unwrap()).unwrap();
+            }
+        }
+
+        let changes = diff_repository_trees_from_object_store(&old.get_id(), &new.get_id(), &trimmed).unwrap();
+
+        assert_eq!(kinds(&changes), kinds(&diff_repository_trees(&old, &new)));
+    }
+
+    #[test]
+    fn should_detect_renames_and_copies() {
+        let lines = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let old = tree("", vec![
+            tree("src", vec![blob("a.rs", "pub fn a() {}\n"), blob("b.rs", lines), blob("c.rs", "c\n")]),
+            blob("gone.txt", "unrelated\n"),
+        ]);
+        let new = tree("", vec![
+            tree("src", vec![
+                tree("lib", vec![blob("a.rs", "pub fn a() {}\n")]),
+                blob("c.rs", "c\nc\n"),
+                blob("d.rs", "c\nc\n"),
+            ]),
+            blob("b.rs", "1\n2\n3\n4\n5\n6\n7\n8\n9\nten\n"),
+            blob("new.txt", "new\n"),
+        ]);
+        let options = DiffOptions { detect_copies: true, ..DiffOptions::default() };
+
+        let changes = diff_repository_trees_with_options(&old, &new, &options);
+
+        let summary: Vec<(ChangeKind, PathBuf, Option<PathBuf>)> = changes.iter()
+            .map(|change| (change.kind, change.path.clone(), change.old_path.clone()))
+            .collect();
+        assert_eq!(summary, vec![
+            (ChangeKind::Renamed, PathBuf::from("b.rs"), Some(PathBuf::from("src/b.rs"))),
+            (ChangeKind::Deleted, PathBuf::from("gone.txt"), None),
+            (ChangeKind::Added, PathBuf::from("new.txt"), None),
+            (ChangeKind::Modified, PathBuf::from("src/c.rs"), None),
+            (ChangeKind::Copied, PathBuf::from("src/d.rs"), Some(PathBuf::from("src/c.rs"))),
+            (ChangeKind::Renamed, PathBuf::from("src/lib/a.rs"), Some(PathBuf::from("src/a.rs"))),
+        ]);
+        assert_eq!(changes[5].old_id, changes[5].new_id);
+        assert_ne!(changes[0].old_id, changes[0].new_id);
+
+        let mut store = MemoryStore::new();
+        transcript_repository_to_object_store(&old, &mut store).unwrap();
+        transcript_repository_to_object_store(&new, &mut store).unwrap();
+        let stored_changes = diff_repository_trees_from_object_store_with_options(&old.get_id(), &new.get_id(), &store, &options).unwrap();
+        assert_eq!(stored_changes, changes);
+
+        let strict = DiffOptions { similarity_threshold: 100, ..DiffOptions::default() };
+        let strict_changes = diff_repository_trees_with_options(&old, &new, &strict);
+        assert_eq!(strict_changes.iter().filter(|change| change.kind == ChangeKind::Renamed).count(), 1);
+    }
+
+    #[test]
+    fn should_report_mode_changes() {
+        let old = tree("", vec![blob("run.sh", "#!/bin/sh")]);
+        let mut script = blob("run.sh", "#!/bin/sh");
+        if let Node::BlobNode(blob) = &mut script {
+            blob.set_mode(EntryMode::Executable);
+        }
+        let new = tree("", vec![script]);
+        let mut store = MemoryStore::new();
+        transcript_repository_to_object_store(&old, &mut store).unwrap();
+        transcript_repository_to_object_store(&new, &mut store).unwrap();
+
+        let changes = diff_repository_trees(&old, &new);
+        let stored_changes = diff_repository_trees_from_object_store(&old.get_id(), &new.get_id(), &store).unwrap();
+
+        assert_ne!(old.get_id(), new.get_id());
+        assert_eq!(kinds(&changes), vec![(ChangeKind::Modified, PathBuf::from("run.sh"))]);
+        assert_eq!(changes[0].old_id, changes[0].new_id);
+        assert_eq!(kinds(&stored_changes), kinds(&changes));
+    }
+}
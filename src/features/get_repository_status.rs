@@ -0,0 +1,355 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use dit_id_generator::traits::generator::Generator;
+
+use crate::error::RepTreeError;
+use crate::features::create_repository_tree::{DirectoryEntry, read_directory};
+use crate::features::diff_repository_trees::{Entry, get_children, Source};
+use crate::models::blob::Blob;
+use crate::models::entry_mode::EntryMode;
+use crate::models::ignore_rules::IgnoreRules;
+use crate::models::index::Index;
+use crate::models::node::Node;
+use crate::store::object_store::ObjectStore;
+
+/// Kind of difference between a directory on disk and a repository tree.
+///
+/// - `Untracked`: the entry only exists on disk.
+/// - `Modified`: the file exists in both with a different content or mode, see `EntryMode`.
+/// - `Deleted`: the entry only exists in the tree.
+/// - `TypeChanged`: a file became a directory or a directory became a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusKind {
+    Untracked,
+    Modified,
+    Deleted,
+    TypeChanged,
+}
+
+/// Status of a single entry, `path` being relative to the compared directory.
+///
+/// Untracked and deleted directories are reported file by file, an empty directory being
+/// reported by itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusEntry {
+    pub kind: StatusKind,
+    pub path: PathBuf,
+}
+
+/// Compare a directory on disk with a repository tree
+///
+/// A file is only read when its size is the same as the size of its blob, its content being
+/// then compared with the blob content. See `get_repository_status_with_index` to avoid
+/// reading unchanged files. Untracked paths matched by the `.ditignore` files of the directory
+/// aren't reported, the global exclude file being only used through
+/// `get_repository_status_with_ignore_rules` and `IgnoreRules::with_global_exclude_file`.
+///
+/// # Arguments
+///
+/// * `root` - root `Node` of the tree
+/// * `directory` - directory matching the root of the tree
+///
+/// # Returns
+///
+/// Result with the entries which differ sorted by path, or `RepTreeError` if the directory
+/// can't be read
+pub fn get_repository_status(root: &Node, directory: &Path) -> Result<Vec<StatusEntry>, RepTreeError> {
+    let mut entries = Vec::new();
+    compare_directory(&Entry::from_node(root), directory, Path::new(""), &Source::Nodes, None, &mut IgnoreRules::new(directory), &mut entries)?;
+    Ok(entries)
+}
+
+/// Compare a directory on disk with a repository tree, untracked paths matched by
+/// `ignore_rules` not being reported
+///
+/// Tracked files are reported even if they are matched by a rule.
+pub fn get_repository_status_with_ignore_rules(root: &Node, directory: &Path, ignore_rules: &mut IgnoreRules) -> Result<Vec<StatusEntry>, RepTreeError> {
+    let mut entries = Vec::new();
+    compare_directory(&Entry::from_node(root), directory, Path::new(""), &Source::Nodes, None, ignore_rules, &mut entries)?;
+    Ok(entries)
+}
+
+/// Compare a directory on disk with a repository tree, reading only the files whose stat data
+/// changed since they were recorded in `index`
+///
+/// Files found unchanged are recorded in `index`, paths being relative to `directory`.
+pub fn get_repository_status_with_index(root: &Node, directory: &Path, index: &mut Index) -> Result<Vec<StatusEntry>, RepTreeError> {
+    let mut entries = Vec::new();
+    compare_directory(&Entry::from_node(root), directory, Path::new(""), &Source::Nodes, Some(index), &mut IgnoreRules::new(directory), &mut entries)?;
+    Ok(entries)
+}
+
+/// Compare a directory on disk with a tree stored in an `ObjectStore`
+///
+/// Tree objects are read lazily and blob objects are never read, files being hashed to be
+/// compared with the ids of the blobs.
+///
+/// # Arguments
+///
+/// * `tree_id` - id of the root tree
+/// * `directory` - directory matching the root of the tree
+/// * `store` - store containing the objects
+///
+/// # Returns
+///
+/// Result with the entries which differ sorted by path, or `RepTreeError` if the directory or
+/// a tree object can't be read
+pub fn get_repository_status_from_object_store(tree_id: &str, directory: &Path, store: &dyn ObjectStore) -> Result<Vec<StatusEntry>, RepTreeError> {
+    let mut entries = Vec::new();
+    compare_directory(&Entry::from_object(tree_id), directory, Path::new(""), &Source::Store(store), None, &mut IgnoreRules::new(directory), &mut entries)?;
+    Ok(entries)
+}
+
+/// Compare a directory on disk with a tree stored in an `ObjectStore`, hashing only the files
+/// whose stat data changed since they were recorded in `index`
+///
+/// Hashed files are recorded in `index`, paths being relative to `directory`.
+pub fn get_repository_status_from_object_store_with_index(tree_id: &str, directory: &Path, store: &dyn ObjectStore, index: &mut Index) -> Result<Vec<StatusEntry>, RepTreeError> {
+    let mut entries = Vec::new();
+    compare_directory(&Entry::from_object(tree_id), directory, Path::new(""), &Source::Store(store), Some(index), &mut IgnoreRules::new(directory), &mut entries)?;
+    Ok(entries)
+}
+
+/// Check if a file on disk has the mode and the content of a tracked blob, a symbolic link
+/// having the path it points to as content
+fn is_unchanged(tracked: &Entry, file_path: &Path, path: &Path, index: Option<&mut Index>) -> Result<bool, RepTreeError> {
+    let metadata = fs::symlink_metadata(file_path).map_err(RepTreeError::IoError)?;
+    let mode = EntryMode::from_metadata(&metadata);
+    // Without executable bits, executable blobs are checked out as regular files
+    let is_same_mode = mode == tracked.mode || (!cfg!(unix) && mode == EntryMode::Regular && tracked.mode == EntryMode::Executable);
+    if !is_same_mode {
+        return Ok(false);
+    }
+    if let Some(index) = index.as_ref() {
+        if !tracked.id.is_empty() && index.get_unchanged_blob_id(path, &metadata).as_ref() == Some(&tracked.id) {
+            return Ok(true);
+        }
+    }
+
+    // An unloaded blob would be read from the file itself, which is hashed instead
+    let loaded_content = match tracked.node {
+        Some(Node::BlobNode(blob)) if blob.is_loaded() => Some(blob.get_content()?),
+        _ => None
+    };
+    let (unchanged, id) = match loaded_content {
+        Some(content) => {
+            if metadata.len() != content.len() as u64 {
+                return Ok(false);
+            }
+            let unchanged = read_file(file_path, mode)? == content;
+            (unchanged, if unchanged { tracked.id.clone() } else { String::new() })
+        },
+        None => {
+            let mut blob = Blob::new(String::new(), read_file(file_path, mode)?);
+            let id = blob.generate_id();
+            (id == tracked.id, id)
+        }
+    };
+    if let Some(index) = index {
+        if !id.is_empty() {
+            index.update_entry(path, &metadata, &id);
+        }
+    }
+    Ok(unchanged)
+}
+
+/// Read the content of a file on disk, or the path a symbolic link points to as a tree records it
+fn read_file(file_path: &Path, mode: EntryMode) -> Result<Vec<u8>, RepTreeError> {
+    if mode == EntryMode::Symlink {
+        let target = fs::read_link(file_path).map_err(RepTreeError::IoError)?;
+        return Ok(target.to_string_lossy().as_bytes().to_vec());
+    }
+    fs::read(file_path).map_err(RepTreeError::IoError)
+}
+
+fn compare_directory<'a>(tracked: &Entry<'a>, directory: &Path, path: &Path, source: &Source<'a>, mut index: Option<&mut Index>, ignore_rules: &mut IgnoreRules, entries: &mut Vec<StatusEntry>) -> Result<(), RepTreeError> {
+    let mut tracked_children = get_children(tracked, source)?;
+    tracked_children.sort_by(|child1, child2| child1.name.cmp(&child2.name));
+    let disk_children = read_directory(directory)?;
+
+    let mut names: Vec<&String> = tracked_children.iter().map(|child| &child.name)
+        .chain(disk_children.iter().map(|child| &child.name))
+        .collect();
+    names.sort();
+    names.dedup();
+
+    for name in names {
+        let child_path = path.join(name);
+        let disk = disk_children.iter().find(|child| child.name == *name);
+        let tracked_blob = tracked_children.iter().find(|child| child.name == *name && !child.is_tree);
+        let tracked_tree = tracked_children.iter().find(|child| child.name == *name && child.is_tree);
+        // A link to a directory is compared as a file when it is tracked as a link
+        let is_dir = disk.is_some_and(|disk| disk.is_dir && !(disk.is_symlink && tracked_blob.is_some()));
+
+        match (disk, tracked_blob, tracked_tree) {
+            (Some(disk), Some(blob), _) if !is_dir => {
+                if !is_unchanged(blob, &disk.path, &child_path, index.as_deref_mut())? {
+                    entries.push(StatusEntry { kind: StatusKind::Modified, path: child_path.clone() });
+                }
+                if let Some(tree) = tracked_tree {
+                    record_tracked(tree, &child_path, source, entries)?;
+                }
+            },
+            (Some(disk), _, Some(tree)) if is_dir => {
+                compare_directory(tree, &disk.path, &child_path, source, index.as_deref_mut(), ignore_rules, entries)?;
+                if let Some(blob) = tracked_blob {
+                    record_tracked(blob, &child_path, source, entries)?;
+                }
+            },
+            (Some(disk), None, None) => record_untracked(disk, &child_path, ignore_rules, entries)?,
+            (Some(_), _, _) => entries.push(StatusEntry { kind: StatusKind::TypeChanged, path: child_path }),
+            (None, blob, tree) => {
+                for tracked_child in [blob, tree].into_iter().flatten() {
+                    record_tracked(tracked_child, &child_path, source, entries)?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Report the files of a tracked entry missing on disk as deleted
+fn record_tracked<'a>(tracked: &Entry<'a>, path: &Path, source: &Source<'a>, entries: &mut Vec<StatusEntry>) -> Result<(), RepTreeError> {
+    let mut children = if tracked.is_tree { get_children(tracked, source)? } else { vec![] };
+    if children.is_empty() {
+        entries.push(StatusEntry { kind: StatusKind::Deleted, path: path.to_path_buf() });
+        return Ok(());
+    }
+    children.sort_by(|child1, child2| child1.name.cmp(&child2.name));
+    for child in children.iter() {
+        record_tracked(child, &path.join(&child.name), source, entries)?;
+    }
+    Ok(())
+}
+
+/// Report the files of an entry missing in the tree as untracked, a link to a directory being
+/// reported by itself as trees record links by default
+fn record_untracked(disk: &DirectoryEntry, path: &Path, ignore_rules: &mut IgnoreRules, entries: &mut Vec<StatusEntry>) -> Result<(), RepTreeError> {
+    let is_dir = disk.is_dir && !disk.is_symlink;
+    if ignore_rules.is_ignored(path, is_dir)? {
+        return Ok(());
+    }
+    let children = if is_dir { read_directory(&disk.path)? } else { vec![] };
+    if children.is_empty() {
+        entries.push(StatusEntry { kind: StatusKind::Untracked, path: path.to_path_buf() });
+        return Ok(());
+    }
+    for child in children.iter() {
+        record_untracked(child, &path.join(&child.name), ignore_rules, entries)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use dit_id_generator::traits::generator::Generator;
+
+    use crate::features::get_repository_status::{get_repository_status, get_repository_status_from_object_store, get_repository_status_with_ignore_rules, get_repository_status_with_index, StatusEntry, StatusKind};
+    use crate::models::ignore_rules::IgnoreRules;
+    use crate::models::index::Index;
+    use crate::features::transcript_repository_tree_to_object_files::transcript_repository_to_object_store;
+    use crate::models::node::Node;
+    use crate::store::memory_store::MemoryStore;
+
+    fn blob(name: &str, content: &str) -> Node {
+        Node::create_blob_node(name.to_string(), content, PathBuf::from(name))
+    }
+
+    fn tree(name: &str, children: Vec<Node>) -> Node {
+        let mut node = Node::create_tree_node(name.to_string(), children, PathBuf::from(name));
+        node.generate_id();
+        node
+    }
+
+    #[test]
+    fn should_report_status_of_directory() {
+        let directory = Path::new("tmp_status");
+        fs::create_dir_all(directory.join("src")).unwrap();
+        fs::create_dir_all(directory.join("build")).unwrap();
+        fs::create_dir_all(directory.join("new/empty")).unwrap();
+        fs::write(directory.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(directory.join("src/lib.rs"), "pub mod b;").unwrap();
+        fs::write(directory.join("build/run.sh"), "#!/bin/sh").unwrap();
+        fs::write(directory.join("new/file.txt"), "new").unwrap();
+        fs::write(directory.join("README.md"), "Hello").unwrap();
+
+        let root = tree("", vec![
+            tree("src", vec![blob("main.rs", "fn main() {}"), blob("lib.rs", "pub mod a;")]),
+            tree("docs", vec![blob("guide.md", "# Guide")]),
+            blob("build", "#!/bin/sh"),
+            blob("README.md", "Hello"),
+        ]);
+        let mut store = MemoryStore::new();
+        transcript_repository_to_object_store(&root, &mut store).unwrap();
+
+        let status = get_repository_status(&root, directory);
+        let stored_status = get_repository_status_from_object_store(&root.get_id(), directory, &store);
+        fs::remove_dir_all(directory).unwrap();
+
+        let expected = vec![
+            StatusEntry { kind: StatusKind::TypeChanged, path: PathBuf::from("build") },
+            StatusEntry { kind: StatusKind::Deleted, path: PathBuf::from("docs/guide.md") },
+            StatusEntry { kind: StatusKind::Untracked, path: PathBuf::from("new/empty") },
+            StatusEntry { kind: StatusKind::Untracked, path: PathBuf::from("new/file.txt") },
+            StatusEntry { kind: StatusKind::Modified, path: PathBuf::from("src/lib.rs") },
+        ];
+        assert_eq!(status.unwrap(), expected);
+        assert_eq!(stored_status.unwrap(), expected);
+    }
+
+    #[test]
+    fn should_trust_index_for_unchanged_stat_data() {
+        let directory = Path::new("tmp_status_index");
+        fs::create_dir_all(directory).unwrap();
+        fs::write(directory.join("a.txt"), "a").unwrap();
+        let root = tree("", vec![blob("a.txt", "a")]);
+        let index_path = Path::new("tmp_status_index.idx");
+
+        let mut index = Index::new();
+        let first = get_repository_status_with_index(&root, directory, &mut index).unwrap();
+        index.write_to_file(index_path).unwrap();
+        // The index vouches for the recorded blob id only
+        let other_root = tree("", vec![Node::create_blob_node("a.txt".to_string(), "b", PathBuf::from("a.txt"))]);
+        let mut index = Index::read_from_file(index_path).unwrap();
+        let trusted = get_repository_status_with_index(&root, directory, &mut index).unwrap();
+        let other = get_repository_status_with_index(&other_root, directory, &mut index).unwrap();
+        fs::remove_dir_all(directory).unwrap();
+        fs::remove_file(index_path).unwrap();
+
+        assert!(first.is_empty());
+        assert_eq!(index.len(), 1);
+        assert!(trusted.is_empty());
+        assert_eq!(other, vec![StatusEntry { kind: StatusKind::Modified, path: PathBuf::from("a.txt") }]);
+    }
+
+    #[test]
+    fn should_not_report_ignored_untracked_paths() {
+        let directory = Path::new("tmp_status_ignore");
+        fs::create_dir_all(directory.join("target")).unwrap();
+        fs::write(directory.join(".ditignore"), "target/\n*.log\n").unwrap();
+        fs::write(directory.join("target/app"), "binary").unwrap();
+        fs::write(directory.join("tracked.log"), "tracked").unwrap();
+        fs::write(directory.join("debug.log"), "debug").unwrap();
+        fs::write(directory.join("notes.swp"), "swap").unwrap();
+        fs::write(directory.join("global"), "*.swp\nglobal\n").unwrap();
+        let root = tree("", vec![blob(".ditignore", "target/\n*.log\n"), blob("tracked.log", "modified")]);
+
+        let status = get_repository_status(&root, directory).unwrap();
+        let mut ignore_rules = IgnoreRules::new(directory);
+        ignore_rules.add_global_exclude_file(&directory.join("global")).unwrap();
+        let global_status = get_repository_status_with_ignore_rules(&root, directory, &mut ignore_rules).unwrap();
+        fs::remove_dir_all(directory).unwrap();
+
+        let modified = StatusEntry { kind: StatusKind::Modified, path: PathBuf::from("tracked.log") };
+        assert_eq!(status, vec![
+            StatusEntry { kind: StatusKind::Untracked, path: PathBuf::from("global") },
+            StatusEntry { kind: StatusKind::Untracked, path: PathBuf::from("notes.swp") },
+            modified.clone(),
+        ]);
+        assert_eq!(global_status, vec![modified]);
+    }
+}
@@ -1,10 +1,14 @@
-use std::io::{BufRead, BufReader, Read};
-use std::path::PathBuf;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
 use crate::error::RepTreeError;
 use crate::models::blob::{BLOB, Blob};
+use crate::models::commit::Commit;
+use crate::models::entry_mode::EntryMode;
 use crate::models::node::Node::{BlobNode, TreeNode};
-use crate::models::tree::Tree;
-use crate::utils::open_object_file;
+use crate::models::tree::{TREE, Tree};
+use crate::utils::unquote_name;
+use crate::store::file_system_store::FileSystemStore;
+use crate::store::object_store::ObjectStore;
 
 /// **Description**  
 /// This function traverses an object file containing repository tree information 
@@ -21,8 +25,25 @@ use crate::utils::open_object_file;
 ///   - `Ok(())` on success.  
 ///   - `Err(RepTreeError)` if an I/O error or unexpected behavior occurs.
 pub fn get_repository_tree_from_object_files(root: &mut Tree, object_id: &String, object_path: &PathBuf) -> Result<(), RepTreeError> {
-    let reader = open_object_file(object_id, object_path).map_err(RepTreeError::IoError)?;
-    let lines = BufReader::new(reader).lines();
+    let store = FileSystemStore::new(object_path.clone());
+    get_repository_tree_from_object_store(root, object_id, &store)
+}
+
+/// **Description**  
+/// Same as `get_repository_tree_from_object_files`, reading the objects from any `ObjectStore`.
+///
+/// **Parameters**  
+/// - `root`: A mutable reference to a `Tree` object representing the root tree.  
+/// - `object_id`: The identifier of the root object.  
+/// - `store`: The store containing the objects.  
+///
+/// **Returns**  
+/// - `Result<(), RepTreeError>`:  
+///   - `Ok(())` on success.  
+///   - `Err(RepTreeError)` if an object is missing or unexpected behavior occurs.
+pub fn get_repository_tree_from_object_store(root: &mut Tree, object_id: &str, store: &dyn ObjectStore) -> Result<(), RepTreeError> {
+    let object = store.get(object_id)?;
+    let lines = object.lines();
 
     for line in lines {
         let content = match line {
@@ -31,16 +52,15 @@ pub fn get_repository_tree_from_object_files(root: &mut Tree, object_id: &String
                 return Err(RepTreeError::UnexpectedComportment("Error while reading a file".to_string()));
             }
         };
-        let id = &content[5..45];
-        let name = &content[46..];
+        let (node_type, mode, id, name) = parse_tree_entry(&content)?;
 
-        if &content[0..4] == BLOB {
-            get_blob_from_object_file(root, String::from(name), String::from(id), object_path)?
+        if node_type == BLOB {
+            get_blob_from_object_store(root, name, String::from(id), mode, store)?
         } else {
-            let mut new_tree = Tree::new(String::from(name), Vec::new());
+            let mut new_tree = Tree::new(name.clone(), Vec::new());
             new_tree.set_id(String::from(id));
-            new_tree.set_path(root.get_path().join(String::from(name)));
-            get_repository_tree_from_object_files(&mut new_tree, &String::from(id), object_path)?;
+            new_tree.set_path(root.get_path().join(name));
+            get_repository_tree_from_object_store(&mut new_tree, id, store)?;
             let node = TreeNode(new_tree);
             root.add_node(node);
         }
@@ -48,17 +68,113 @@ pub fn get_repository_tree_from_object_files(root: &mut Tree, object_id: &String
     Ok(())
 }
 
-fn get_blob_from_object_file(root: &mut Tree, file_name: String, id: String, object_path: &PathBuf) -> Result<(), RepTreeError> {
-    let mut reader = open_object_file(&String::from(id.clone()), object_path).map_err(RepTreeError::IoError)?;
-    
-    let mut contents = String::new();
-    reader.read_to_string(&mut contents).map_err(RepTreeError::IoError)?;
+/// **Description**  
+/// Read a commit object from the object files.
+///
+/// **Parameters**  
+/// - `object_id`: The identifier of the commit object.  
+/// - `object_path`: The path to the object files.  
+///
+/// **Returns**  
+/// - `Result<Commit, RepTreeError>`:  
+///   - `Ok(Commit)` on success.  
+///   - `Err(RepTreeError)` if the object is missing or isn't a commit.
+pub fn get_commit_from_object_files(object_id: &str, object_path: &Path) -> Result<Commit, RepTreeError> {
+    let store = FileSystemStore::new(object_path.to_path_buf());
+    get_commit_from_object_store(object_id, &store)
+}
+
+/// **Description**  
+/// Read a commit object from any `ObjectStore`.
+///
+/// **Parameters**  
+/// - `object_id`: The identifier of the commit object.  
+/// - `store`: The store containing the objects.  
+///
+/// **Returns**  
+/// - `Result<Commit, RepTreeError>`:  
+///   - `Ok(Commit)` on success.  
+///   - `Err(RepTreeError)` if the object is missing or isn't a commit.
+pub fn get_commit_from_object_store(object_id: &str, store: &dyn ObjectStore) -> Result<Commit, RepTreeError> {
+    let object = store.get(object_id)?;
+    let content = String::from_utf8(object).map_err(|_| {
+        RepTreeError::UnexpectedComportment(format!("Object {object_id} is not a commit"))
+    })?;
+    Commit::deserialize(object_id.to_string(), &content)
+}
+
+/// Read the entries of a tree object without loading its children
+///
+/// # Returns
+///
+/// Result with one `(type, mode, id, name)` tuple per line of the tree object
+pub(crate) fn read_tree_entries(object_id: &str, store: &dyn ObjectStore) -> Result<Vec<(String, EntryMode, String, String)>, RepTreeError> {
+    let object = store.get(object_id)?;
+    let content = String::from_utf8(object).map_err(|_| {
+        RepTreeError::UnexpectedComportment(format!("Object {object_id} is not a tree"))
+    })?;
+    content
+        .lines()
+        .map(|line| {
+            parse_tree_entry(line).map(|(node_type, mode, id, name)| (node_type.to_string(), mode, id.to_string(), name))
+        })
+        .collect()
+}
+
+/// Split a line of a tree object, written as `<TYPE> <id> <name>` or `<TYPE> <mode> <id> <name>`,
+/// see `Tree::serialize`
+///
+/// # Returns
+///
+/// Result with the type, mode, id and name of the entry, the mode being `Regular` for blobs
+/// and `Directory` for trees when it isn't written, and the name being unquoted, see `quote_name`
+fn parse_tree_entry(line: &str) -> Result<(&str, EntryMode, &str, String), RepTreeError> {
+    let mut parts = line.splitn(3, ' ');
+    let entry = match (parts.next(), parts.next(), parts.next()) {
+        (Some(node_type), Some(field), Some(rest)) if node_type == BLOB || node_type == TREE => {
+            let default_mode = if node_type == BLOB { EntryMode::Regular } else { EntryMode::Directory };
+            match (EntryMode::parse(field), rest.split_once(' ')) {
+                (Some(mode), Some((id, name))) => Some((node_type, mode, id, name)),
+                _ => Some((node_type, default_mode, field, rest))
+            }
+        },
+        _ => None
+    };
+    match entry {
+        Some((node_type, mode, id, name)) if (node_type == TREE) == (mode == EntryMode::Directory) => {
+            let name = unquote_name(name).ok_or_else(|| RepTreeError::UnexpectedComportment(format!("Invalid tree entry: {line}")))?;
+            Ok((node_type, mode, id, name))
+        },
+        _ => Err(RepTreeError::UnexpectedComportment(format!("Invalid tree entry: {line}")))
+    }
+}
+
+fn get_blob_from_object_store(root: &mut Tree, file_name: String, id: String, mode: EntryMode, store: &dyn ObjectStore) -> Result<(), RepTreeError> {
+    let contents = store.get(&id)?;
     
     let mut blob = Blob::new(file_name.clone(), contents);
     blob.set_id(id);
+    blob.set_mode(mode);
     blob.set_path(root.get_path().join(file_name));
     let node = BlobNode(blob);
     
     root.add_node(node);
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::features::get_repository_tree_from_object_files::parse_tree_entry;
+    use crate::models::entry_mode::EntryMode;
+
+    #[test]
+    fn should_parse_tree_entry() {
+        assert_eq!(parse_tree_entry("BLOB 1234 my file.txt").unwrap(), ("BLOB", EntryMode::Regular, "1234", "my file.txt".to_string()));
+        assert_eq!(parse_tree_entry("TREE 5678 src").unwrap(), ("TREE", EntryMode::Directory, "5678", "src".to_string()));
+        assert_eq!(parse_tree_entry("BLOB 100755 1234 run all.sh").unwrap(), ("BLOB", EntryMode::Executable, "1234", "run all.sh".to_string()));
+        assert_eq!(parse_tree_entry("BLOB 120000 1234 link").unwrap(), ("BLOB", EntryMode::Symlink, "1234", "link".to_string()));
+        assert!(parse_tree_entry("TREE 100755 5678 src").is_err());
+        assert!(parse_tree_entry("LEAF 1234 leaf").is_err());
+        assert!(parse_tree_entry("BLOB 1234").is_err());
+        assert_eq!(parse_tree_entry("BLOB 1234 \"two\\nlines\"").unwrap(), ("BLOB", EntryMode::Regular, "1234", "two\nlines".to_string()));
+    }
+}
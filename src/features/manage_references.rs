@@ -0,0 +1,434 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::RepTreeError;
+use crate::models::reference::{HEAD, HEADS_PREFIX, Reference, TAGS_PREFIX};
+
+const LOCK_EXTENSION: &str = ".lock";
+const MAX_SYMBOLIC_DEPTH: usize = 5;
+
+/// Condition checked on the current value of a reference before updating or deleting it.
+///
+/// - `Any`: no check, the reference is overwritten whatever its value.
+/// - `Absent`: the reference must not exist yet.
+/// - `Equal`: the reference must currently hold this value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expected {
+    Any,
+    Absent,
+    Equal(Reference),
+}
+
+/// Lock file held while a reference is modified, removed on drop unless committed
+struct ReferenceLock {
+    lock_path: PathBuf,
+    committed: bool,
+}
+
+impl ReferenceLock {
+    fn acquire(reference_path: &Path) -> Result<ReferenceLock, RepTreeError> {
+        if let Some(parent) = reference_path.parent() {
+            fs::create_dir_all(parent).map_err(RepTreeError::IoError)?;
+        }
+        let lock_path = PathBuf::from(format!("{}{}", reference_path.display(), LOCK_EXTENSION));
+        match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+            Ok(_) => Ok(ReferenceLock { lock_path, committed: false }),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Err(RepTreeError::ReferenceConflict(
+                format!("{} is locked by another process", reference_path.display())
+            )),
+            Err(e) => Err(RepTreeError::IoError(e)),
+        }
+    }
+
+    /// Atomically replace the reference by the new content
+    fn commit(mut self, reference_path: &Path, content: &str) -> Result<(), RepTreeError> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&self.lock_path)
+            .map_err(RepTreeError::IoError)?;
+        file.write_all(content.as_bytes()).map_err(RepTreeError::IoError)?;
+        file.sync_all().map_err(RepTreeError::IoError)?;
+        fs::rename(&self.lock_path, reference_path).map_err(RepTreeError::IoError)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for ReferenceLock {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+    }
+}
+
+/// Return the directory holding `HEAD` and `refs/`, which is the parent of the objects directory
+///
+/// # Arguments
+///
+/// * `object_path` - path to the object files
+pub fn get_references_path(object_path: &Path) -> PathBuf {
+    match object_path.parent() {
+        Some(parent) => parent.to_path_buf(),
+        None => PathBuf::new(),
+    }
+}
+
+/// Check that a reference name is `HEAD` or a safe path under `refs/`
+fn check_reference_name(name: &str) -> Result<(), RepTreeError> {
+    let invalid = || RepTreeError::UnexpectedComportment(format!("Invalid reference name: {name}"));
+
+    if name == HEAD {
+        return Ok(());
+    }
+    if !name.starts_with("refs/") || name.ends_with('/') || name.ends_with(LOCK_EXTENSION) {
+        return Err(invalid());
+    }
+    let forbidden = |c: char| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c);
+    if name.contains(forbidden) || name.contains("..") {
+        return Err(invalid());
+    }
+    if name.split('/').any(|component| component.is_empty() || component.starts_with('.')) {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+fn get_reference_path(name: &str, object_path: &Path) -> Result<PathBuf, RepTreeError> {
+    check_reference_name(name)?;
+    Ok(get_references_path(object_path).join(name))
+}
+
+fn read_reference_file(reference_path: &Path) -> Result<Option<Reference>, RepTreeError> {
+    match fs::read_to_string(reference_path) {
+        Ok(content) => Ok(Some(Reference::deserialize(&content)?)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(RepTreeError::IoError(e)),
+    }
+}
+
+fn check_expected(name: &str, current: &Option<Reference>, expected: &Expected) -> Result<(), RepTreeError> {
+    let matches = match (expected, current) {
+        (Expected::Any, _) => true,
+        (Expected::Absent, current) => current.is_none(),
+        (Expected::Equal(value), Some(current)) => value == current,
+        (Expected::Equal(_), None) => false,
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(RepTreeError::ReferenceConflict(format!("{name} was expected to be {expected:?} but is {current:?}")))
+    }
+}
+
+/// Read a reference without following symbolic references
+///
+/// # Arguments
+///
+/// * `name` - full name of the reference, `HEAD` or `refs/...`
+/// * `object_path` - path to the object files
+///
+/// # Returns
+///
+/// Result with `Some(Reference)`, `None` if the reference doesn't exist, or `RepTreeError`
+pub fn read_reference(name: &str, object_path: &Path) -> Result<Option<Reference>, RepTreeError> {
+    read_reference_file(&get_reference_path(name, object_path)?)
+}
+
+/// Create or update a reference if its current value matches `expected`
+///
+/// The reference is locked during the update and replaced atomically, so two processes can't
+/// overwrite each other's update.
+///
+/// # Arguments
+///
+/// * `name` - full name of the reference, `HEAD` or `refs/...`
+/// * `value` - new value of the reference
+/// * `expected` - condition on the current value
+/// * `object_path` - path to the object files
+///
+/// # Returns
+///
+/// `RepTreeError::ReferenceConflict` if the reference is locked or doesn't match `expected`
+pub fn update_reference(name: &str, value: &Reference, expected: &Expected, object_path: &Path) -> Result<(), RepTreeError> {
+    let reference_path = get_reference_path(name, object_path)?;
+    if let Reference::Symbolic(target) = value {
+        check_reference_name(target)?;
+    }
+
+    let lock = ReferenceLock::acquire(&reference_path)?;
+    let current = read_reference_file(&reference_path)?;
+    check_expected(name, &current, expected)?;
+    lock.commit(&reference_path, &value.serialize())
+}
+
+/// Delete a reference if its current value matches `expected`
+///
+/// Directories left empty under `refs/` are removed along with the reference.
+///
+/// # Arguments
+///
+/// * `name` - full name of the reference, `HEAD` or `refs/...`
+/// * `expected` - condition on the current value
+/// * `object_path` - path to the object files
+///
+/// # Returns
+///
+/// `RepTreeError::ReferenceNotFound` if the reference doesn't exist,
+/// `RepTreeError::ReferenceConflict` if it is locked or doesn't match `expected`
+pub fn delete_reference(name: &str, expected: &Expected, object_path: &Path) -> Result<(), RepTreeError> {
+    let reference_path = get_reference_path(name, object_path)?;
+
+    let lock = ReferenceLock::acquire(&reference_path)?;
+    let current = read_reference_file(&reference_path)?;
+    if current.is_none() {
+        return Err(RepTreeError::ReferenceNotFound(name.to_string()));
+    }
+    check_expected(name, &current, expected)?;
+    fs::remove_file(&reference_path).map_err(RepTreeError::IoError)?;
+    drop(lock);
+    remove_empty_parents(&reference_path, &get_references_path(object_path).join("refs"));
+    Ok(())
+}
+
+/// Remove the directories left empty above a deleted reference, up to `refs_path` excluded, so
+/// that a reference can be created again with the name of one of them
+///
+/// Directories still holding references or locks are left in place, as are the directories
+/// that can't be removed, a reference created meanwhile by another process being kept.
+fn remove_empty_parents(reference_path: &Path, refs_path: &Path) {
+    let mut directory = reference_path.parent();
+    while let Some(path) = directory {
+        if path == refs_path || !path.starts_with(refs_path) || fs::remove_dir(path).is_err() {
+            break;
+        }
+        directory = path.parent();
+    }
+}
+
+/// List every reference stored under `refs/`
+///
+/// # Arguments
+///
+/// * `object_path` - path to the object files
+///
+/// # Returns
+///
+/// Result with the references and their full names, sorted by name
+pub fn list_references(object_path: &Path) -> Result<Vec<(String, Reference)>, RepTreeError> {
+    let mut references: Vec<(String, Reference)> = Vec::new();
+    let references_path = get_references_path(object_path);
+    collect_references(&references_path, &references_path.join("refs"), &mut references)?;
+    references.sort_by(|(name1, _), (name2, _)| name1.cmp(name2));
+    Ok(references)
+}
+
+fn collect_references(references_path: &Path, directory: &Path, references: &mut Vec<(String, Reference)>) -> Result<(), RepTreeError> {
+    if !directory.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(directory).map_err(RepTreeError::IoError)? {
+        let path = entry.map_err(RepTreeError::IoError)?.path();
+        if path.is_dir() {
+            collect_references(references_path, &path, references)?;
+        } else if !path.to_string_lossy().ends_with(LOCK_EXTENSION) {
+            let name = path
+                .strip_prefix(references_path)
+                .map_err(|_| RepTreeError::UnexpectedComportment(format!("Invalid reference path: {:?}", path)))?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<String>>()
+                .join("/");
+            if let Some(reference) = read_reference_file(&path)? {
+                references.push((name, reference));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolve a name to the object id it points to, following symbolic references
+///
+/// Short names are looked up as given, then as a branch (`refs/heads/<name>`),
+/// then as a tag (`refs/tags/<name>`).
+///
+/// # Arguments
+///
+/// * `name` - `HEAD`, full reference name or short branch/tag name
+/// * `object_path` - path to the object files
+///
+/// # Returns
+///
+/// Result with the object id, or `RepTreeError::ReferenceNotFound` if no reference matches
+pub fn resolve_reference(name: &str, object_path: &Path) -> Result<String, RepTreeError> {
+    let candidates = if name == HEAD || name.starts_with("refs/") {
+        vec![name.to_string()]
+    } else {
+        vec![format!("{HEADS_PREFIX}{name}"), format!("{TAGS_PREFIX}{name}")]
+    };
+
+    for candidate in candidates.iter() {
+        let mut current = candidate.to_string();
+        for _ in 0..MAX_SYMBOLIC_DEPTH {
+            match read_reference(&current, object_path)? {
+                Some(Reference::Direct(id)) => return Ok(id),
+                Some(Reference::Symbolic(target)) => current = target,
+                None => break,
+            }
+        }
+    }
+    Err(RepTreeError::ReferenceNotFound(name.to_string()))
+}
+
+/// Create a branch pointing to a commit, failing if the branch already exists
+pub fn create_branch(name: &str, commit_id: &str, object_path: &Path) -> Result<(), RepTreeError> {
+    update_reference(&format!("{HEADS_PREFIX}{name}"), &Reference::Direct(commit_id.to_string()), &Expected::Absent, object_path)
+}
+
+/// Delete a branch whatever its value
+pub fn delete_branch(name: &str, object_path: &Path) -> Result<(), RepTreeError> {
+    delete_reference(&format!("{HEADS_PREFIX}{name}"), &Expected::Any, object_path)
+}
+
+/// List branch short names with the commit id they point to
+pub fn list_branches(object_path: &Path) -> Result<Vec<(String, String)>, RepTreeError> {
+    Ok(list_references(object_path)?
+        .into_iter()
+        .filter_map(|(name, reference)| match (name.strip_prefix(HEADS_PREFIX), reference) {
+            (Some(branch), Reference::Direct(id)) => Some((branch.to_string(), id)),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Create a tag pointing to an object, failing if the tag already exists
+pub fn create_tag(name: &str, object_id: &str, object_path: &Path) -> Result<(), RepTreeError> {
+    update_reference(&format!("{TAGS_PREFIX}{name}"), &Reference::Direct(object_id.to_string()), &Expected::Absent, object_path)
+}
+
+/// Point `HEAD` to a branch, or detach it on an object id
+///
+/// # Arguments
+///
+/// * `reference` - `Reference::Symbolic` with the full branch name, or `Reference::Direct`
+/// * `object_path` - path to the object files
+pub fn set_head(reference: &Reference, object_path: &Path) -> Result<(), RepTreeError> {
+    update_reference(HEAD, reference, &Expected::Any, object_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::error::RepTreeError;
+    use crate::features::manage_references::*;
+    use crate::models::reference::Reference;
+
+    fn teardown(directory: &str) {
+        fs::remove_dir_all(directory).unwrap_or_else(|_| println!("Failed to delete temporary directory"));
+    }
+
+    #[test]
+    fn should_compare_and_swap_reference() {
+        let object_path = PathBuf::from("tmp_refs1/objects");
+        let name = "refs/heads/main";
+
+        let created = update_reference(name, &Reference::Direct("1111".to_string()), &Expected::Absent, &object_path);
+        let recreated = update_reference(name, &Reference::Direct("2222".to_string()), &Expected::Absent, &object_path);
+        let stale = update_reference(name, &Reference::Direct("2222".to_string()), &Expected::Equal(Reference::Direct("0000".to_string())), &object_path);
+        let swapped = update_reference(name, &Reference::Direct("2222".to_string()), &Expected::Equal(Reference::Direct("1111".to_string())), &object_path);
+        let value = read_reference(name, &object_path);
+        let lock_left = PathBuf::from("tmp_refs1/refs/heads/main.lock").exists();
+
+        teardown("tmp_refs1");
+
+        assert!(created.is_ok());
+        assert!(matches!(recreated, Err(RepTreeError::ReferenceConflict(_))));
+        assert!(matches!(stale, Err(RepTreeError::ReferenceConflict(_))));
+        assert!(swapped.is_ok());
+        assert_eq!(value.unwrap(), Some(Reference::Direct("2222".to_string())));
+        assert!(!lock_left);
+    }
+
+    #[test]
+    fn should_refuse_update_while_locked() {
+        let object_path = PathBuf::from("tmp_refs2/objects");
+        fs::create_dir_all("tmp_refs2/refs/heads").unwrap();
+        fs::write("tmp_refs2/refs/heads/main.lock", "").unwrap();
+
+        let result = create_branch("main", "1111", &object_path);
+
+        teardown("tmp_refs2");
+
+        assert!(matches!(result, Err(RepTreeError::ReferenceConflict(_))));
+    }
+
+    #[test]
+    fn should_resolve_list_and_delete_references() {
+        let object_path = PathBuf::from("tmp_refs3/objects");
+
+        create_branch("main", "1111", &object_path).unwrap();
+        create_branch("feature/oak", "2222", &object_path).unwrap();
+        create_tag("v1", "3333", &object_path).unwrap();
+        set_head(&Reference::Symbolic("refs/heads/main".to_string()), &object_path).unwrap();
+
+        let head = resolve_reference("HEAD", &object_path);
+        let branch = resolve_reference("feature/oak", &object_path);
+        let tag = resolve_reference("v1", &object_path);
+        let branches = list_branches(&object_path);
+        let deleted = delete_branch("feature/oak", &object_path);
+        let missing = resolve_reference("feature/oak", &object_path);
+        let references = list_references(&object_path);
+
+        teardown("tmp_refs3");
+
+        assert_eq!(head.unwrap(), "1111");
+        assert_eq!(branch.unwrap(), "2222");
+        assert_eq!(tag.unwrap(), "3333");
+        assert_eq!(branches.unwrap(), vec![
+            ("feature/oak".to_string(), "2222".to_string()),
+            ("main".to_string(), "1111".to_string())
+        ]);
+        assert!(deleted.is_ok());
+        assert!(matches!(missing, Err(RepTreeError::ReferenceNotFound(_))));
+        assert_eq!(references.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn should_remove_empty_directories_of_deleted_references() {
+        let object_path = PathBuf::from("tmp_refs5/objects");
+
+        create_branch("feature/x/y", "1111", &object_path).unwrap();
+        create_branch("feature/z", "2222", &object_path).unwrap();
+        delete_branch("feature/x/y", &object_path).unwrap();
+        let x_left = PathBuf::from("tmp_refs5/refs/heads/feature/x").exists();
+        let feature_left = PathBuf::from("tmp_refs5/refs/heads/feature").exists();
+        delete_branch("feature/z", &object_path).unwrap();
+        let heads_left = PathBuf::from("tmp_refs5/refs/heads").exists();
+        let refs_left = PathBuf::from("tmp_refs5/refs").exists();
+        let recreated = create_branch("feature", "3333", &object_path);
+        let value = resolve_reference("feature", &object_path);
+
+        teardown("tmp_refs5");
+
+        assert!(!x_left);
+        assert!(feature_left);
+        assert!(!heads_left);
+        assert!(refs_left);
+        assert!(recreated.is_ok());
+        assert_eq!(value.unwrap(), "3333");
+    }
+
+    #[test]
+    fn should_reject_invalid_names() {
+        let object_path = PathBuf::from("tmp_refs4/objects");
+
+        assert!(read_reference("refs/heads/../../escape", &object_path).is_err());
+        assert!(read_reference("main", &object_path).is_err());
+        assert!(read_reference("refs/heads/main.lock", &object_path).is_err());
+        assert!(read_reference("refs/heads/a b", &object_path).is_err());
+    }
+}
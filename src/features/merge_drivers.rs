@@ -0,0 +1,365 @@
+use std::fmt::Write;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Content merged by a `MergeDriver`.
+///
+/// `conflicts` describes the parts of the file both sides changed, the driver having picked a
+/// version for them in `content`. It is empty when the merge is clean.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DriverMerge {
+    pub content: Vec<u8>,
+    pub conflicts: Vec<String>,
+}
+
+/// Merge of the contents of a file type which can't be merged line by line
+///
+/// Drivers are registered in `MergeOptions` for a glob pattern or an extension, and are used
+/// by `merge_repository_trees_with_options` instead of the line based merge for files changed
+/// on both sides.
+pub trait MergeDriver {
+    /// Merge both versions of a file
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - content in the common ancestor, `None` if the file was added on both sides
+    /// * `ours` - current content
+    /// * `theirs` - incoming content
+    ///
+    /// # Returns
+    ///
+    /// The merged content, or `None` if the driver can't handle these contents, the line based
+    /// merge being used instead
+    fn merge(&self, base: Option<&[u8]>, ours: &[u8], theirs: &[u8]) -> Option<DriverMerge>;
+}
+
+/// Driver merging JSON objects key by key
+///
+/// Keys changed on a single side are taken from that side, objects changed on both sides are
+/// merged recursively. Other values changed differently on both sides are conflicts, the
+/// current value being kept. The result is pretty printed with two spaces, keys keeping the
+/// order of the current version, keys added by the incoming version coming last.
+pub struct JsonDriver;
+
+impl MergeDriver for JsonDriver {
+    fn merge(&self, base: Option<&[u8]>, ours: &[u8], theirs: &[u8]) -> Option<DriverMerge> {
+        let base = match base {
+            Some(base) => Some(parse_json(std::str::from_utf8(base).ok()?)?),
+            None => None,
+        };
+        let ours_text = std::str::from_utf8(ours).ok()?;
+        let ours = parse_json(ours_text)?;
+        let theirs = parse_json(std::str::from_utf8(theirs).ok()?)?;
+        if !matches!(ours, Json::Object(_)) || !matches!(theirs, Json::Object(_)) {
+            return None;
+        }
+
+        let mut conflicts = Vec::new();
+        let merged = merge_json(base.as_ref(), &ours, &theirs, "", &mut conflicts);
+
+        let mut content = String::new();
+        write_json(&merged, 0, &mut content);
+        if ours_text.ends_with('\n') {
+            content.push('\n');
+        }
+        Some(DriverMerge { content: content.into_bytes(), conflicts })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    // Kept as written to avoid changing the precision of numbers
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+fn get_value<'a>(entries: &'a [(String, Json)], key: &str) -> Option<&'a Json> {
+    entries.iter().find(|(name, _)| name == key).map(|(_, value)| value)
+}
+
+/// Three-way merge of JSON values, `path` being the dotted path of the keys for conflicts
+fn merge_json(base: Option<&Json>, ours: &Json, theirs: &Json, path: &str, conflicts: &mut Vec<String>) -> Json {
+    if ours == theirs || base == Some(theirs) {
+        return ours.clone();
+    }
+    if base == Some(ours) {
+        return theirs.clone();
+    }
+
+    match (ours, theirs) {
+        (Json::Object(ours_entries), Json::Object(theirs_entries)) => {
+            let base_entries: &[(String, Json)] = match base {
+                Some(Json::Object(base_entries)) => base_entries,
+                _ => &[],
+            };
+            let mut keys: Vec<&String> = ours_entries.iter().map(|(key, _)| key).collect();
+            for (key, _) in theirs_entries.iter().chain(base_entries.iter()) {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+
+            let mut merged = Vec::new();
+            for key in keys {
+                let key_path = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+                let base_value = get_value(base_entries, key);
+                let ours_value = get_value(ours_entries, key);
+                let theirs_value = get_value(theirs_entries, key);
+
+                let value = match (ours_value, theirs_value) {
+                    (Some(ours_value), Some(theirs_value)) => {
+                        Some(merge_json(base_value, ours_value, theirs_value, &key_path, conflicts))
+                    },
+                    (Some(value), None) | (None, Some(value)) => {
+                        if base_value.is_none() {
+                            Some(value.clone())
+                        } else if base_value == Some(value) {
+                            // Deleted on the other side
+                            None
+                        } else {
+                            conflicts.push(key_path);
+                            Some(value.clone())
+                        }
+                    },
+                    (None, None) => None,
+                };
+                if let Some(value) = value {
+                    merged.push((key.clone(), value));
+                }
+            }
+            Json::Object(merged)
+        },
+        _ => {
+            conflicts.push(path.to_string());
+            ours.clone()
+        }
+    }
+}
+
+fn parse_json(text: &str) -> Option<Json> {
+    let mut chars = text.chars().peekable();
+    let value = parse_value(&mut chars)?;
+    skip_whitespaces(&mut chars);
+    match chars.next() {
+        None => Some(value),
+        Some(_) => None,
+    }
+}
+
+fn skip_whitespaces(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn parse_literal(chars: &mut Peekable<Chars>, literal: &str, value: Json) -> Option<Json> {
+    for expected in literal.chars() {
+        if chars.next()? != expected {
+            return None;
+        }
+    }
+    Some(value)
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Option<Json> {
+    skip_whitespaces(chars);
+    match *chars.peek()? {
+        'n' => parse_literal(chars, "null", Json::Null),
+        't' => parse_literal(chars, "true", Json::Bool(true)),
+        'f' => parse_literal(chars, "false", Json::Bool(false)),
+        '"' => parse_string(chars).map(Json::String),
+        '[' => {
+            chars.next();
+            let mut values = Vec::new();
+            skip_whitespaces(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Some(Json::Array(values));
+            }
+            loop {
+                values.push(parse_value(chars)?);
+                skip_whitespaces(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    ']' => return Some(Json::Array(values)),
+                    _ => return None,
+                }
+            }
+        },
+        '{' => {
+            chars.next();
+            let mut entries = Vec::new();
+            skip_whitespaces(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Some(Json::Object(entries));
+            }
+            loop {
+                skip_whitespaces(chars);
+                let key = parse_string(chars)?;
+                skip_whitespaces(chars);
+                if chars.next()? != ':' {
+                    return None;
+                }
+                entries.push((key, parse_value(chars)?));
+                skip_whitespaces(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    '}' => return Some(Json::Object(entries)),
+                    _ => return None,
+                }
+            }
+        },
+        '-' | '0'..='9' => {
+            let mut number = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+                number.push(c);
+            }
+            number.parse::<f64>().ok()?;
+            Some(Json::Number(number))
+        },
+        _ => None,
+    }
+}
+
+fn parse_hex(chars: &mut Peekable<Chars>) -> Option<u32> {
+    let mut code = 0;
+    for _ in 0..4 {
+        code = code * 16 + chars.next()?.to_digit(16)?;
+    }
+    Some(code)
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    if chars.next()? != '"' {
+        return None;
+    }
+    let mut result = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(result),
+            '\\' => match chars.next()? {
+                '"' => result.push('"'),
+                '\\' => result.push('\\'),
+                '/' => result.push('/'),
+                'b' => result.push('\u{8}'),
+                'f' => result.push('\u{c}'),
+                'n' => result.push('\n'),
+                'r' => result.push('\r'),
+                't' => result.push('\t'),
+                'u' => {
+                    let mut code = parse_hex(chars)?;
+                    if (0xD800..0xDC00).contains(&code) {
+                        // Surrogate pair
+                        if chars.next()? != '\\' || chars.next()? != 'u' {
+                            return None;
+                        }
+                        let low = parse_hex(chars)?;
+                        code = 0x10000 + ((code - 0xD800) << 10) + (low.checked_sub(0xDC00)? & 0x3FF);
+                    }
+                    result.push(char::from_u32(code)?);
+                },
+                _ => return None,
+            },
+            c => result.push(c),
+        }
+    }
+}
+
+fn write_string(text: &str, output: &mut String) {
+    output.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(output, "\\u{:04x}", c as u32);
+            },
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+fn write_json(value: &Json, indent: usize, output: &mut String) {
+    let padding = "  ".repeat(indent + 1);
+    match value {
+        Json::Null => output.push_str("null"),
+        Json::Bool(value) => output.push_str(if *value { "true" } else { "false" }),
+        Json::Number(number) => output.push_str(number),
+        Json::String(text) => write_string(text, output),
+        Json::Array(values) if values.is_empty() => output.push_str("[]"),
+        Json::Object(entries) if entries.is_empty() => output.push_str("{}"),
+        Json::Array(values) => {
+            output.push_str("[\n");
+            for (position, value) in values.iter().enumerate() {
+                output.push_str(&padding);
+                write_json(value, indent + 1, output);
+                output.push_str(if position + 1 < values.len() { ",\n" } else { "\n" });
+            }
+            output.push_str(&"  ".repeat(indent));
+            output.push(']');
+        },
+        Json::Object(entries) => {
+            output.push_str("{\n");
+            for (position, (key, value)) in entries.iter().enumerate() {
+                output.push_str(&padding);
+                write_string(key, output);
+                output.push_str(": ");
+                write_json(value, indent + 1, output);
+                output.push_str(if position + 1 < entries.len() { ",\n" } else { "\n" });
+            }
+            output.push_str(&"  ".repeat(indent));
+            output.push('}');
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::features::merge_drivers::{DriverMerge, JsonDriver, MergeDriver};
+
+    #[test]
+    fn should_merge_json_objects_by_key() {
+        let base = r#"{"name": "app", "version": "1.0", "deps": {"a": "1", "b": "1"}}"#;
+        let ours = r#"{"name": "app", "version": "1.1", "deps": {"a": "2", "b": "1"}}"#;
+        let theirs = r#"{"name": "app", "version": "1.0", "deps": {"a": "1", "b": "1", "c": "1"}, "private": true}"#;
+
+        let merged = JsonDriver.merge(Some(base.as_bytes()), ours.as_bytes(), theirs.as_bytes()).unwrap();
+
+        assert_eq!(String::from_utf8(merged.content).unwrap(), r#"{
+  "name": "app",
+  "version": "1.1",
+  "deps": {
+    "a": "2",
+    "b": "1",
+    "c": "1"
+  },
+  "private": true
+}"#);
+        assert!(merged.conflicts.is_empty());
+    }
+
+    #[test]
+    fn should_report_json_conflicts() {
+        let base = "{\"a\": 1, \"b\": [1], \"c\": \"\\u00e9\"}\n";
+        let ours = "{\"a\": 2, \"b\": [1]}\n";
+        let theirs = "{\"a\": 3, \"b\": [], \"c\": \"\\u00e8\"}\n";
+
+        let merged = JsonDriver.merge(Some(base.as_bytes()), ours.as_bytes(), theirs.as_bytes()).unwrap();
+
+        assert_eq!(merged, DriverMerge {
+            content: "{\n  \"a\": 2,\n  \"b\": [],\n  \"c\": \"è\"\n}\n".as_bytes().to_vec(),
+            conflicts: vec!["a".to_string(), "c".to_string()],
+        });
+    }
+
+    #[test]
+    fn should_decline_invalid_json() {
+        assert!(JsonDriver.merge(None, b"{\"a\": 1", b"{}").is_none());
+        assert!(JsonDriver.merge(None, b"[1]", b"[2]").is_none());
+    }
+}
@@ -1,6 +1,14 @@
+use std::path::{Path, PathBuf};
+
+use dit_id_generator::traits::generator::Generator;
+
+use crate::features::diff_repository_trees::{ChangeKind, diff_repository_trees_with_options, DiffOptions};
+use crate::features::merge_drivers::MergeDriver;
 use crate::models::blob::Blob;
+use crate::models::entry_mode::EntryMode;
 use crate::models::node::Node;
 use crate::models::node::Node::{BlobNode, TreeNode};
+use crate::utils::matches_glob;
 
 #[derive(PartialEq)]
 pub enum Mode {
@@ -17,93 +25,852 @@ impl Mode {
     }
 }
 
-pub fn merge_repository_trees(n1: Node, n2: Node, mode: &Mode) -> Option<Node>{
-    if n1.is_root() && n1.get_id() == n2.get_id() {
-        return None;
+/// How regions changed on both sides are resolved.
+///
+/// - `Normal`: conflict markers are written around them.
+/// - `Ours`: the current version is taken.
+/// - `Theirs`: the incoming version is taken.
+/// - `Union`: lines of the current version are followed by the incoming lines it doesn't
+///   contain, which suits files like changelogs.
+///
+/// `Ours` and `Theirs` also resolve deletions against modifications and files added on both
+/// sides. Changes made on a single side are merged whatever the strategy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    Normal,
+    Ours,
+    Theirs,
+    Union,
+}
+
+/// Size of the conflict markers, as expected by editors and other tools
+pub const MARKER_SIZE: usize = 7;
+
+/// Layout of the conflicts written in merged files.
+///
+/// - `Merge`: current and incoming versions, separated by `=======`.
+/// - `Diff3`: current, base and incoming versions, the base version following `|||||||`.
+/// - `ZDiff3`: like `Diff3`, lines at the start or at the end of the conflict on which both
+///   sides agree being moved out of the markers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictStyle {
+    Merge,
+    Diff3,
+    ZDiff3,
+}
+
+/// Strategy used for the files matching a glob pattern, see `utils::matches_glob`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeAttribute {
+    pub pattern: String,
+    pub strategy: Strategy,
+}
+
+/// Options of `merge_repository_trees_with_options`.
+///
+/// `strategy` applies to every file unless one of `attributes` matches its path, the last
+/// matching attribute winning. In `Mode::Complete`, files matching the glob pattern of one of
+/// `drivers` are merged by that driver rather than line by line, the last registered driver
+/// winning. Conflicts are written in `conflict_style`, markers being followed by the label of
+/// their version, usually a branch name. Files renamed on one side since the common ancestor
+/// are detected with `renames`, their modifications on the other side being merged into the
+/// renamed files.
+pub struct MergeOptions {
+    pub mode: Mode,
+    pub strategy: Strategy,
+    pub attributes: Vec<MergeAttribute>,
+    pub drivers: Vec<(String, Box<dyn MergeDriver>)>,
+    pub conflict_style: ConflictStyle,
+    pub ours_label: String,
+    pub base_label: String,
+    pub theirs_label: String,
+    pub renames: DiffOptions,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            mode: Mode::Complete,
+            strategy: Strategy::Normal,
+            attributes: vec![],
+            drivers: vec![],
+            conflict_style: ConflictStyle::Merge,
+            ours_label: "HEAD".to_string(),
+            base_label: "base".to_string(),
+            theirs_label: "incoming".to_string(),
+            renames: DiffOptions { detect_copies: false, ..DiffOptions::default() },
+        }
+    }
+}
+
+impl MergeOptions {
+    /// Use `strategy` for the files matching `pattern`
+    pub fn add_attribute(&mut self, pattern: &str, strategy: Strategy) {
+        self.attributes.push(MergeAttribute { pattern: pattern.to_string(), strategy });
+    }
+
+    /// Merge the files matching `pattern` with `driver`
+    pub fn register_driver(&mut self, pattern: &str, driver: impl MergeDriver + 'static) {
+        self.drivers.push((pattern.to_string(), Box::new(driver)));
+    }
+
+    /// Merge the files with the extension `extension`, given without dot, with `driver`
+    pub fn register_driver_for_extension(&mut self, extension: &str, driver: impl MergeDriver + 'static) {
+        self.register_driver(&format!("*.{extension}"), driver);
+    }
+
+    /// Driver used to merge the file at `path`, if any
+    pub fn get_driver(&self, path: &Path) -> Option<&dyn MergeDriver> {
+        let path = path.to_string_lossy().replace('\\', "/");
+        self.drivers.iter()
+            .rev()
+            .find(|(pattern, _)| matches_glob(pattern, &path))
+            .map(|(_, driver)| driver.as_ref())
+    }
+
+    /// Strategy used to merge the file at `path`
+    pub fn get_strategy(&self, path: &Path) -> Strategy {
+        let path = path.to_string_lossy().replace('\\', "/");
+        self.attributes.iter()
+            .rev()
+            .find(|attribute| matches_glob(&attribute.pattern, &path))
+            .map_or(self.strategy, |attribute| attribute.strategy)
+    }
+}
+
+/// Global result of a merge.
+///
+/// - `UpToDate`: both trees are identical, there is nothing to merge.
+/// - `Merged`: trees were merged, possibly with conflicts.
+/// - `TypeMismatch`: a file can't be merged with a directory.
+/// - `Unreadable`: the content of a file read from the disk couldn't be loaded, or was modified
+///   since its id was read, see `Blob::load_content`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeStatus {
+    UpToDate,
+    Merged,
+    TypeMismatch,
+    Unreadable,
+}
+
+/// Kind of conflict found while merging.
+///
+/// - `Content`: the file was modified on both sides and the changes overlap.
+/// - `ModifyDelete`: the entry was deleted on one side and modified on the other.
+/// - `FileDirectory`: a file and a directory ended up with the same name, the file being
+///   renamed `name~label` after the label of the side it comes from.
+/// - `AddAdd`: the file was added on both sides with different contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictKind {
+    Content,
+    ModifyDelete,
+    FileDirectory,
+    AddAdd,
+}
+
+/// Conflict on a single entry, `path` being relative to the merged roots.
+///
+/// Ids are those of the entry in the common ancestor, the current and the incoming trees,
+/// `None` when the entry doesn't exist on that side.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub path: PathBuf,
+    pub base_id: Option<String>,
+    pub ours_id: Option<String>,
+    pub theirs_id: Option<String>,
+}
+
+/// Merged tree with the conflicts left in it.
+///
+/// `tree` is `None` unless `status` is `MergeStatus::Merged`. Conflicted files are part of the
+/// tree, with conflict markers when their contents were merged.
+#[derive(Clone, Debug)]
+pub struct MergeOutcome {
+    pub status: MergeStatus,
+    pub tree: Option<Node>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl MergeOutcome {
+    fn without_tree(status: MergeStatus) -> MergeOutcome {
+        MergeOutcome { status, tree: None, conflicts: vec![] }
+    }
+
+    /// Check if the merge succeeded without conflict
+    pub fn is_clean(&self) -> bool {
+        matches!(self.status, MergeStatus::UpToDate | MergeStatus::Merged) && self.conflicts.is_empty()
+    }
+}
+
+/// Merge two repository trees, using their common ancestor when known
+///
+/// Files or directories changed on a single side since `base` are taken from that side,
+/// including deletions. Files changed on both sides are merged: `Mode::Complete` merges them
+/// line by line and writes conflict markers only around the regions changed by both sides,
+/// `Mode::Partial` takes the incoming version. A file deleted on one side and modified on the
+/// other is kept with its modifications and reported as a conflict.
+///
+/// Without `base`, every difference between `n1` and `n2` is considered as a conflict.
+///
+/// # Arguments
+///
+/// * `base` - common ancestor of both trees
+/// * `n1` - current tree
+/// * `n2` - incoming tree
+/// * `mode` - how files changed on both sides are merged
+///
+/// # Returns
+///
+/// The `MergeOutcome` with the merged tree and its conflicts
+pub fn merge_repository_trees(base: Option<Node>, n1: Node, n2: Node, mode: &Mode) -> MergeOutcome {
+    let options = MergeOptions {
+        mode: if *mode == Mode::Partial { Mode::Partial } else { Mode::Complete },
+        ..MergeOptions::default()
+    };
+    merge_repository_trees_with_options(base, n1, n2, &options)
+}
+
+/// Merge two repository trees, resolving regions changed on both sides with a `Strategy`
+///
+/// Works like `merge_repository_trees`, the strategy of each file being picked from `options`.
+/// Conflicts resolved by a strategy are not reported.
+///
+/// # Arguments
+///
+/// * `base` - common ancestor of both trees
+/// * `n1` - current tree
+/// * `n2` - incoming tree
+/// * `options` - mode and strategies of the merge
+///
+/// # Returns
+///
+/// The `MergeOutcome` with the merged tree and its conflicts
+pub fn merge_repository_trees_with_options(base: Option<Node>, mut n1: Node, mut n2: Node, options: &MergeOptions) -> MergeOutcome {
+    if !Node::is_same_type(&n1, &n2) {
+        return MergeOutcome::without_tree(MergeStatus::TypeMismatch);
+    }
+
+    n1.generate_id();
+    n2.generate_id();
+    if n1.get_id() == n2.get_id() {
+        return MergeOutcome::without_tree(MergeStatus::UpToDate);
+    }
+    let mut base = base.map(|mut base| {
+        base.generate_id();
+        base
+    });
+    let loaded = n1.load_contents()
+        .and_then(|_| n2.load_contents())
+        .and_then(|_| base.as_mut().map_or(Ok(()), |base| base.load_contents()));
+    if loaded.is_err() {
+        return MergeOutcome::without_tree(MergeStatus::Unreadable);
+    }
+    if let Some(base) = base.as_mut() {
+        follow_renames(base, &mut n1, &mut n2, &options.renames);
+    }
+
+    let mut conflicts = Vec::new();
+    let mut merged = merge_nodes(base.as_ref(), &n1, &n2, options, Path::new(""), &mut conflicts);
+    merged.generate_id();
+    MergeOutcome { status: MergeStatus::Merged, tree: Some(merged), conflicts }
+}
+
+/// Move in the other tree and in `base` the files renamed in one tree, so that changes made
+/// on both sides to a renamed file are merged together
+///
+/// Files renamed on both sides, or whose destination already exists, are left in place.
+fn follow_renames(base: &mut Node, n1: &mut Node, n2: &mut Node, diff_options: &DiffOptions) {
+    if !diff_options.detect_renames || !base.is_tree() || !n1.is_tree() || !n2.is_tree() {
+        return;
+    }
+    let diff_options = DiffOptions { detect_copies: false, ..diff_options.clone() };
+    let renames = |other: &Node| -> Vec<(PathBuf, PathBuf)> {
+        diff_repository_trees_with_options(base, other, &diff_options)
+            .into_iter()
+            .filter(|change| change.kind == ChangeKind::Renamed)
+            .filter_map(|change| Some((change.old_path?, change.path)))
+            .collect()
+    };
+    let renames1 = renames(n1);
+    let renames2 = renames(n2);
+
+    let move_blob = |root: &mut Node, old_path: &Path, new_path: &Path| -> bool {
+        let exists = |path: &Path| root.get_node_at_path(path).is_some_and(|node| node.is_blob());
+        if !exists(old_path) || exists(new_path) {
+            return false;
+        }
+        let node = root.remove_node_at_path(old_path).expect("the blob exists");
+        root.insert_node_at_path(new_path, node)
+    };
+
+    let mut moved = false;
+    for (renames, other, other_renames) in [(&renames1, &mut *n2, &renames2), (&renames2, &mut *n1, &renames1)] {
+        for (old_path, new_path) in renames.iter() {
+            if other_renames.iter().any(|(other_old_path, _)| other_old_path == old_path) {
+                continue;
+            }
+            if move_blob(other, old_path, new_path) {
+                move_blob(base, old_path, new_path);
+                moved = true;
+            }
+        }
+    }
+
+    if moved {
+        base.generate_id();
+        n1.generate_id();
+        n2.generate_id();
+    }
+}
+
+/// Check if two nodes are the same, blob ids not including the mode of the file
+fn is_same_node(n1: Option<&Node>, n2: Option<&Node>) -> bool {
+    match (n1, n2) {
+        (Some(n1), Some(n2)) => Node::is_same_type(n1, n2) && n1.get_id() == n2.get_id() && n1.get_mode() == n2.get_mode(),
+        (None, None) => true,
+        _ => false
     }
-    
+}
+
+fn find_child(children: &[Node], name: &str, is_tree: bool) -> Option<Node> {
+    children.iter()
+        .find(|child| child.get_name() == name && child.is_tree() == is_tree)
+        .cloned()
+}
+
+/// Merge two nodes of the same type, `base` being ignored if it has another type
+fn merge_nodes(base: Option<&Node>, n1: &Node, n2: &Node, options: &MergeOptions, path: &Path, conflicts: &mut Vec<Conflict>) -> Node {
+    let base = base.filter(|base| Node::is_same_type(base, n1));
+
     match (n1, n2) {
-        (BlobNode(mut b1), BlobNode(b2)) => {
-            match mode {
+        (BlobNode(b1), BlobNode(b2)) => {
+            let mut blob = b1.clone();
+            let base_blob = match base {
+                Some(BlobNode(base_blob)) => Some(base_blob),
+                _ => None
+            };
+            let conflicted = match options.mode {
                 Mode::Partial => {
-                    Some(BlobNode(b2))
+                    blob = b2.clone();
+                    false
                 },
                 Mode::Complete => {
-                    merge_blob(&mut b1, &b2);
-                    Some(BlobNode(b1))
-                }
+                    let driver_merge = options.get_driver(path).and_then(|driver| {
+                        let base_content = base_blob.map(loaded_content);
+                        driver.merge(base_content.as_deref(), &loaded_content(b1), &loaded_content(b2))
+                    });
+                    match driver_merge {
+                        Some(driver_merge) => {
+                            blob.set_content(driver_merge.content);
+                            !driver_merge.conflicts.is_empty()
+                        },
+                        None => merge_blob(base_blob, &mut blob, b2, path, options),
+                    }
+                },
+            };
+            let mode_conflicted = options.mode == Mode::Complete
+                && match merge_mode(base_blob.map(|base_blob| base_blob.get_mode()), b1.get_mode(), b2.get_mode()) {
+                    Some(mode) => {
+                        blob.set_mode(mode);
+                        false
+                    },
+                    None => true
+                };
+            let conflicted = conflicted || mode_conflicted;
+            if conflicted {
+                conflicts.push(Conflict {
+                    kind: if base.is_some() { ConflictKind::Content } else { ConflictKind::AddAdd },
+                    path: path.to_path_buf(),
+                    base_id: base.map(|base| base.get_id()),
+                    ours_id: Some(n1.get_id()),
+                    theirs_id: Some(n2.get_id()),
+                });
             }
+            BlobNode(blob)
+        },
+        (TreeNode(t1), TreeNode(t2)) => {
+            let base_children: &[Node] = match base {
+                Some(TreeNode(base_tree)) => base_tree.get_children_ref(),
+                _ => &[]
+            };
+            let children = merge_children(base_children, t1.get_children_ref(), t2.get_children_ref(), options, path, conflicts);
+            let mut tree = t1.clone();
+            tree.set_children(children);
+            TreeNode(tree)
         },
-        (TreeNode(mut t1), TreeNode(t2)) => {
-            for node1 in t1.get_children() {
-                let name_node1 = node1.get_name();
-                for node2 in t2.get_children() {
-                    if name_node1 == node2.get_name() && Node::is_same_type(&node1, &node2) {
-                        if let Some(result) = merge_repository_trees(node1.clone(), node2, mode) {
-                            t1.replace_node_among_children(result);
-                        }
+        _ => n1.clone()
+    }
+}
+
+/// Merge children of a directory, children being matched by name and type
+fn merge_children(
+    base: &[Node],
+    children1: &[Node],
+    children2: &[Node],
+    options: &MergeOptions,
+    path: &Path,
+    conflicts: &mut Vec<Conflict>
+) -> Vec<Node> {
+    let mut keys: Vec<(String, bool)> = Vec::new();
+    for node in children1.iter().chain(children2.iter()).chain(base.iter()) {
+        let key = (node.get_name(), node.is_tree());
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    let mut merged = Vec::new();
+    for (name, is_tree) in keys.iter() {
+        let child_path = path.join(name);
+        let base_node = find_child(base, name, *is_tree);
+        let node1 = find_child(children1, name, *is_tree);
+        let node2 = find_child(children2, name, *is_tree);
+
+        let result = if is_same_node(node1.as_ref(), node2.as_ref()) || is_same_node(base_node.as_ref(), node2.as_ref()) {
+            node1
+        } else if is_same_node(base_node.as_ref(), node1.as_ref()) {
+            node2
+        } else {
+            match (node1, node2) {
+                (Some(node1), Some(node2)) => Some(merge_nodes(base_node.as_ref(), &node1, &node2, options, &child_path, conflicts)),
+                // Deleted on one side and modified on the other, modifications are kept
+                (node1, node2) => match options.get_strategy(&child_path) {
+                    Strategy::Ours => node1,
+                    Strategy::Theirs => node2,
+                    _ => {
+                        conflicts.push(Conflict {
+                            kind: ConflictKind::ModifyDelete,
+                            path: child_path,
+                            base_id: base_node.as_ref().map(|node| node.get_id()),
+                            ours_id: node1.as_ref().map(|node| node.get_id()),
+                            theirs_id: node2.as_ref().map(|node| node.get_id()),
+                        });
+                        node1.or(node2)
                     }
                 }
             }
+        };
 
-            for node in t2.get_children().iter() {
-                if !t1.exist_node_same_name_and_type(node) {
-                    t1.add_node(node.to_owned())
-                }
+        if let Some(node) = result {
+            merged.push(node);
+        }
+    }
+
+    // A file and a directory with the same name can't both be written, unless they already were
+    for (name, _) in keys.iter().filter(|(_, is_tree)| !*is_tree) {
+        let blob_kept = merged.iter().any(|node| node.get_name() == *name && !node.is_tree());
+        let tree_kept = merged.iter().any(|node| node.get_name() == *name && node.is_tree());
+        let in_base = find_child(base, name, false).is_some() && find_child(base, name, true).is_some();
+        if blob_kept && tree_kept && !in_base {
+            // Each side is represented by the entry it changed, whatever its type
+            let changed = |children: &[Node]| {
+                [false, true].iter()
+                    .filter_map(|is_tree| find_child(children, name, *is_tree))
+                    .find(|node| !is_same_node(find_child(base, name, node.is_tree()).as_ref(), Some(node)))
+                    .or_else(|| children.iter().find(|node| node.get_name() == *name).cloned())
+                    .map(|node| node.get_id())
+            };
+            conflicts.push(Conflict {
+                kind: ConflictKind::FileDirectory,
+                path: path.join(name),
+                base_id: base.iter().find(|node| node.get_name() == *name).map(|node| node.get_id()),
+                ours_id: changed(children1),
+                theirs_id: changed(children2),
+            });
+            let label = if find_child(children1, name, false).is_some() { &options.ours_label } else { &options.theirs_label };
+            if let Some(BlobNode(blob)) = merged.iter_mut().find(|node| node.get_name() == *name && !node.is_tree()) {
+                blob.set_name(format!("{}~{}", name, label.replace(['/', '\\'], "_")));
             }
-            
-            Some(TreeNode(t1))
+        }
+    }
+    merged
+}
+
+/// Merge the content of two blobs into `b1`
+///
+/// # Returns
+///
+/// `true` if both sides changed the same regions and the strategy of `path` left conflict
+/// markers around them
+fn merge_blob(base: Option<&Blob>, b1: &mut Blob, b2: &Blob, path: &Path, options: &MergeOptions) -> bool {
+    let strategy = options.get_strategy(path);
+    let contents = (
+        base.map(|base| String::from_utf8(loaded_content(base))).transpose(),
+        String::from_utf8(loaded_content(b1)),
+        String::from_utf8(loaded_content(b2)),
+    );
+    match contents {
+        (Ok(base), Ok(content1), Ok(content2)) => {
+            let (content, conflicted) = merge_content(base.as_deref(), &content1, &content2, strategy, options);
+            b1.set_content(content);
+            conflicted
         },
-        _ => None
+        // Binary content can't be merged line by line, the current version is kept until
+        // the conflict is resolved
+        _ => match strategy {
+            Strategy::Ours => false,
+            Strategy::Theirs => {
+                b1.set_content(loaded_content(b2));
+                false
+            },
+            _ => true
+        }
     }
 }
 
-fn merge_blob(b1: &mut Blob, b2: &Blob) {
-    b1.set_content(merge_content(&b1.get_content(), &b2.get_content()));
+/// Content of a blob of a merged tree, whose contents are loaded before merging
+fn loaded_content(blob: &Blob) -> Vec<u8> {
+    blob.get_content().unwrap_or_default()
 }
 
-fn merge_content(content1: &String, content2: &String) -> String {
-    let mut result = String::new();
-    let mut in_conflict = false;
-
-    for diff in diff::lines(&content1, &content2) {
-        match diff {
-            diff::Result::Left(old) => {
-                if !in_conflict {
-                    result.push_str("\n<<<<<< HEAD (current change)\n");
-                    in_conflict = true;
-                }
-                result.push_str(old);
+/// Merge the modes of a file, a mode changed on a single side being kept
+///
+/// # Returns
+///
+/// The merged mode, or `None` if both sides changed it differently, the current mode being
+/// kept then
+fn merge_mode(base: Option<EntryMode>, mode1: EntryMode, mode2: EntryMode) -> Option<EntryMode> {
+    if mode1 == mode2 || base == Some(mode2) {
+        Some(mode1)
+    } else if base == Some(mode1) {
+        Some(mode2)
+    } else {
+        None
+    }
+}
+
+/// Chunk of a merge, either kept by both sides or changed by at least one side
+enum Chunk<'a> {
+    Resolved(Vec<&'a str>),
+    /// Lines of the base, current and incoming versions
+    Conflict(Vec<&'a str>, Vec<&'a str>, Vec<&'a str>),
+}
+
+/// Position of each `base` line in `other`, for lines kept by `other`
+fn matching_lines(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+    let (mut base_position, mut other_position) = (0, 0);
+    for result in diff::slice(base, other) {
+        match result {
+            diff::Result::Left(_) => base_position += 1,
+            diff::Result::Right(_) => other_position += 1,
+            diff::Result::Both(_, _) => {
+                matches[base_position] = Some(other_position);
+                base_position += 1;
+                other_position += 1;
             }
-            diff::Result::Right(new) => {
-                if in_conflict {
-                    result.push_str("\n======\n");
-                }
-                result.push_str(new);
-                result.push_str("\n>>>>>> (incoming change)\n");
-                in_conflict = false; // End the conflict block
+        }
+    }
+    matches
+}
+
+/// Split three versions of a text in chunks, as diff3 does
+///
+/// Stable lines are kept by both sides. Between them, a region changed by a single side, or
+/// identically by both, is resolved, others are conflicts.
+fn merge_chunks<'a>(base: &[&'a str], lines1: &[&'a str], lines2: &[&'a str]) -> Vec<Chunk<'a>> {
+    let matches1 = matching_lines(base, lines1);
+    let matches2 = matching_lines(base, lines2);
+    let mut chunks = Vec::new();
+    let (mut base_position, mut position1, mut position2) = (0, 0, 0);
+
+    loop {
+        let next_stable = (base_position..base.len())
+            .find_map(|i| Some((i, matches1[i]?, matches2[i]?)));
+        let (base_end, end1, end2) = next_stable.unwrap_or((base.len(), lines1.len(), lines2.len()));
+
+        if (base_end, end1, end2) != (base_position, position1, position2) {
+            let region = &base[base_position..base_end];
+            let region1 = &lines1[position1..end1];
+            let region2 = &lines2[position2..end2];
+
+            if region1 == region2 || region == region2 {
+                chunks.push(Chunk::Resolved(region1.to_vec()));
+            } else if region == region1 {
+                chunks.push(Chunk::Resolved(region2.to_vec()));
+            } else {
+                chunks.push(Chunk::Conflict(region.to_vec(), region1.to_vec(), region2.to_vec()));
             }
-            diff::Result::Both(common, _) => {
-                if in_conflict {
-                    result.push_str("\n======\n>>>>>> (incoming change)\n");
-                    in_conflict = false; // Close the conflict block
+        }
+
+        if next_stable.is_none() {
+            return chunks;
+        }
+        chunks.push(Chunk::Resolved(vec![base[base_end]]));
+        (base_position, position1, position2) = (base_end + 1, end1 + 1, end2 + 1);
+    }
+}
+
+/// Split two versions of a text in chunks when their common ancestor is unknown, every
+/// difference being a conflict
+fn merge_chunks_two_way<'a>(lines1: &[&'a str], lines2: &[&'a str]) -> Vec<Chunk<'a>> {
+    let mut chunks = Vec::new();
+    let (mut region1, mut region2) = (Vec::new(), Vec::new());
+    for result in diff::slice(lines1, lines2) {
+        match result {
+            diff::Result::Left(line) => region1.push(*line),
+            diff::Result::Right(line) => region2.push(*line),
+            diff::Result::Both(line, _) => {
+                if !region1.is_empty() || !region2.is_empty() {
+                    chunks.push(Chunk::Conflict(vec![], region1, region2));
+                    (region1, region2) = (Vec::new(), Vec::new());
                 }
-                result.push_str(common);
+                chunks.push(Chunk::Resolved(vec![*line]));
             }
         }
     }
+    if !region1.is_empty() || !region2.is_empty() {
+        chunks.push(Chunk::Conflict(vec![], region1, region2));
+    }
+    chunks
+}
+
+fn push_lines(result: &mut String, lines: &[&str]) {
+    for line in lines {
+        result.push_str(line);
+    }
+}
+
+/// Add the newline missing at the end of the last line of a file before writing after it
+fn end_line(result: &mut String) {
+    if !result.is_empty() && !result.ends_with('\n') {
+        result.push('\n');
+    }
+}
 
-    if in_conflict {
-        result.push_str("\n======\n>>>>>> (incoming change)\n");
+fn push_marker(result: &mut String, marker: char, label: &str) {
+    end_line(result);
+    result.push_str(&marker.to_string().repeat(MARKER_SIZE));
+    if !label.is_empty() {
+        result.push(' ');
+        result.push_str(label);
     }
+    result.push('\n');
+}
 
-    result
+/// Write a conflict with markers in the `ConflictStyle` of `options`
+fn push_conflict(result: &mut String, base: &[&str], lines1: &[&str], lines2: &[&str], options: &MergeOptions) {
+    let (mut lines1, mut lines2) = (lines1, lines2);
+    let mut suffix: &[&str] = &[];
+    if options.conflict_style == ConflictStyle::ZDiff3 {
+        // Lines both sides agree on at the edges of the conflict are moved out of it
+        let prefix = lines1.iter().zip(lines2.iter()).take_while(|(line1, line2)| line1 == line2).count();
+        push_lines(result, &lines1[..prefix]);
+        (lines1, lines2) = (&lines1[prefix..], &lines2[prefix..]);
+
+        let suffix_size = lines1.iter().rev().zip(lines2.iter().rev()).take_while(|(line1, line2)| line1 == line2).count();
+        suffix = &lines1[lines1.len() - suffix_size..];
+        (lines1, lines2) = (&lines1[..lines1.len() - suffix_size], &lines2[..lines2.len() - suffix_size]);
+    }
+
+    push_marker(result, '<', &options.ours_label);
+    push_lines(result, lines1);
+    if options.conflict_style != ConflictStyle::Merge {
+        push_marker(result, '|', &options.base_label);
+        push_lines(result, base);
+    }
+    push_marker(result, '=', "");
+    push_lines(result, lines2);
+    push_marker(result, '>', &options.theirs_label);
+    push_lines(result, suffix);
+}
+
+/// Merge texts, regions changed by both sides being resolved with `strategy`
+///
+/// Without `base`, every difference between both texts is considered as changed by both sides.
+///
+/// # Returns
+///
+/// The merged text and `true` if it contains conflict markers
+fn merge_content(base: Option<&str>, content1: &str, content2: &str, strategy: Strategy, options: &MergeOptions) -> (String, bool) {
+    let lines1: Vec<&str> = content1.split_inclusive('\n').collect();
+    let lines2: Vec<&str> = content2.split_inclusive('\n').collect();
+    let chunks = match base {
+        Some(base) => merge_chunks(&base.split_inclusive('\n').collect::<Vec<&str>>(), &lines1, &lines2),
+        None => merge_chunks_two_way(&lines1, &lines2),
+    };
+
+    let mut result = String::new();
+    let mut conflicted = false;
+    for chunk in chunks {
+        match chunk {
+            Chunk::Resolved(lines) => push_lines(&mut result, &lines),
+            Chunk::Conflict(_, lines1, _) if strategy == Strategy::Ours => push_lines(&mut result, &lines1),
+            Chunk::Conflict(_, _, lines2) if strategy == Strategy::Theirs => push_lines(&mut result, &lines2),
+            Chunk::Conflict(_, lines1, lines2) if strategy == Strategy::Union => {
+                let same_line = |line1: &&str, line2: &&str| line1.trim_end_matches('\n') == line2.trim_end_matches('\n');
+                let unique2: Vec<&str> = lines2.into_iter()
+                    .filter(|line2| !lines1.iter().any(|line1| same_line(line1, line2)))
+                    .collect();
+                push_lines(&mut result, &lines1);
+                if !unique2.is_empty() {
+                    end_line(&mut result);
+                    push_lines(&mut result, &unique2);
+                }
+            },
+            Chunk::Conflict(base, lines1, lines2) => {
+                conflicted = true;
+                push_conflict(&mut result, &base, &lines1, &lines2, options);
+            }
+        }
+    }
+    (result, conflicted)
 }
 
 #[cfg(test)]
 mod test {
-    use crate::features::merge_repository_trees::merge_blob;
+    use std::path::{Path, PathBuf};
+
+    use crate::features::merge_drivers::JsonDriver;
+    use crate::features::merge_repository_trees::{ConflictKind, ConflictStyle, merge_blob, merge_content, merge_repository_trees, merge_repository_trees_with_options, MergeOptions, MergeOutcome, MergeStatus, Mode, Strategy};
     use crate::models::blob::Blob;
+    use crate::models::entry_mode::EntryMode;
+    use crate::models::node::Node;
+
+    fn blob(name: &str, content: &str) -> Node {
+        Node::create_blob_node(name.to_string(), content, PathBuf::from(name))
+    }
+
+    fn root(children: Vec<Node>) -> Node {
+        Node::create_tree_node("".to_string(), children, PathBuf::new())
+    }
+
+    fn content(root: &Node, path: &str) -> Option<String> {
+        root.get_node_at_path(Path::new(path))
+            .and_then(|node| node.get_content().ok())
+            .map(|content| String::from_utf8(content).unwrap())
+    }
+
+    #[test]
+    fn should_merge_non_overlapping_changes() {
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "A\nb\nc\nd\ne\n";
+        let theirs = "a\nb\nc\nd\nE\nf\n";
+
+        assert_eq!(merge_content(Some(base), ours, theirs, Strategy::Normal, &MergeOptions::default()), ("A\nb\nc\nd\nE\nf\n".to_string(), false));
+        assert_eq!(merge_content(Some(base), "b\nc\nd\ne\n", base, Strategy::Normal, &MergeOptions::default()), ("b\nc\nd\ne\n".to_string(), false));
+        assert_eq!(merge_content(Some(base), "a\nb\nX\nd\ne\n", "a\nb\nX\nd\ne\n", Strategy::Normal, &MergeOptions::default()), ("a\nb\nX\nd\ne\n".to_string(), false));
+    }
+
+    #[test]
+    fn should_mark_conflicts_only_where_both_sides_changed() {
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "A\nb\nc\nours\ne\n";
+        let theirs = "a\nb\nc\ntheirs\ne";
+
+        let (merged, conflicted) = merge_content(Some(base), ours, theirs, Strategy::Normal, &MergeOptions::default());
+
+        assert!(conflicted);
+        assert_eq!(merged, "A\nb\nc\n<<<<<<< HEAD\nours\ne\n=======\ntheirs\ne\n>>>>>>> incoming\n");
+    }
+
+    #[test]
+    fn should_merge_trees_with_common_ancestor() {
+        let base = root(vec![
+            blob("kept.txt", "1\n2\n3\n"),
+            blob("deleted.txt", "old\n"),
+            blob("modify_delete.txt", "base\n"),
+            blob("same.txt", "same\n"),
+        ]);
+        let ours = root(vec![
+            blob("kept.txt", "one\n2\n3\n"),
+            blob("modify_delete.txt", "ours\n"),
+            blob("same.txt", "same\n"),
+            blob("ours.txt", "new\n"),
+        ]);
+        let theirs = root(vec![
+            blob("kept.txt", "1\n2\nthree\n"),
+            blob("deleted.txt", "old\n"),
+            blob("theirs.txt", "new\n"),
+        ]);
+
+        let outcome = merge_repository_trees(Some(base), ours, theirs, &Mode::Complete);
+        let merged = outcome.tree.unwrap();
+
+        assert_eq!(content(&merged, "kept.txt").unwrap(), "one\n2\nthree\n");
+        assert!(content(&merged, "deleted.txt").is_none());
+        assert_eq!(content(&merged, "modify_delete.txt").unwrap(), "ours\n");
+        assert!(content(&merged, "same.txt").is_none());
+        assert_eq!(content(&merged, "ours.txt").unwrap(), "new\n");
+        assert_eq!(content(&merged, "theirs.txt").unwrap(), "new\n");
+        assert_eq!(outcome.conflicts.len(), 1);
+        assert_eq!(outcome.conflicts[0].kind, ConflictKind::ModifyDelete);
+        assert_eq!(outcome.conflicts[0].path, PathBuf::from("modify_delete.txt"));
+        assert!(outcome.conflicts[0].base_id.is_some());
+        assert!(outcome.conflicts[0].ours_id.is_some());
+        assert!(outcome.conflicts[0].theirs_id.is_none());
+    }
+
+    #[test]
+    fn should_report_conflicts() {
+        let base = root(vec![
+            Node::create_tree_node("src".to_string(), vec![blob("lib.rs", "a\nb\n")], PathBuf::from("src")),
+        ]);
+        let ours = root(vec![
+            Node::create_tree_node("src".to_string(), vec![blob("lib.rs", "ours\nb\n")], PathBuf::from("src")),
+            blob("docs", "file\n"),
+            blob("new.txt", "ours\n"),
+        ]);
+        let theirs = root(vec![
+            Node::create_tree_node("src".to_string(), vec![blob("lib.rs", "theirs\nb\n")], PathBuf::from("src")),
+            Node::create_tree_node("docs".to_string(), vec![blob("guide.md", "guide\n")], PathBuf::from("docs")),
+            blob("new.txt", "theirs\n"),
+        ]);
+
+        let outcome = merge_repository_trees(Some(base), ours, theirs, &Mode::Complete);
+
+        assert_eq!(outcome.status, MergeStatus::Merged);
+        assert!(!outcome.is_clean());
+        let mut conflicts: Vec<(ConflictKind, PathBuf)> = outcome.conflicts.iter()
+            .map(|conflict| (conflict.kind, conflict.path.clone()))
+            .collect();
+        conflicts.sort_by(|c1, c2| c1.1.cmp(&c2.1));
+        assert_eq!(conflicts, vec![
+            (ConflictKind::FileDirectory, PathBuf::from("docs")),
+            (ConflictKind::AddAdd, PathBuf::from("new.txt")),
+            (ConflictKind::Content, PathBuf::from("src/lib.rs")),
+        ]);
+        let content_conflict = outcome.conflicts.iter().find(|conflict| conflict.kind == ConflictKind::Content).unwrap();
+        assert!(content_conflict.base_id.is_some() && content_conflict.ours_id != content_conflict.theirs_id);
+    }
+
+    #[test]
+    fn should_rename_file_in_conflict_with_directory() {
+        let ours = root(vec![blob("docs", "file\n")]);
+        let theirs = root(vec![
+            Node::create_tree_node("docs".to_string(), vec![blob("guide.md", "guide\n")], PathBuf::from("docs")),
+        ]);
+        let options = MergeOptions { ours_label: "feature/docs".to_string(), ..MergeOptions::default() };
+
+        let outcome = merge_repository_trees_with_options(Some(root(vec![])), ours.clone(), theirs.clone(), &options);
+        let reversed = merge_repository_trees(Some(root(vec![])), theirs, ours, &Mode::Complete);
+
+        let children = |outcome: &MergeOutcome| -> Vec<(String, bool)> {
+            outcome.tree.as_ref().unwrap().get_children_ref().unwrap().iter()
+                .map(|node| (node.get_name(), node.is_tree()))
+                .collect()
+        };
+        assert_eq!(children(&outcome), vec![("docs~feature_docs".to_string(), false), ("docs".to_string(), true)]);
+        assert_eq!(children(&reversed), vec![("docs~incoming".to_string(), false), ("docs".to_string(), true)]);
+        assert_eq!(outcome.conflicts.len(), 1);
+        assert_eq!(outcome.conflicts[0].kind, ConflictKind::FileDirectory);
+        assert_eq!(content(outcome.tree.as_ref().unwrap(), "docs~feature_docs"), Some("file\n".to_string()));
+        assert_eq!(content(outcome.tree.as_ref().unwrap(), "docs/guide.md"), Some("guide\n".to_string()));
+    }
+
+    #[test]
+    fn should_tell_identical_roots_from_type_mismatch() {
+        let tree = root(vec![blob("a.txt", "a\n")]);
+
+        let up_to_date = merge_repository_trees(None, tree.clone(), tree.clone(), &Mode::Complete);
+        let mismatch = merge_repository_trees(None, tree, blob("a.txt", "a\n"), &Mode::Complete);
+
+        assert_eq!(up_to_date.status, MergeStatus::UpToDate);
+        assert!(up_to_date.tree.is_none() && up_to_date.is_clean());
+        assert_eq!(mismatch.status, MergeStatus::TypeMismatch);
+        assert!(mismatch.tree.is_none());
+    }
 
     #[test]
     fn test_merge() {
@@ -123,20 +890,181 @@ print(add(2, 4))"#.to_string();
         let b2 = Blob::new("blob2".to_string(), text2);
 
         let result = r#"def add(a, b):
-<<<<<< HEAD (current change)
+<<<<<<< HEAD
     return a + b
-======
+=======
     return a + b + 1
->>>>>> (incoming change)
+>>>>>>> incoming
+
 # Test
-<<<<<< HEAD (current change)
+<<<<<<< HEAD
 print(add(2, 3))
-======
+=======
 print(add(2, 4))
->>>>>> (incoming change)
+>>>>>>> incoming
 "#.to_string();
 
-        merge_blob(&mut b1, &b2);
-        assert_eq!(b1.get_content(), result);
+        merge_blob(None, &mut b1, &b2, Path::new("blob1"), &MergeOptions::default());
+        assert_eq!(b1.get_content().unwrap(), result.into_bytes());
+    }
+
+    #[test]
+    fn should_merge_file_modes() {
+        let executable = |name: &str, content: &str| {
+            let mut blob = Blob::new(name.to_string(), content);
+            blob.set_mode(EntryMode::Executable);
+            Node::BlobNode(blob)
+        };
+        let mode = |root: &Node, path: &str| root.get_node_at_path(Path::new(path)).unwrap().get_mode();
+        let base = root(vec![blob("run.sh", "echo 1\n"), blob("build.sh", "a\nb\nc\n")]);
+        let ours = root(vec![executable("run.sh", "echo 1\n"), blob("build.sh", "a2\nb\nc\n")]);
+        let theirs = root(vec![blob("run.sh", "echo 2\n"), executable("build.sh", "a\nb\nc2\n")]);
+
+        let outcome = merge_repository_trees(Some(base), ours, theirs, &Mode::Complete);
+
+        assert!(outcome.is_clean());
+        let tree = outcome.tree.unwrap();
+        assert_eq!(content(&tree, "run.sh"), Some("echo 2\n".to_string()));
+        assert_eq!(mode(&tree, "run.sh"), EntryMode::Executable);
+        assert_eq!(content(&tree, "build.sh"), Some("a2\nb\nc2\n".to_string()));
+        assert_eq!(mode(&tree, "build.sh"), EntryMode::Executable);
+    }
+
+    #[test]
+    fn should_keep_current_binary_content_on_conflict() {
+        let base = Blob::new("image.png".to_string(), vec![0x89, b'P', 0xff]);
+        let mut b1 = Blob::new("image.png".to_string(), vec![0x89, b'P', 0xfe]);
+        let b2 = Blob::new("image.png".to_string(), vec![0x89, b'P', 0xfd]);
+
+        let conflicted = merge_blob(Some(&base), &mut b1, &b2, Path::new("image.png"), &MergeOptions::default());
+
+        assert!(conflicted);
+        assert_eq!(b1.get_content().unwrap(), vec![0x89, b'P', 0xfe]);
+    }
+
+    #[test]
+    fn should_resolve_conflicts_with_strategy() {
+        let base = "title\n- a\n";
+        let ours = "title\n- a\n- ours\n- both\n";
+        let theirs = "title\n- a\n- theirs\n- both\n";
+
+        assert_eq!(merge_content(Some(base), ours, theirs, Strategy::Ours, &MergeOptions::default()), (ours.to_string(), false));
+        assert_eq!(merge_content(Some(base), ours, theirs, Strategy::Theirs, &MergeOptions::default()), (theirs.to_string(), false));
+        assert_eq!(
+            merge_content(Some(base), ours, theirs, Strategy::Union, &MergeOptions::default()),
+            ("title\n- a\n- ours\n- both\n- theirs\n".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn should_pick_strategy_per_path() {
+        let base = root(vec![
+            blob("Cargo.lock", "v1\n"),
+            blob("CHANGELOG.md", "# Changes\n"),
+            blob("gone.lock", "v1\n"),
+            blob("main.rs", "base\n"),
+        ]);
+        let ours = root(vec![
+            blob("Cargo.lock", "v2\n"),
+            blob("CHANGELOG.md", "# Changes\n- ours\n"),
+            blob("main.rs", "ours\n"),
+        ]);
+        let theirs = root(vec![
+            blob("Cargo.lock", "v3\n"),
+            blob("CHANGELOG.md", "# Changes\n- theirs\n"),
+            blob("gone.lock", "v2\n"),
+            blob("main.rs", "theirs\n"),
+        ]);
+        let mut options = MergeOptions::default();
+        options.add_attribute("*.lock", Strategy::Theirs);
+        options.add_attribute("CHANGELOG.md", Strategy::Union);
+
+        let outcome = merge_repository_trees_with_options(Some(base), ours, theirs, &options);
+        let merged = outcome.tree.unwrap();
+
+        assert_eq!(content(&merged, "Cargo.lock").unwrap(), "v3\n");
+        assert_eq!(content(&merged, "gone.lock").unwrap(), "v2\n");
+        assert_eq!(content(&merged, "CHANGELOG.md").unwrap(), "# Changes\n- ours\n- theirs\n");
+        assert_eq!(outcome.conflicts.len(), 1);
+        assert_eq!(outcome.conflicts[0].path, PathBuf::from("main.rs"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_merge_with_registered_driver() {
+        let base = root(vec![blob("config.json", "{\"a\": 1, \"b\": 1}\n")]);
+        let ours = root(vec![blob("config.json", "{\"a\": 2, \"b\": 1}\n")]);
+        let theirs = root(vec![blob("config.json", "{\"b\": 2, \"a\": 1}\n")]);
+        let mut options = MergeOptions::default();
+        options.register_driver_for_extension("json", JsonDriver);
+
+        let outcome = merge_repository_trees_with_options(Some(base), ours, theirs, &options);
+
+        assert!(outcome.is_clean());
+        assert_eq!(content(&outcome.tree.unwrap(), "config.json").unwrap(), "{\n  \"a\": 2,\n  \"b\": 2\n}\n");
+    }
+
+    #[test]
+    fn should_write_conflicts_in_configured_style() {
+        let base = "fn main() {\n    run();\n}\n";
+        let ours = "fn main() {\n    init();\n    run(1);\n}\n";
+        let theirs = "fn main() {\n    init();\n    run(2);\n}\n";
+        let mut options = MergeOptions {
+            conflict_style: ConflictStyle::Diff3,
+            ours_label: "main".to_string(),
+            base_label: "merge base".to_string(),
+            theirs_label: "feature".to_string(),
+            ..MergeOptions::default()
+        };
+
+        let (diff3, _) = merge_content(Some(base), ours, theirs, Strategy::Normal, &options);
+        options.conflict_style = ConflictStyle::ZDiff3;
+        let (zdiff3, _) = merge_content(Some(base), ours, theirs, Strategy::Normal, &options);
+
+        assert_eq!(diff3, "fn main() {
+<<<<<<< main
+    init();
+    run(1);
+||||||| merge base
+    run();
+=======
+    init();
+    run(2);
+>>>>>>> feature
+}
+");
+        assert_eq!(zdiff3, "fn main() {
+    init();
+<<<<<<< main
+    run(1);
+||||||| merge base
+    run();
+=======
+    run(2);
+>>>>>>> feature
+}
+");
+    }
+
+    #[test]
+    fn should_carry_changes_across_renames() {
+        let lines = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let base = root(vec![
+            Node::create_tree_node("src".to_string(), vec![blob("a.rs", lines)], PathBuf::from("src")),
+        ]);
+        let ours = root(vec![
+            Node::create_tree_node("src".to_string(), vec![blob("a.rs", "1\n2\n3\n4\n5\n6\n7\neight\n")], PathBuf::from("src")),
+        ]);
+        let theirs = root(vec![
+            Node::create_tree_node("src".to_string(), vec![
+                Node::create_tree_node("lib".to_string(), vec![blob("a.rs", "one\n2\n3\n4\n5\n6\n7\n8\n")], PathBuf::from("src/lib")),
+            ], PathBuf::from("src")),
+        ]);
+
+        let outcome = merge_repository_trees_with_options(Some(base), ours, theirs, &MergeOptions::default());
+        let merged = outcome.tree.unwrap();
+
+        assert!(outcome.conflicts.is_empty());
+        assert!(content(&merged, "src/a.rs").is_none());
+        assert_eq!(content(&merged, "src/lib/a.rs").unwrap(), "one\n2\n3\n4\n5\n6\n7\neight\n");
+    }
+}
@@ -6,11 +6,12 @@ pub fn remove_element_from_repository_tree(
     root: &mut Node,
     element: &PathBuf,
 ) -> Result<(), RepTreeError> {
-    if let Some(pos) = root
-        .get_mut_children()
-        .and_then(|children| children.iter().position(|child| child.get_path() == *element)) 
-    {
-        root.get_mut_children().unwrap().remove(pos);
+    let found = root
+        .get_children_ref()
+        .and_then(|children| children.iter().find(|child| child.get_path() == *element))
+        .map(|child| (child.get_name(), child.is_tree()));
+    if let (Some((name, is_tree)), Node::TreeNode(tree)) = (found, &mut *root) {
+        tree.remove_child(&name, is_tree);
     } else {
         if let Some(children) = root.get_mut_children() {
             for child in children {
@@ -1,14 +1,16 @@
 use std::{fs, io};
 use std::cmp::PartialEq;
 use std::io::{ErrorKind, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use dit_file_encryptor::CompressedFile;
 
 use crate::error::RepTreeError;
+use crate::features::diff_repository_trees::{ChangeKind, diff_repository_trees};
 use crate::models::blob::Blob;
+use crate::models::checkout_journal::{CheckoutJournal, get_default_journal_path, get_temporary_path, JournalOperation};
+use crate::models::entry_mode::EntryMode;
 use crate::models::node::Node;
-use crate::models::tree::Tree;
 
 /// Represents the different operational modes for the program.
 ///
@@ -17,6 +19,9 @@ use crate::models::tree::Tree;
 /// - `Modify`: A modification mode, which allows for modifying a directory without deleting it.
 /// - `Complete`: A complete mode that may involve more extensive operations, including potentially
 ///   deleting and recreating directories. 
+///
+/// None of them removes the files deleted since a previous checkout without removing untracked
+/// files too, see `checkout_repository_tree`.
 #[derive(PartialEq)]
 pub enum Mode {
     Partial,
@@ -34,106 +39,630 @@ impl Mode {
     }
 }
 
+/// Options of `transcript_repository_tree_to_files_with_options`.
+///
+/// Files are written with their plain content. With `compress`, they are written compressed
+/// as in the object files, to be read with `CompressedFile`, which is only meant for exports.
+///
+/// With `force`, `checkout_repository_tree` overwrites and deletes files even if they have
+/// local changes.
+///
+/// Checkouts keep a `CheckoutJournal` while they run, in the directory `journal`, or next to
+/// the working directory by default, see `get_default_journal_path`, so that an interrupted
+/// checkout is detected by the next one, and can be resumed with `resume_checkout` or undone
+/// with `rollback_checkout`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CheckoutOptions {
+    pub compress: bool,
+    pub force: bool,
+    pub journal: Option<PathBuf>,
+}
+
+/// State of a checkout in progress
+struct Checkout<'a> {
+    directory: &'a Path,
+    options: &'a CheckoutOptions,
+    journal: Option<CheckoutJournal>,
+}
+
+impl Checkout<'_> {
+    /// Record `operation` on `path` in the journal if any, before doing it
+    fn record(&mut self, operation: JournalOperation, path: &Path) -> Result<(), RepTreeError> {
+        match self.journal.as_mut() {
+            Some(journal) => journal.record(operation, path),
+            None => Ok(())
+        }
+    }
+
+    /// Back up the file at `file_path`, `path` relative to `directory`, in the journal if any
+    fn backup(&self, file_path: &Path, path: &Path) -> Result<(), RepTreeError> {
+        match self.journal.as_ref() {
+            Some(journal) => journal.backup(file_path, path),
+            None => Ok(())
+        }
+    }
+
+    /// Remove the journal once the checkout is done
+    fn finish(self) -> Result<(), RepTreeError> {
+        match self.journal {
+            Some(journal) => journal.remove(),
+            None => Ok(())
+        }
+    }
+}
+
+impl CheckoutOptions {
+    /// Path of the journal of the checkouts of `directory`
+    fn get_journal_path(&self, directory: &Path) -> Result<PathBuf, RepTreeError> {
+        match self.journal.as_ref() {
+            Some(journal_path) => Ok(journal_path.clone()),
+            None => get_default_journal_path(directory)
+        }
+    }
+}
+
+/// Write the files of a repository tree in the directory `path`
+///
+/// # Arguments
+///
+/// * `root` - root `Node` of the repository tree
+/// * `path` - existing directory receiving the files
+/// * `mode` - how existing files and directories are handled, see `Mode`
 pub fn transcript_repository_tree_to_files(root: &Node, path: &PathBuf, mode: &Mode) -> Result<(), RepTreeError> {
+    transcript_repository_tree_to_files_with_options(root, path, mode, &CheckoutOptions::default())
+}
+
+/// Write the files of a repository tree in the directory `path`
+///
+/// The writes are journaled as for `checkout_repository_tree`. A transcription of the same
+/// tree interrupted in `path` is resumed, while any other interrupted checkout is reported, to
+/// be resumed or rolled back first. An interrupted transcription is undone with
+/// `rollback_checkout`.
+///
+/// # Arguments
+///
+/// * `root` - root `Node` of the repository tree
+/// * `path` - existing directory receiving the files
+/// * `mode` - how existing files and directories are handled, see `Mode`
+/// * `options` - how files are written
+///
+/// # Returns
+///
+/// `RepTreeError::InterruptedCheckout` if another checkout was interrupted in `path`, or
+/// another `RepTreeError` if a file can't be written
+pub fn transcript_repository_tree_to_files_with_options(root: &Node, path: &PathBuf, mode: &Mode, options: &CheckoutOptions) -> Result<(), RepTreeError> {
     if !path.exists() {
         Err(RepTreeError::IoError(io::Error::new(ErrorKind::NotFound, "path not found")))?;
     }
-    match root {
-        Node::BlobNode(blob) => {
-            create_file(blob, path, mode)?;
-        },
+    let journal_path = options.get_journal_path(path)?;
+    // Transcriptions don't start from a tree, their journal having no current tree id
+    let journal = if CheckoutJournal::exists(&journal_path) {
+        let journal = CheckoutJournal::open(&journal_path)?;
+        if !journal.get_current_id().is_empty() || journal.get_target_id() != root.get_id() {
+            return Err(RepTreeError::InterruptedCheckout(journal_path));
+        }
+        journal
+    } else {
+        CheckoutJournal::create(&journal_path, "", &root.get_id())?
+    };
+    let mut checkout = Checkout { directory: path, options, journal: Some(journal) };
+    transcript_node(&mut checkout, root, Path::new(""), mode)?;
+    checkout.finish()
+}
+
+/// Write `node` in the directory `parent`, relative to the directory of `checkout`
+fn transcript_node(checkout: &mut Checkout, node: &Node, parent: &Path, mode: &Mode) -> Result<(), RepTreeError> {
+    let name = node.get_name();
+    let path = if name.is_empty() { parent.to_path_buf() } else { parent.join(name) };
+    match node {
+        Node::BlobNode(blob) => create_file(checkout, blob, &path, mode),
         Node::TreeNode(tree) => {
-            let directory_path = create_directory(tree, path, mode)?;
-            if directory_path != PathBuf::from("") {
-                for node in tree.get_children().iter() {
-                    transcript_repository_tree_to_files(node, &directory_path, mode)?;
+            if !create_directory(checkout, &path, mode)? {
+                return Ok(());
+            }
+            for child in tree.get_children_ref() {
+                transcript_node(checkout, child, &path, mode)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Update the files of `directory` from the repository tree `current` to the tree `target`
+///
+/// Only the files which differ between the trees are written. The files of `current` which
+/// aren't in `target` are deleted, as well as the directories they leave empty, while files
+/// which aren't in `current` are left untouched, unless `target` has a file at their path.
+///
+/// Unless `options.force` is set, nothing is written if a file to write or delete has a
+/// content different from its content in both trees, as it would lose local changes. Untracked
+/// files in the way of a file to write, in place of one of its directories or left in a
+/// directory it replaces, are reported even with `options.force`, as they are never deleted.
+///
+/// # Arguments
+///
+/// * `current` - root `Node` of the tree checked out in `directory`, an empty tree if none is
+/// * `target` - root `Node` of the tree to check out
+/// * `directory` - existing directory matching the roots of the trees
+/// * `options` - how files are written
+///
+/// # Returns
+///
+/// `RepTreeError::LocalChanges` with the paths of the files with local changes, relative to
+/// `directory`, `RepTreeError::InterruptedCheckout` if a journal is left by an interrupted
+/// checkout, see `CheckoutOptions`, or another `RepTreeError` if a file can't be written
+pub fn checkout_repository_tree(current: &Node, target: &Node, directory: &Path, options: &CheckoutOptions) -> Result<(), RepTreeError> {
+    if !directory.is_dir() {
+        Err(RepTreeError::IoError(io::Error::new(ErrorKind::NotFound, "path not found")))?;
+    }
+    let journal_path = options.get_journal_path(directory)?;
+    if CheckoutJournal::exists(&journal_path) {
+        return Err(RepTreeError::InterruptedCheckout(journal_path));
+    }
+    run_checkout(current, target, directory, options)
+}
+
+/// Finish a checkout of `directory` interrupted with its journal left, see `CheckoutOptions`
+///
+/// The checkout is run again from `current` to `target`, the files it already wrote being
+/// left as they are. Without a journal left, this is `checkout_repository_tree`.
+///
+/// # Arguments
+///
+/// * `current` - root `Node` of the tree the interrupted checkout started from
+/// * `target` - root `Node` of the tree the interrupted checkout was checking out
+/// * `directory` - existing directory matching the roots of the trees
+/// * `options` - how files are written, with the journal of the interrupted checkout
+///
+/// # Returns
+///
+/// `RepTreeError::UnexpectedComportment` if the journal is for other trees, or the errors of
+/// `checkout_repository_tree`
+pub fn resume_checkout(current: &Node, target: &Node, directory: &Path, options: &CheckoutOptions) -> Result<(), RepTreeError> {
+    if !directory.is_dir() {
+        Err(RepTreeError::IoError(io::Error::new(ErrorKind::NotFound, "path not found")))?;
+    }
+    let journal_path = options.get_journal_path(directory)?;
+    if CheckoutJournal::exists(&journal_path) {
+        let journal = CheckoutJournal::open(&journal_path)?;
+        if journal.get_current_id() != current.get_id() || journal.get_target_id() != target.get_id() {
+            return Err(RepTreeError::UnexpectedComportment(format!(
+                "The checkout journal at {} is for a checkout from {} to {}",
+                journal_path.display(), journal.get_current_id(), journal.get_target_id())));
+        }
+    }
+    run_checkout(current, target, directory, options)
+}
+
+/// Undo a checkout of `directory` interrupted with its journal left, see `CheckoutOptions`,
+/// restoring the files it overwrote or deleted. Without a journal left, nothing is done.
+///
+/// # Returns
+///
+/// `RepTreeError` if a file can't be restored
+pub fn rollback_checkout(directory: &Path, options: &CheckoutOptions) -> Result<(), RepTreeError> {
+    let journal_path = options.get_journal_path(directory)?;
+    if !CheckoutJournal::exists(&journal_path) {
+        return Ok(());
+    }
+    CheckoutJournal::open(&journal_path)?.rollback(directory)
+}
+
+fn run_checkout(current: &Node, target: &Node, directory: &Path, options: &CheckoutOptions) -> Result<(), RepTreeError> {
+    if !directory.is_dir() {
+        Err(RepTreeError::IoError(io::Error::new(ErrorKind::NotFound, "path not found")))?;
+    }
+    let changes = diff_repository_trees(current, target);
+
+    let mut locally_changed: Vec<PathBuf> = Vec::new();
+    for change in changes.iter() {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for root in [current, target] {
+            if let Some(node) = root.get_node_at_path(&change.path) {
+                collect_blob_paths(node, &change.path, &mut paths);
+            }
+        }
+        for path in paths {
+            let mut changed_paths = match target.get_node_at_path(&path) {
+                Some(_) => get_obstructions(current, directory, &path)?,
+                None => vec![]
+            };
+            let file_path = directory.join(&path);
+            let is_local_change = !options.force
+                && has_local_changes(current.get_node_at_path(&path), &file_path)?
+                && has_local_changes(target.get_node_at_path(&path), &file_path)?;
+            if is_local_change {
+                changed_paths.push(path);
+            }
+            for changed_path in changed_paths {
+                if !locally_changed.contains(&changed_path) {
+                    locally_changed.push(changed_path);
                 }
             }
         }
     }
+    if !locally_changed.is_empty() {
+        locally_changed.sort();
+        return Err(RepTreeError::LocalChanges(locally_changed));
+    }
+
+    let journal_path = options.get_journal_path(directory)?;
+    let journal = match CheckoutJournal::exists(&journal_path) {
+        true => CheckoutJournal::open(&journal_path)?,
+        false => CheckoutJournal::create(&journal_path, &current.get_id(), &target.get_id())?
+    };
+    let mut checkout = Checkout { directory, options, journal: Some(journal) };
+
+    // Deletions come first so that a file can replace a deleted directory and conversely
+    for change in changes.iter().filter(|change| matches!(change.kind, ChangeKind::Deleted | ChangeKind::TypeChanged)) {
+        if let Some(node) = current.get_node_at_path(&change.path) {
+            remove_tracked(&mut checkout, node, &change.path)?;
+            remove_empty_directories(&mut checkout, target, &change.path)?;
+        }
+    }
+    for change in changes.iter().filter(|change| change.kind != ChangeKind::Deleted) {
+        if let Some(node) = target.get_node_at_path(&change.path) {
+            write_node(&mut checkout, node, &change.path)?;
+        }
+    }
+    checkout.finish()
+}
+
+/// Add the paths of the blobs of `node`, `path` being the path of `node`
+fn collect_blob_paths(node: &Node, path: &Path, paths: &mut Vec<PathBuf>) {
+    match node.get_children_ref() {
+        Some(children) => {
+            for child in children {
+                collect_blob_paths(child, &path.join(child.get_name()), paths);
+            }
+        },
+        None => paths.push(path.to_path_buf())
+    }
+}
+
+/// Untracked files which would prevent writing a file at `path`: a file in place of one of its
+/// parent directories, or the files left in a directory in its place once the tracked files
+/// of `current` are deleted
+fn get_obstructions(current: &Node, directory: &Path, path: &Path) -> Result<Vec<PathBuf>, RepTreeError> {
+    let is_tracked_file = |path: &Path| current.get_node_at_path(path).is_some_and(|node| node.is_blob());
+    for parent in path.ancestors().skip(1).filter(|parent| !parent.as_os_str().is_empty()) {
+        let parent_path = directory.join(parent);
+        let exists = fs::symlink_metadata(&parent_path).is_ok();
+        if exists && !parent_path.is_dir() {
+            return Ok(if is_tracked_file(parent) { vec![] } else { vec![parent.to_path_buf()] });
+        }
+    }
+    let mut untracked: Vec<PathBuf> = Vec::new();
+    if fs::symlink_metadata(directory.join(path)).is_ok_and(|metadata| metadata.is_dir()) {
+        collect_untracked_files(current, directory, path, &mut untracked)?;
+    }
+    Ok(untracked)
+}
+
+/// Add the paths of the files under the directory `path` which aren't files of `current`
+fn collect_untracked_files(current: &Node, directory: &Path, path: &Path, untracked: &mut Vec<PathBuf>) -> Result<(), RepTreeError> {
+    for entry in fs::read_dir(directory.join(path)).map_err(RepTreeError::IoError)? {
+        let entry = entry.map_err(RepTreeError::IoError)?;
+        let entry_path = path.join(entry.file_name());
+        if entry.file_type().map_err(RepTreeError::IoError)?.is_dir() {
+            collect_untracked_files(current, directory, &entry_path, untracked)?;
+        } else if !current.get_node_at_path(&entry_path).is_some_and(|node| node.is_blob()) {
+            untracked.push(entry_path);
+        }
+    }
+    Ok(())
+}
+
+/// Check if the file at `file_path` exists with a content other than the content of `node`
+///
+/// Missing files and directories aren't local changes, as nothing would be lost by writing
+/// or deleting a file there.
+fn has_local_changes(node: Option<&Node>, file_path: &Path) -> Result<bool, RepTreeError> {
+    let metadata = match fs::symlink_metadata(file_path) {
+        Ok(metadata) if !metadata.is_dir() => metadata,
+        _ => return Ok(false)
+    };
+    let blob = match node {
+        Some(Node::BlobNode(blob)) => blob,
+        _ => return Ok(true)
+    };
+    let content = if metadata.file_type().is_symlink() {
+        fs::read_link(file_path).map_err(RepTreeError::IoError)?.to_string_lossy().as_bytes().to_vec()
+    } else {
+        fs::read(file_path).map_err(RepTreeError::IoError)?
+    };
+    Ok(content != blob.get_content()?)
+}
+
+/// Delete the files of `node` at `path`, keeping the files which aren't in `node`
+fn remove_tracked(checkout: &mut Checkout, node: &Node, path: &Path) -> Result<(), RepTreeError> {
+    let file_path = checkout.directory.join(path);
+    match node {
+        Node::BlobNode(_) => {
+            if fs::symlink_metadata(&file_path).is_ok_and(|metadata| !metadata.is_dir()) {
+                checkout.backup(&file_path, path)?;
+                checkout.record(JournalOperation::Delete, path)?;
+                fs::remove_file(&file_path).map_err(RepTreeError::IoError)?;
+            }
+        },
+        Node::TreeNode(tree) => {
+            for child in tree.get_children_ref() {
+                remove_tracked(checkout, child, &path.join(child.get_name()))?;
+            }
+            remove_directory_if_empty(checkout, path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove the directory at `path` if it exists and is empty
+///
+/// # Returns
+///
+/// Result with `true` if the directory was removed
+fn remove_directory_if_empty(checkout: &mut Checkout, path: &Path) -> Result<bool, RepTreeError> {
+    let directory_path = checkout.directory.join(path);
+    let is_directory = fs::symlink_metadata(&directory_path).is_ok_and(|metadata| metadata.is_dir());
+    if !is_directory || fs::read_dir(&directory_path).map_err(RepTreeError::IoError)?.next().is_some() {
+        return Ok(false);
+    }
+    checkout.record(JournalOperation::RemoveDirectory, path)?;
+    fs::remove_dir(&directory_path).map_err(RepTreeError::IoError)?;
+    Ok(true)
+}
+
+/// Remove the parent directories of a deleted `path` left empty, unless `target` has them
+fn remove_empty_directories(checkout: &mut Checkout, target: &Node, path: &Path) -> Result<(), RepTreeError> {
+    for parent in path.ancestors().skip(1).filter(|parent| !parent.as_os_str().is_empty()) {
+        let in_target = target.get_node_at_path(parent).is_some_and(|node| node.is_tree());
+        if in_target || !remove_directory_if_empty(checkout, parent)? {
+            break;
+        }
+    }
     Ok(())
 }
 
-fn create_file(blob: &Blob, path: &PathBuf, mode: &Mode) -> Result<(), RepTreeError> {
-    let file_path = path.join(blob.get_name());
-    if *mode != Mode::Partial || !file_path.is_file() {
-        let mut writer = CompressedFile::new(file_path.clone())
+/// Create the directory at `path` and its missing parents
+fn create_directories(checkout: &mut Checkout, path: &Path) -> Result<(), RepTreeError> {
+    let mut missing: Vec<&Path> = path.ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty() && !checkout.directory.join(ancestor).is_dir())
+        .collect();
+    missing.reverse();
+    for ancestor in missing {
+        checkout.record(JournalOperation::CreateDirectory, ancestor)?;
+        fs::create_dir(checkout.directory.join(ancestor)).map_err(RepTreeError::IoError)?;
+    }
+    Ok(())
+}
+
+/// Write `node` at `path`, creating the missing parent directories
+fn write_node(checkout: &mut Checkout, node: &Node, path: &Path) -> Result<(), RepTreeError> {
+    match node {
+        Node::BlobNode(blob) => {
+            let parent = path.parent().unwrap_or(Path::new(""));
+            create_directories(checkout, parent)?;
+            create_file(checkout, blob, path, &Mode::Modify)
+        },
+        Node::TreeNode(tree) => {
+            create_directories(checkout, path)?;
+            for child in tree.get_children_ref() {
+                write_node(checkout, child, &path.join(child.get_name()))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Write the file of `blob` at `path`, relative to the directory of `checkout`
+///
+/// The file is written to a temporary file in the same directory, then renamed over the
+/// existing file, so that an interruption never leaves a partially written file.
+fn create_file(checkout: &mut Checkout, blob: &Blob, path: &Path, mode: &Mode) -> Result<(), RepTreeError> {
+    let file_path = checkout.directory.join(path);
+    if *mode == Mode::Partial && file_path.is_file() {
+        return Ok(());
+    }
+    // Backed up before being recorded, so that rolling back never deletes a file without its
+    // backup, and kept in place until the new file is renamed over it
+    checkout.backup(&file_path, path)?;
+    checkout.record(JournalOperation::Write, path)?;
+    let temporary_path = write_temporary_file(blob, &file_path, checkout.options)?;
+    // Replaces a link rather than writing through it
+    fs::rename(&temporary_path, &file_path).map_err(RepTreeError::IoError)
+}
+
+/// Write the file of `blob` to the temporary file of `file_path`
+///
+/// # Returns
+///
+/// Result with the path of the temporary file, synced to the disk, which is removed if it
+/// can't be written
+fn write_temporary_file(blob: &Blob, file_path: &Path, options: &CheckoutOptions) -> Result<PathBuf, RepTreeError> {
+    let temporary_path = get_temporary_path(file_path)
+        .ok_or(RepTreeError::UnexpectedComportment(format!("Invalid file name {:?}", blob.get_name())))?;
+    if fs::symlink_metadata(&temporary_path).is_ok() {
+        fs::remove_file(&temporary_path).map_err(RepTreeError::IoError)?;
+    }
+    let written = write_content(blob, &temporary_path, options);
+    if written.is_err() && fs::symlink_metadata(&temporary_path).is_ok() {
+        // The error of the write is the one reported
+        let _ = fs::remove_file(&temporary_path);
+    }
+    written.map(|_| temporary_path)
+}
+
+/// Write the content of `blob` to `file_path`, syncing it to the disk so that the file it is
+/// renamed over is never replaced by an empty file after a crash
+fn write_content(blob: &Blob, file_path: &Path, options: &CheckoutOptions) -> Result<(), RepTreeError> {
+    if blob.get_mode() == EntryMode::Symlink {
+        return create_symlink(blob, file_path);
+    }
+    if options.compress {
+        let mut writer = CompressedFile::new(file_path.to_path_buf())
             .open_for_write()
             .map_err(|e| {
                 RepTreeError::Encryptor(format!("Error {e} reading file {:?}", file_path))
             })?;
+        writer.write_all(&blob.get_content()?).map_err(RepTreeError::IoError)?;
+    } else {
+        fs::write(file_path, blob.get_content()?).map_err(RepTreeError::IoError)?;
+    }
+    set_executable(file_path, blob.get_mode() == EntryMode::Executable)?;
+    fs::OpenOptions::new()
+        .write(true)
+        .open(file_path)
+        .and_then(|file| file.sync_all())
+        .map_err(RepTreeError::IoError)
+}
+
+#[cfg(unix)]
+fn create_symlink(blob: &Blob, file_path: &Path) -> Result<(), RepTreeError> {
+    let target = String::from_utf8_lossy(&blob.get_content()?).to_string();
+    std::os::unix::fs::symlink(target, file_path).map_err(RepTreeError::IoError)
+}
 
-        writer.write_all(blob.get_content().as_bytes()).map_err(RepTreeError::IoError)?;
+/// Without symbolic links, the file is written with the path the link points to as content
+#[cfg(not(unix))]
+fn create_symlink(blob: &Blob, file_path: &Path) -> Result<(), RepTreeError> {
+    fs::write(file_path, blob.get_content()?).map_err(RepTreeError::IoError)
+}
+
+/// Give the executable bit to the users who can read the file, or remove it
+#[cfg(unix)]
+fn set_executable(file_path: &Path, executable: bool) -> Result<(), RepTreeError> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(file_path).map_err(RepTreeError::IoError)?.permissions();
+    let current = permissions.mode();
+    let wanted = if executable { current | ((current & 0o444) >> 2) } else { current & !0o111 };
+    if wanted != current {
+        permissions.set_mode(wanted);
+        fs::set_permissions(file_path, permissions).map_err(RepTreeError::IoError)?;
     }
     Ok(())
 }
 
-fn create_directory(tree: &Tree, path: &PathBuf, mode: &Mode) -> Result<PathBuf, RepTreeError> {
-    let directory_path = path.join(tree.get_name());
+#[cfg(not(unix))]
+fn set_executable(_file_path: &Path, _executable: bool) -> Result<(), RepTreeError> {
+    Ok(())
+}
+
+/// Create the directory at `path`, relative to the directory of `checkout`, as `mode` requires
+///
+/// With `Mode::Complete`, the content of an existing directory is deleted, the deleted files
+/// being backed up.
+///
+/// # Returns
+///
+/// Result with `false` if the content of the directory mustn't be written, with `Mode::Partial`
+fn create_directory(checkout: &mut Checkout, path: &Path, mode: &Mode) -> Result<bool, RepTreeError> {
+    let directory_path = checkout.directory.join(path);
     let exist = directory_path.is_dir();
-    match mode { 
-        Mode::Partial => {
-            Ok(PathBuf::from(""))
-        },
-        Mode::Modify => {
-            if !exist {
-                fs::create_dir(directory_path.clone()).map_err(RepTreeError::IoError)?;
-            }
-            Ok(directory_path)
-        },
+    match mode {
+        Mode::Partial => return Ok(false),
+        Mode::Modify => {},
         Mode::Complete => {
             if exist {
-                fs::remove_dir_all(directory_path.clone()).map_err(RepTreeError::IoError)?;
+                remove_directory_content(checkout, path)?;
             }
-            fs::create_dir(directory_path.clone()).map_err(RepTreeError::IoError)?;
-            Ok(directory_path)
         }
     }
+    if !exist {
+        checkout.record(JournalOperation::CreateDirectory, path)?;
+        fs::create_dir(&directory_path).map_err(RepTreeError::IoError)?;
+    }
+    Ok(true)
 }
 
+/// Delete everything in the directory at `path`, relative to the directory of `checkout`
+fn remove_directory_content(checkout: &mut Checkout, path: &Path) -> Result<(), RepTreeError> {
+    for entry in fs::read_dir(checkout.directory.join(path)).map_err(RepTreeError::IoError)? {
+        let entry = entry.map_err(RepTreeError::IoError)?;
+        let entry_path = path.join(entry.file_name());
+        let file_path = entry.path();
+        if entry.file_type().map_err(RepTreeError::IoError)?.is_dir() {
+            remove_directory_content(checkout, &entry_path)?;
+            remove_directory_if_empty(checkout, &entry_path)?;
+        } else {
+            checkout.backup(&file_path, &entry_path)?;
+            checkout.record(JournalOperation::Delete, &entry_path)?;
+            fs::remove_file(&file_path).map_err(RepTreeError::IoError)?;
+        }
+    }
+    Ok(())
+}
 #[cfg(test)]
 mod tests {
     use std::fs;
     use std::fs::File;
     use std::io::Read;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
     use dit_file_encryptor::CompressedFile;
 
-    use crate::features::transcript_repository_to_files::{create_file, Mode};
+    use crate::error::RepTreeError;
+    use crate::features::transcript_repository_to_files::{Checkout, CheckoutOptions, create_file, Mode};
     use crate::models::blob::Blob;
 
+    /// Write the file of `blob` in the current directory, without journal
+    fn create_blob_file(blob: &Blob, mode: &Mode, options: &CheckoutOptions) -> Result<(), RepTreeError> {
+        let mut checkout = Checkout { directory: Path::new(""), options, journal: None };
+        create_file(&mut checkout, blob, Path::new(&blob.get_name()), mode)
+    }
+
     #[test]
     fn test_should_create_file(){
         let blob = Blob::new("blob".to_string(), "Hello, World".to_string());
-        create_file(&blob, &PathBuf::from(""), &Mode::Complete).unwrap();
-        let mut content = String::from("");
-        let mut reader = CompressedFile::new(PathBuf::from("blob"))
-            .open_for_read()
-            .unwrap();
-        reader.read_to_string(&mut content).unwrap();
+        create_blob_file(&blob, &Mode::Complete, &CheckoutOptions::default()).unwrap();
+        let content = fs::read_to_string("blob").unwrap();
         
         assert!(PathBuf::from("blob").is_file());
         assert_eq!("Hello, World", content);
         
         fs::remove_file("blob").unwrap();
     }
-    
+
     #[test]
-    #[should_panic]
-    fn test_should_not_create_file(){
-        File::create("blob1").unwrap();
-        
-        let blob = Blob::new("blob1".to_string(), "Hello, World".to_string());
-        create_file(&blob, &PathBuf::from(""), &Mode::Partial).unwrap();
-        
+    fn test_should_create_compressed_file(){
+        let blob = Blob::new("compressed_blob".to_string(), "Hello, World".to_string());
+        create_blob_file(&blob, &Mode::Complete, &CheckoutOptions { compress: true, ..CheckoutOptions::default() }).unwrap();
         let mut content = String::from("");
-        let mut reader = CompressedFile::new(PathBuf::from("blob1"))
+        let mut reader = CompressedFile::new(PathBuf::from("compressed_blob"))
             .open_for_read()
             .unwrap();
-        
         reader.read_to_string(&mut content).unwrap();
 
-        fs::remove_file("blob1").unwrap();
+        assert_eq!("Hello, World", content);
+
+        fs::remove_file("compressed_blob").unwrap();
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_should_not_create_file(){
+        File::create("partial_blob").unwrap();
+        
+        let blob = Blob::new("partial_blob".to_string(), "Hello, World".to_string());
+        create_blob_file(&blob, &Mode::Partial, &CheckoutOptions::default()).unwrap();
+        let content = fs::read_to_string("partial_blob").unwrap();
+
+        fs::remove_file("partial_blob").unwrap();
+
+        assert!(content.is_empty());
+    }
+
+    #[test]
+    fn test_should_remove_temporary_file_on_failure(){
+        fs::write("failed_blob", "before").unwrap();
+
+        let blob = Blob::new_unloaded("failed_blob".to_string(), "1234".to_string(), PathBuf::from("missing_failed_blob"));
+        // The compressed file is created before the content is read
+        let result = create_blob_file(&blob, &Mode::Complete, &CheckoutOptions { compress: true, ..CheckoutOptions::default() });
+        let content = fs::read_to_string("failed_blob").unwrap();
+        let temporary_exists = PathBuf::from(".failed_blob.dit-tmp").exists();
+
+        fs::remove_file("failed_blob").unwrap();
+
+        assert!(result.is_err());
+        assert_eq!("before", content);
+        assert!(!temporary_exists);
+    }
+}
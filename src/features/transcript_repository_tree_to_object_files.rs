@@ -1,65 +1,78 @@
 use std::io::Write;
-use std::path::PathBuf;
-
-use dit_file_encryptor::CompressedFile;
+use std::path::{Path, PathBuf};
 
 use crate::error::RepTreeError;
 use crate::models::blob::Blob;
+use crate::models::commit::Commit;
 use crate::models::node::Node;
 use crate::models::tree::Tree;
-
+use crate::store::file_system_store::FileSystemStore;
+use crate::store::object_store::ObjectStore;
+
+/// Write every object of a repository tree in the object files located at `path`
+///
+/// # Arguments
+///
+/// * `root` - root `Node` of the repository tree
+/// * `path` - path to the object files
 pub fn transcript_repository_to_object_files(root: &Node, path: &PathBuf) -> Result<(), RepTreeError>{
-    let (filepath, filename) = create_details(root, path.clone());
-    let mut writer = create_file(&filepath, filename)?;
+    let mut store = FileSystemStore::new(path.clone());
+    transcript_repository_to_object_store(root, &mut store)
+}
+
+/// Write every object of a repository tree in an `ObjectStore`
+///
+/// # Arguments
+///
+/// * `root` - root `Node` of the repository tree
+/// * `store` - store receiving the objects
+pub fn transcript_repository_to_object_store(root: &Node, store: &mut dyn ObjectStore) -> Result<(), RepTreeError>{
+    let mut content: Vec<u8> = Vec::new();
     match root {
         Node::BlobNode( blob) => {
-            transcript_blob(blob, &mut writer)?;
+            transcript_blob(blob, &mut content)?;
         },
         Node::TreeNode(tree) => {
             for node in tree.get_children().iter() {
-                transcript_repository_to_object_files(node, path)?;
+                transcript_repository_to_object_store(node, store)?;
             }
-            transcript_tree(tree, &mut writer)?;
+            transcript_tree(tree, &mut content)?;
         }
     }
-    Ok(())
+    store.put(&root.get_id(), &content)
 }
 
-fn create_details(node: &Node, path_buf: PathBuf) -> (PathBuf, String){
-    let id: String = node.get_id();
-    let directory: String = id.chars().take(2).collect();
-    let filename: String = id.chars().skip(2).collect();
-    let path: PathBuf = path_buf.join(directory);
-    (path, filename)
+/// Write a commit object in the object files located at `path`
+///
+/// # Arguments
+///
+/// * `commit` - commit to write, its id must already be generated
+/// * `path` - path to the object files
+pub fn transcript_commit_to_object_files(commit: &Commit, path: &Path) -> Result<(), RepTreeError> {
+    let mut store = FileSystemStore::new(path.to_path_buf());
+    transcript_commit_to_object_store(commit, &mut store)
 }
 
-fn create_file(path_buf: &PathBuf, filename: String) -> Result<Box<dyn Write>, RepTreeError> {
-    let path_file = path_buf.join(filename);
-    let compressed_file = CompressedFile::new(path_file);
-    
-    let writer = compressed_file.open_for_write().map_err(|_e| {
-        RepTreeError::Encryptor("Error creating writer for compressed file".to_string())
-    })?;
-
-    Ok(writer)
+/// Write a commit object in an `ObjectStore`
+///
+/// # Arguments
+///
+/// * `commit` - commit to write, its id must already be generated
+/// * `store` - store receiving the object
+pub fn transcript_commit_to_object_store(commit: &Commit, store: &mut dyn ObjectStore) -> Result<(), RepTreeError> {
+    if commit.get_id().is_empty() {
+        return Err(RepTreeError::UnexpectedComportment("Commit id must be generated before writing it".to_string()));
+    }
+    store.put(&commit.get_id(), commit.serialize().as_bytes())
 }
 
-fn transcript_tree(tree: &Tree, writer: &mut Box<dyn Write>) -> Result<(), RepTreeError> {
-    for node in tree.get_children().iter() {
-        match node {
-            Node::TreeNode(tree)  => {
-                writeln!(writer, "TREE {} {}", tree.get_id(), tree.get_name()).map_err(RepTreeError::IoError)?;
-            },
-            Node::BlobNode(blob) => {
-                writeln!(writer, "BLOB {} {}", blob.get_id(), blob.get_name()).map_err(RepTreeError::IoError)?;
-            }
-        }
-    }
+fn transcript_tree(tree: &Tree, writer: &mut impl Write) -> Result<(), RepTreeError> {
+    write!(writer, "{}", tree.serialize()).map_err(RepTreeError::IoError)?;
     Ok(())
 }
 
-fn transcript_blob(blob: &Blob, writer: &mut Box<dyn Write>) -> Result<(), RepTreeError> {
-    write!(writer, "{}", blob.get_content()).map_err(RepTreeError::IoError)?;
+fn transcript_blob(blob: &Blob, writer: &mut impl Write) -> Result<(), RepTreeError> {
+    writer.write_all(&blob.get_content()?).map_err(RepTreeError::IoError)?;
     Ok(())
 }
 
@@ -70,10 +83,12 @@ mod tests {
 
     use dit_file_encryptor::CompressedFile;
 
-    use crate::features::transcript_repository_tree_to_object_files::{create_details, create_file, transcript_blob, transcript_tree};
+    use crate::features::transcript_repository_tree_to_object_files::{transcript_blob, transcript_tree, transcript_repository_to_object_store};
     use crate::models::blob::Blob;
-    use crate::models::node::Node::BlobNode;
+    use crate::models::node::Node::{BlobNode, TreeNode};
     use crate::models::tree::Tree;
+    use crate::store::memory_store::MemoryStore;
+    use crate::store::object_store::ObjectStore;
 
     #[test]
     fn test_should_transcript_blob() {
@@ -132,22 +147,17 @@ mod tests {
     }
     
     #[test]
-    fn should_create_file(){
-        let filename = String::from("tmp5");
-        create_file(&PathBuf::from(""), filename.clone()).expect("Failed to create");
-        assert!(PathBuf::from("tmp5").is_file(), "File should exist");
-        std::fs::remove_file(filename).unwrap_or_else(|_| println!("Failed to delete temporary file"));
-    }
-    
-    #[test]
-    fn should_create_details(){
+    fn should_transcript_repository_to_object_store(){
         let mut blob = Blob::new("HI".to_string(), "Hello".to_string());
         blob.set_id("12345667890".to_string());
-        
-        let (directory, filename) = create_details(&BlobNode(blob), PathBuf::from(""));
-        assert_eq!(PathBuf::from("12"), directory);
-        assert_eq!("345667890", filename);
-        
+        let mut tree = Tree::new("".to_string(), vec![BlobNode(blob)]);
+        tree.set_id("0987654321".to_string());
+
+        let mut store = MemoryStore::new();
+        transcript_repository_to_object_store(&TreeNode(tree), &mut store).expect("Failed to transcript");
+
+        assert_eq!(store.get("12345667890").unwrap(), b"Hello");
+        assert_eq!(store.get("0987654321").unwrap(), b"BLOB 12345667890 HI\n");
     }
 }
 
@@ -2,3 +2,4 @@ pub mod models;
 pub mod features;
 pub mod error;
 pub mod utils;
+pub mod store;
@@ -1,3 +1,9 @@
 pub mod tree;
 pub mod blob;
-pub mod node;
\ No newline at end of file
+pub mod node;
+pub mod commit;
+pub mod reference;
+pub mod index;
+pub mod ignore_rules;
+pub mod entry_mode;
+pub mod checkout_journal;
\ No newline at end of file
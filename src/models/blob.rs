@@ -1,8 +1,8 @@
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::fs;
 use std::path::{Path, PathBuf};
 use dit_id_generator::features::generator::generate;
 use crate::error::RepTreeError;
+use crate::models::entry_mode::EntryMode;
 use dit_id_generator::traits::generator::Generator;
 
 pub const BLOB: &str = "BLOB";
@@ -10,8 +10,13 @@ pub const BLOB: &str = "BLOB";
 pub struct Blob{
     id: String,
     name: String,
-    content: String,
-    path: PathBuf
+    content: Vec<u8>,
+    path: PathBuf,
+    // File the content is read from while it isn't loaded
+    source: Option<PathBuf>,
+    // Whether the content changed since the id was generated
+    dirty: bool,
+    mode: EntryMode
 }
 
 impl Blob {
@@ -20,24 +25,76 @@ impl Blob {
         Blob {
             id: "".to_string(),
             name: "".to_string(),
-            content: "".to_string(),
-            path: PathBuf::new()
+            content: vec![],
+            path: PathBuf::new(),
+            source: None,
+            dirty: true,
+            mode: EntryMode::Regular
         }
     }
     
-    pub fn new(name: String, content: String) -> Blob {
+    pub fn new(name: String, content: impl Into<Vec<u8>>) -> Blob {
         Blob {
             id: "".to_string(),
             name,
-            content,
-            path: PathBuf::new()
+            content: content.into(),
+            path: PathBuf::new(),
+            source: None,
+            dirty: true,
+            mode: EntryMode::Regular
         }
     }
 
+    /// Create a `Blob` whose id is already known, its content being read from `source` only
+    /// when needed
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - file name
+    /// * `id` - id of the content of `source`
+    /// * `source` - file containing the content
+    ///
+    /// # Returns
+    ///
+    /// The unloaded `Blob`, `generate_id` keeping `id` until the content is loaded
+    pub fn new_unloaded(name: String, id: String, source: PathBuf) -> Blob {
+        Blob {
+            id,
+            name,
+            content: vec![],
+            path: PathBuf::new(),
+            source: Some(source),
+            dirty: false,
+            mode: EntryMode::Regular
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.source.is_none()
+    }
+
+    /// Read the content of an unloaded `Blob` from its source file
+    ///
+    /// # Returns
+    ///
+    /// `RepTreeError::IoError` if the source can't be read, or
+    /// `RepTreeError::UnexpectedComportment` if its content no longer matches the id
+    pub fn load_content(&mut self) -> Result<(), RepTreeError> {
+        if let Some(source) = &self.source {
+            self.content = read_source(source, &self.id)?;
+            self.source = None;
+        }
+        Ok(())
+    }
+
     pub fn get_name(&self) -> String {
         self.name.to_string()
     }
 
+    pub fn get_name_ref(&self) -> &str {
+        &self.name
+    }
+
     pub fn set_name(&mut self, name: String) {
         self.name = name;
     }
@@ -46,14 +103,33 @@ impl Blob {
         self.id.to_string()
     }
 
+    /// Set the id, which is generated again by `generate_id` as it may not match the content
     pub fn set_id(&mut self, id: String){
         self.id = id;
+        self.dirty = true;
     }
     
-    pub fn get_content(&self) -> String {
-        self.content.to_string()
+    /// Content of the blob, read from its source file if it isn't loaded
+    ///
+    /// # Returns
+    ///
+    /// Result with the content, or the errors of `load_content` if it isn't loaded
+    pub fn get_content(&self) -> Result<Vec<u8>, RepTreeError> {
+        match &self.source {
+            Some(source) => read_source(source, &self.id),
+            None => Ok(self.content.clone())
+        }
     }
     
+    pub fn get_mode(&self) -> EntryMode {
+        self.mode
+    }
+
+    /// Set the mode of the file, `Regular`, `Executable` or `Symlink`
+    pub fn set_mode(&mut self, mode: EntryMode) {
+        self.mode = mode;
+    }
+
     pub fn set_path(&mut self, path: PathBuf) {
         self.path = path;
     }
@@ -86,25 +162,58 @@ impl Blob {
     }
     
     pub fn set_content_from_file(&mut self, path_buf: &Path) -> Result<(),RepTreeError>{
-        let file = File::open(path_buf).map_err(RepTreeError::IoError)?;
-        let mut buf_reader = BufReader::new(file);
-        let mut contents = String::new();
-        buf_reader.read_to_string(&mut contents).map_err(RepTreeError::IoError)?;
-        self.content = contents;
+        self.content = fs::read(path_buf).map_err(RepTreeError::IoError)?;
+        self.source = None;
+        self.dirty = true;
         self.generate_id();
         Ok(())
     }
     
-    pub fn set_content(&mut self, content: String) {
-        self.content = content;
+    pub fn set_content(&mut self, content: impl Into<Vec<u8>>) {
+        self.content = content.into();
+        self.source = None;
+        self.dirty = true;
+    }
+
+    /// Check if the id has to be generated again because the content changed
+    pub fn is_dirty(&self) -> bool {
+        self.dirty || self.id.is_empty()
+    }
+}
+
+/// Marks the input of a content which isn't UTF-8, so that it doesn't share its id with a text
+const BINARY_GENERATOR_PREFIX: &str = "\0binary\0";
+
+/// `String` form of a content for the id generator
+///
+/// UTF-8 content is given as is, so that text blobs keep the ids they had when blobs held a
+/// `String`. Other content has every byte mapped to the `char` with the same code point, which
+/// is lossless, after a prefix telling it apart from text.
+fn content_to_generator_input(content: &[u8]) -> String {
+    match std::str::from_utf8(content) {
+        Ok(text) => text.to_string(),
+        Err(_) => BINARY_GENERATOR_PREFIX.chars().chain(content.iter().map(|&byte| byte as char)).collect()
     }
 }
 
+/// Read the content of an unloaded blob, checking that the file wasn't modified since its id
+/// was known
+fn read_source(source: &Path, id: &str) -> Result<Vec<u8>, RepTreeError> {
+    let content = fs::read(source).map_err(RepTreeError::IoError)?;
+    if generate(content_to_generator_input(&content)) != id {
+        return Err(RepTreeError::UnexpectedComportment(format!("{} was modified since its id was read", source.display())));
+    }
+    Ok(content)
+}
+
 impl Generator for Blob {
     fn generate_id(&mut self) -> String {
-        let content = self.content.clone();
-        let id = generate(content);
-        self.set_id(id.clone());
+        if !self.is_loaded() || !self.is_dirty() {
+            return self.get_id();
+        }
+        let id = generate(content_to_generator_input(&self.content));
+        self.id = id.clone();
+        self.dirty = false;
         id
     }
 }
@@ -0,0 +1,399 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use crate::error::RepTreeError;
+use crate::utils::{quote_name, unquote_name};
+
+const ENTRIES_FILE: &str = "entries";
+const BACKUP_DIRECTORY: &str = "backup";
+const HEADER: &str = "DIT CHECKOUT JOURNAL";
+
+/// Operation of a checkout on a path of the working directory, recorded before it is done
+///
+/// - `Write`: a file is written, the file it replaces being backed up first.
+/// - `Delete`: a file is deleted, being backed up first.
+/// - `CreateDirectory`: a missing directory is created.
+/// - `RemoveDirectory`: an empty directory is removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JournalOperation {
+    Write,
+    Delete,
+    CreateDirectory,
+    RemoveDirectory,
+}
+
+impl JournalOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JournalOperation::Write => "WRITE",
+            JournalOperation::Delete => "DELETE",
+            JournalOperation::CreateDirectory => "MKDIR",
+            JournalOperation::RemoveDirectory => "RMDIR",
+        }
+    }
+
+    /// Parse an operation written by `JournalOperation::as_str`
+    pub fn parse(operation: &str) -> Option<JournalOperation> {
+        [JournalOperation::Write, JournalOperation::Delete, JournalOperation::CreateDirectory, JournalOperation::RemoveDirectory]
+            .into_iter()
+            .find(|journal_operation| journal_operation.as_str() == operation)
+    }
+}
+
+/// Journal of a checkout in progress, kept in its own directory
+///
+/// The journal records the ids of the trees checked out from and to, then each operation
+/// before it is done, the entries being synced to the disk. The files a checkout overwrites or
+/// deletes are kept in the backups of the journal rather than lost, so that an interrupted
+/// checkout can be rolled back as well as resumed. The journal directory is removed once the
+/// checkout is done, its existence meaning that a checkout was interrupted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckoutJournal {
+    path: PathBuf,
+    current_id: String,
+    target_id: String,
+    entries: Vec<(JournalOperation, PathBuf)>
+}
+
+impl CheckoutJournal {
+
+    /// Check if a journal is left at `path`
+    pub fn exists(path: &Path) -> bool {
+        path.join(ENTRIES_FILE).is_file()
+    }
+
+    /// Start a journal at `path` for a checkout from the tree `current_id` to `target_id`
+    ///
+    /// # Returns
+    ///
+    /// Result with the journal, `RepTreeError::UnexpectedComportment` if a journal is already
+    /// left at `path`
+    pub fn create(path: &Path, current_id: &str, target_id: &str) -> Result<CheckoutJournal, RepTreeError> {
+        if CheckoutJournal::exists(path) {
+            return Err(RepTreeError::UnexpectedComportment(format!("A checkout journal already exists at {}", path.display())));
+        }
+        fs::create_dir_all(path).map_err(RepTreeError::IoError)?;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path.join(ENTRIES_FILE))
+            .map_err(RepTreeError::IoError)?;
+        file.write_all(format!("{}\n{} {}\n", HEADER, current_id, target_id).as_bytes()).map_err(RepTreeError::IoError)?;
+        file.sync_all().map_err(RepTreeError::IoError)?;
+        Ok(CheckoutJournal {
+            path: path.to_path_buf(),
+            current_id: current_id.to_string(),
+            target_id: target_id.to_string(),
+            entries: vec![]
+        })
+    }
+
+    /// Read the journal left at `path`
+    ///
+    /// An entry cut by the interruption is ignored, its operation not having been started.
+    pub fn open(path: &Path) -> Result<CheckoutJournal, RepTreeError> {
+        let content = fs::read_to_string(path.join(ENTRIES_FILE)).map_err(RepTreeError::IoError)?;
+        // The last piece is empty unless the last line was cut
+        let mut lines: Vec<&str> = content.split('\n').collect();
+        lines.pop();
+        let ids = match lines.as_slice() {
+            [HEADER, ids, ..] => ids.split_once(' '),
+            _ => None
+        };
+        let (current_id, target_id) = ids.ok_or(RepTreeError::UnexpectedComportment(format!("Invalid checkout journal at {}", path.display())))?;
+        let entries = lines.iter()
+            .skip(2)
+            .filter_map(|line| {
+                let (operation, entry_path) = line.split_once(' ')?;
+                Some((JournalOperation::parse(operation)?, PathBuf::from(unquote_name(entry_path)?)))
+            })
+            .collect();
+        Ok(CheckoutJournal {
+            path: path.to_path_buf(),
+            current_id: current_id.to_string(),
+            target_id: target_id.to_string(),
+            entries
+        })
+    }
+
+    pub fn get_current_id(&self) -> String {
+        self.current_id.to_string()
+    }
+
+    pub fn get_target_id(&self) -> String {
+        self.target_id.to_string()
+    }
+
+    pub fn get_entries(&self) -> Vec<(JournalOperation, PathBuf)> {
+        self.entries.clone()
+    }
+
+    /// Record an operation on `path`, relative to the working directory, before doing it
+    pub fn record(&mut self, operation: JournalOperation, path: &Path) -> Result<(), RepTreeError> {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(self.path.join(ENTRIES_FILE))
+            .map_err(RepTreeError::IoError)?;
+        file.write_all(format!("{} {}\n", operation.as_str(), quote_name(&path.to_string_lossy())).as_bytes()).map_err(RepTreeError::IoError)?;
+        file.sync_data().map_err(RepTreeError::IoError)?;
+        self.entries.push((operation, path.to_path_buf()));
+        Ok(())
+    }
+
+    fn get_backup_path(&self, path: &Path) -> PathBuf {
+        self.path.join(BACKUP_DIRECTORY).join(path)
+    }
+
+    /// Keep the file at `file_path` in the backups under `path`, relative to the working
+    /// directory, unless it is missing or a directory
+    ///
+    /// The file is left in place, so that it is never missing while the checkout replaces it.
+    /// The backup is a hard link to the file, or a copy synced to the disk if the journal is on
+    /// another file system, so the file must then be replaced by a rename or deleted rather than
+    /// written in place. A file already backed up keeps its first backup, which is its content
+    /// before the checkout.
+    pub fn backup(&self, file_path: &Path, path: &Path) -> Result<(), RepTreeError> {
+        if !fs::symlink_metadata(file_path).is_ok_and(|metadata| !metadata.is_dir()) {
+            return Ok(());
+        }
+        let backup_path = self.get_backup_path(path);
+        if fs::symlink_metadata(&backup_path).is_ok() {
+            return Ok(());
+        }
+        if let Some(parent) = backup_path.parent() {
+            fs::create_dir_all(parent).map_err(RepTreeError::IoError)?;
+        }
+        match fs::hard_link(file_path, &backup_path) {
+            Ok(()) => Ok(()),
+            Err(_) => copy_file(file_path, &backup_path)
+        }
+    }
+
+    /// Undo the recorded operations in `directory`, restoring the backed up files, then
+    /// remove the journal
+    ///
+    /// Files written by the checkout are deleted when it didn't replace a file, and the
+    /// directories it created are removed if they are empty.
+    pub fn rollback(self, directory: &Path) -> Result<(), RepTreeError> {
+        let mut restored: BTreeSet<PathBuf> = BTreeSet::new();
+        for (operation, path) in self.entries.iter().rev() {
+            let file_path = directory.join(path);
+            match operation {
+                JournalOperation::Write | JournalOperation::Delete => {
+                    // A path can be recorded again by a resumed checkout, its backup being restored once
+                    if !restored.insert(path.clone()) {
+                        continue;
+                    }
+                    if let Some(temporary_path) = get_temporary_path(&file_path) {
+                        if fs::symlink_metadata(&temporary_path).is_ok() {
+                            fs::remove_file(&temporary_path).map_err(RepTreeError::IoError)?;
+                        }
+                    }
+                    if fs::symlink_metadata(&file_path).is_ok_and(|metadata| !metadata.is_dir()) {
+                        fs::remove_file(&file_path).map_err(RepTreeError::IoError)?;
+                    }
+                    let backup_path = self.get_backup_path(path);
+                    if fs::symlink_metadata(&backup_path).is_ok() {
+                        if let Some(parent) = file_path.parent() {
+                            fs::create_dir_all(parent).map_err(RepTreeError::IoError)?;
+                        }
+                        move_file(&backup_path, &file_path)?;
+                    }
+                },
+                JournalOperation::CreateDirectory => {
+                    let is_empty_directory = fs::read_dir(&file_path).is_ok_and(|mut entries| entries.next().is_none());
+                    if is_empty_directory {
+                        fs::remove_dir(&file_path).map_err(RepTreeError::IoError)?;
+                    }
+                },
+                JournalOperation::RemoveDirectory => {
+                    fs::create_dir_all(&file_path).map_err(RepTreeError::IoError)?;
+                }
+            }
+        }
+        self.remove()
+    }
+
+    /// Remove the journal once the checkout is done
+    pub fn remove(self) -> Result<(), RepTreeError> {
+        fs::remove_dir_all(&self.path).map_err(RepTreeError::IoError)
+    }
+}
+
+/// Move the file at `from` to `to`, copying it then deleting it if they aren't on the same file
+/// system
+fn move_file(from: &Path, to: &Path) -> Result<(), RepTreeError> {
+    if fs::rename(from, to).is_err() {
+        copy_file(from, to)?;
+        fs::remove_file(from).map_err(RepTreeError::IoError)?;
+    }
+    Ok(())
+}
+
+/// Copy the file at `from` to `to` through a temporary file synced to the disk, so that `to` is
+/// never left partially written, a symbolic link being copied as a link
+fn copy_file(from: &Path, to: &Path) -> Result<(), RepTreeError> {
+    let temporary_path = get_temporary_path(to)
+        .ok_or(RepTreeError::UnexpectedComportment(format!("Invalid file name {}", to.display())))?;
+    if fs::symlink_metadata(&temporary_path).is_ok() {
+        fs::remove_file(&temporary_path).map_err(RepTreeError::IoError)?;
+    }
+    let is_symlink = fs::symlink_metadata(from).map_err(RepTreeError::IoError)?.file_type().is_symlink();
+    let copied = if is_symlink {
+        copy_symlink(from, &temporary_path)
+    } else {
+        fs::copy(from, &temporary_path)
+            .and_then(|_| OpenOptions::new().write(true).open(&temporary_path))
+            .and_then(|file| file.sync_all())
+    };
+    if let Err(error) = copied.and_then(|_| fs::rename(&temporary_path, to)) {
+        // The error of the copy is the one reported
+        let _ = fs::remove_file(&temporary_path);
+        return Err(RepTreeError::IoError(error));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+/// Without symbolic links, checkouts write links as files with the path they point to
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+/// Default path of the journal of the checkouts of `directory`, `.<name>.dit-checkout` next to
+/// it, so that it is outside of the working directory and outlives a restart
+///
+/// # Returns
+///
+/// Result with the path, or `RepTreeError` if `directory` can't be found
+pub fn get_default_journal_path(directory: &Path) -> Result<PathBuf, RepTreeError> {
+    let directory = fs::canonicalize(directory).map_err(RepTreeError::IoError)?;
+    match (directory.parent(), directory.file_name()) {
+        (Some(parent), Some(name)) => {
+            let mut journal_name = std::ffi::OsString::from(".");
+            journal_name.push(name);
+            journal_name.push(".dit-checkout");
+            Ok(parent.join(journal_name))
+        },
+        // The root of the file system has no directory next to it
+        _ => Ok(std::env::temp_dir().join("dit-checkout"))
+    }
+}
+
+/// Path of the temporary file a file is written to before being renamed to `file_path`,
+/// in the same directory so that the rename is atomic
+pub fn get_temporary_path(file_path: &Path) -> Option<PathBuf> {
+    let name = file_path.file_name()?;
+    let mut temporary_name = std::ffi::OsString::from(".");
+    temporary_name.push(name);
+    temporary_name.push(".dit-tmp");
+    Some(file_path.with_file_name(temporary_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use crate::models::checkout_journal::{CheckoutJournal, copy_file, get_temporary_path, JournalOperation};
+
+    #[test]
+    fn should_read_recorded_entries() {
+        let path = Path::new("tmp_checkout_journal");
+        let mut journal = CheckoutJournal::create(path, "1234", "5678").unwrap();
+        journal.record(JournalOperation::CreateDirectory, Path::new("src")).unwrap();
+        journal.record(JournalOperation::Write, Path::new("src/main.rs")).unwrap();
+        journal.record(JournalOperation::Write, Path::new("src/two\nlines.rs")).unwrap();
+        // An entry cut by an interruption
+        fs::OpenOptions::new().append(true).open(path.join("entries")).unwrap().write_all(b"DELETE READ").unwrap();
+        let already_exists = CheckoutJournal::create(path, "1234", "5678").is_err();
+        let opened = CheckoutJournal::open(path).unwrap();
+        let exists = CheckoutJournal::exists(path);
+        opened.clone().remove().unwrap();
+
+        assert!(already_exists);
+        assert!(exists);
+        assert!(!CheckoutJournal::exists(path));
+        assert_eq!((opened.get_current_id(), opened.get_target_id()), ("1234".to_string(), "5678".to_string()));
+        assert_eq!(opened.get_entries(), vec![
+            (JournalOperation::CreateDirectory, PathBuf::from("src")),
+            (JournalOperation::Write, PathBuf::from("src/main.rs")),
+            (JournalOperation::Write, PathBuf::from("src/two\nlines.rs")),
+        ]);
+    }
+
+    /// Replace the file at `file_path` as a checkout does, its backup being a hard link to it
+    fn replace(file_path: &Path, content: &str) {
+        let temporary_path = get_temporary_path(file_path).unwrap();
+        fs::write(&temporary_path, content).unwrap();
+        fs::rename(&temporary_path, file_path).unwrap();
+    }
+
+    #[test]
+    fn should_rollback_recorded_operations() {
+        let directory = Path::new("tmp_checkout_journal_rollback");
+        let path = directory.join(".journal");
+        fs::create_dir_all(directory.join("old")).unwrap();
+        fs::write(directory.join("file.txt"), "before").unwrap();
+        fs::write(directory.join("old/deleted.txt"), "deleted").unwrap();
+
+        let mut journal = CheckoutJournal::create(&path, "1234", "5678").unwrap();
+        journal.backup(&directory.join("file.txt"), Path::new("file.txt")).unwrap();
+        journal.record(JournalOperation::Write, Path::new("file.txt")).unwrap();
+        replace(&directory.join("file.txt"), "after");
+        journal.backup(&directory.join("old/deleted.txt"), Path::new("old/deleted.txt")).unwrap();
+        journal.record(JournalOperation::Delete, Path::new("old/deleted.txt")).unwrap();
+        fs::remove_file(directory.join("old/deleted.txt")).unwrap();
+        journal.record(JournalOperation::RemoveDirectory, Path::new("old")).unwrap();
+        fs::remove_dir(directory.join("old")).unwrap();
+        journal.record(JournalOperation::CreateDirectory, Path::new("new")).unwrap();
+        fs::create_dir(directory.join("new")).unwrap();
+        journal.record(JournalOperation::Write, Path::new("new/added.txt")).unwrap();
+        fs::write(directory.join("new/added.txt"), "added").unwrap();
+        // Recorded again by a resumed checkout
+        journal.backup(&directory.join("file.txt"), Path::new("file.txt")).unwrap();
+        journal.record(JournalOperation::Write, Path::new("file.txt")).unwrap();
+        replace(&directory.join("file.txt"), "after");
+
+        CheckoutJournal::open(&path).unwrap().rollback(directory).unwrap();
+        let read = |file: &str| fs::read_to_string(directory.join(file)).ok();
+        let contents = [read("file.txt"), read("old/deleted.txt")];
+        let (new_exists, journal_exists) = (directory.join("new").exists(), path.exists());
+        fs::remove_dir_all(directory).unwrap();
+
+        assert_eq!(contents, [Some("before".to_string()), Some("deleted".to_string())]);
+        assert!(!new_exists);
+        assert!(!journal_exists);
+    }
+
+    #[test]
+    fn should_copy_files_and_links() {
+        let directory = Path::new("tmp_checkout_journal_copy");
+        fs::create_dir_all(directory).unwrap();
+        fs::write(directory.join("file.txt"), "content").unwrap();
+        fs::write(directory.join("copy.txt"), "replaced").unwrap();
+
+        copy_file(&directory.join("file.txt"), &directory.join("copy.txt")).unwrap();
+        let content = fs::read_to_string(directory.join("copy.txt")).unwrap();
+        let leftover = directory.join(".copy.txt.dit-tmp").exists();
+        #[cfg(unix)]
+        let link = {
+            std::os::unix::fs::symlink("file.txt", directory.join("link")).unwrap();
+            copy_file(&directory.join("link"), &directory.join("link_copy")).unwrap();
+            fs::read_link(directory.join("link_copy")).ok()
+        };
+        fs::remove_dir_all(directory).unwrap();
+
+        assert_eq!(content, "content");
+        assert!(!leftover);
+        #[cfg(unix)]
+        assert_eq!(link, Some(PathBuf::from("file.txt")));
+    }
+}
@@ -0,0 +1,274 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use dit_id_generator::features::generator::generate;
+use dit_id_generator::traits::generator::Generator;
+use crate::error::RepTreeError;
+use crate::models::tree::TREE;
+
+pub const COMMIT: &str = "COMMIT";
+pub const PARENT: &str = "PARENT";
+pub const AUTHOR: &str = "AUTHOR";
+pub const COMMITTER: &str = "COMMITTER";
+
+/// Identity of the author or committer of a `Commit`, with the time of the action
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Signature {
+    name: String,
+    email: String,
+    timestamp: u64
+}
+
+impl Signature {
+
+    /// Create Signature
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the person
+    /// * `email` - email of the person
+    /// * `timestamp` - seconds since the Unix epoch
+    ///
+    /// # Returns
+    ///
+    /// Return a Signature
+    pub fn new(name: String, email: String, timestamp: u64) -> Signature {
+        Signature {
+            name,
+            email,
+            timestamp
+        }
+    }
+
+    /// Create Signature timestamped with the current time
+    pub fn now(name: String, email: String) -> Signature {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        Signature::new(name, email, timestamp)
+    }
+
+    pub fn get_name(&self) -> String {
+        self.name.to_string()
+    }
+
+    pub fn get_email(&self) -> String {
+        self.email.to_string()
+    }
+
+    pub fn get_timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Serialize the signature as `<name> <<email>> <timestamp>`
+    pub fn serialize(&self) -> String {
+        format!("{} <{}> {}", self.name, self.email, self.timestamp)
+    }
+
+    /// Parse a signature written by `Signature::serialize`
+    ///
+    /// # Returns
+    ///
+    /// Result with the `Signature`, or `RepTreeError` if the text is malformed
+    pub fn deserialize(text: &str) -> Result<Signature, RepTreeError> {
+        let error = || RepTreeError::UnexpectedComportment(format!("Invalid signature: {text}"));
+
+        let (identity, timestamp) = text.rsplit_once(' ').ok_or_else(error)?;
+        let timestamp: u64 = timestamp.parse().map_err(|_| error())?;
+        let (name, email) = identity.rsplit_once(" <").ok_or_else(error)?;
+        let email = email.strip_suffix('>').ok_or_else(error)?;
+
+        Ok(Signature::new(name.to_string(), email.to_string(), timestamp))
+    }
+}
+
+/// Snapshot of a repository tree in the history, pointing to its root tree and parent commits
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Commit {
+    id: String,
+    tree_id: String,
+    parent_ids: Vec<String>,
+    author: Signature,
+    committer: Signature,
+    message: String
+}
+
+impl Commit {
+
+    /// Create Commit
+    ///
+    /// # Arguments
+    ///
+    /// * `tree_id` - id of the root tree
+    /// * `parent_ids` - ids of the parent commits, empty for the first commit
+    /// * `author` - who wrote the changes
+    /// * `committer` - who recorded the commit
+    /// * `message` - commit message
+    ///
+    /// # Returns
+    ///
+    /// Return a Commit
+    pub fn new(tree_id: String, parent_ids: Vec<String>, author: Signature, committer: Signature, message: String) -> Commit {
+        Commit {
+            id: "".to_string(),
+            tree_id,
+            parent_ids,
+            author,
+            committer,
+            message
+        }
+    }
+
+    pub fn get_id(&self) -> String {
+        self.id.to_string()
+    }
+
+    pub fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
+    pub fn get_tree_id(&self) -> String {
+        self.tree_id.to_string()
+    }
+
+    pub fn get_parent_ids(&self) -> Vec<String> {
+        self.parent_ids.clone()
+    }
+
+    pub fn get_author(&self) -> Signature {
+        self.author.clone()
+    }
+
+    pub fn get_committer(&self) -> Signature {
+        self.committer.clone()
+    }
+
+    pub fn get_message(&self) -> String {
+        self.message.to_string()
+    }
+
+    /// Serialize the commit, used both as content of the commit object and to generate its id
+    ///
+    /// # Returns
+    ///
+    /// The serialized commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use repository_tree_creator::models::commit::{Commit, Signature};
+    /// let author = Signature::new(String::from("John Doe"), String::from("john@doe.com"), 1700000000);
+    /// let commit = Commit::new(
+    ///     String::from("1234"),
+    ///     vec![String::from("5678")],
+    ///     author.clone(),
+    ///     author,
+    ///     String::from("Plant an oak")
+    /// );
+    ///
+    /// assert_eq!(commit.serialize(), "COMMIT\nTREE 1234\nPARENT 5678\n\
+    /// AUTHOR John Doe <john@doe.com> 1700000000\n\
+    /// COMMITTER John Doe <john@doe.com> 1700000000\n\nPlant an oak");
+    /// ```
+    pub fn serialize(&self) -> String {
+        let mut content = format!("{}\n{} {}\n", COMMIT, TREE, self.tree_id);
+        for parent_id in self.parent_ids.iter() {
+            content += &format!("{} {}\n", PARENT, parent_id);
+        }
+        content += &format!("{} {}\n", AUTHOR, self.author.serialize());
+        content += &format!("{} {}\n", COMMITTER, self.committer.serialize());
+        content += "\n";
+        content += &self.message;
+        content
+    }
+
+    /// Parse a commit written by `Commit::serialize`
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - id of the commit object
+    /// * `content` - content of the commit object
+    ///
+    /// # Returns
+    ///
+    /// Result with the `Commit`, or `RepTreeError` if the content isn't a valid commit
+    pub fn deserialize(id: String, content: &str) -> Result<Commit, RepTreeError> {
+        let (header, message) = content.split_once("\n\n").ok_or_else(|| {
+            RepTreeError::UnexpectedComportment(format!("Invalid commit {id}: missing message"))
+        })?;
+        let mut lines = header.lines();
+        if lines.next() != Some(COMMIT) {
+            return Err(RepTreeError::UnexpectedComportment(format!("Object {id} is not a commit")));
+        }
+
+        let mut tree_id: Option<String> = None;
+        let mut parent_ids: Vec<String> = Vec::new();
+        let mut author: Option<Signature> = None;
+        let mut committer: Option<Signature> = None;
+
+        for line in lines {
+            let (key, value) = line.split_once(' ').ok_or_else(|| {
+                RepTreeError::UnexpectedComportment(format!("Invalid commit {id} line: {line}"))
+            })?;
+            match key {
+                TREE => tree_id = Some(value.to_string()),
+                PARENT => parent_ids.push(value.to_string()),
+                AUTHOR => author = Some(Signature::deserialize(value)?),
+                COMMITTER => committer = Some(Signature::deserialize(value)?),
+                _ => return Err(RepTreeError::UnexpectedComportment(format!("Invalid commit {id} line: {line}")))
+            }
+        }
+
+        match (tree_id, author, committer) {
+            (Some(tree_id), Some(author), Some(committer)) => {
+                let mut commit = Commit::new(tree_id, parent_ids, author, committer, message.to_string());
+                commit.set_id(id);
+                Ok(commit)
+            },
+            _ => Err(RepTreeError::UnexpectedComportment(format!("Invalid commit {id}: missing header")))
+        }
+    }
+}
+
+impl Generator for Commit {
+    fn generate_id(&mut self) -> String {
+        let id = generate(self.serialize());
+        self.set_id(id.clone());
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dit_id_generator::traits::generator::Generator;
+    use crate::models::commit::{Commit, Signature};
+
+    fn commit(parent_ids: Vec<String>, message: &str) -> Commit {
+        let author = Signature::new("John Doe".to_string(), "john@doe.com".to_string(), 1700000000);
+        let committer = Signature::new("Jane Doe".to_string(), "jane@doe.com".to_string(), 1700000100);
+        Commit::new("1234".to_string(), parent_ids, author, committer, message.to_string())
+    }
+
+    #[test]
+    fn should_deserialize_serialized_commit() {
+        let mut original = commit(vec!["5678".to_string(), "9012".to_string()], "Merge\n\nTwo branches");
+        let id = original.generate_id();
+
+        let parsed = Commit::deserialize(id, &original.serialize()).unwrap();
+
+        assert_eq!(parsed, original);
+    }
+
+    #[test]
+    fn should_change_id_with_parents() {
+        let mut c1 = commit(vec![], "Plant an oak");
+        let mut c2 = commit(vec!["5678".to_string()], "Plant an oak");
+
+        assert_ne!(c1.generate_id(), c2.generate_id());
+    }
+
+    #[test]
+    fn should_not_deserialize_other_objects() {
+        assert!(Commit::deserialize("1234".to_string(), "BLOB 1234 oak\n").is_err());
+        assert!(Signature::deserialize("John Doe john@doe.com 12").is_err());
+    }
+}
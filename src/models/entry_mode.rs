@@ -0,0 +1,92 @@
+use std::fs::Metadata;
+
+/// Kind of an entry of a tree, written in the tree objects with the octal modes of git.
+///
+/// - `Regular`: a file, `100644`.
+/// - `Executable`: a file with the executable bit, `100755`.
+/// - `Symlink`: a symbolic link, the content of its blob being the path it points to, `120000`.
+/// - `Directory`: a tree, `040000`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EntryMode {
+    #[default]
+    Regular,
+    Executable,
+    Symlink,
+    Directory,
+}
+
+impl EntryMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntryMode::Regular => "100644",
+            EntryMode::Executable => "100755",
+            EntryMode::Symlink => "120000",
+            EntryMode::Directory => "040000",
+        }
+    }
+
+    /// Parse a mode written by `EntryMode::as_str`
+    pub fn parse(mode: &str) -> Option<EntryMode> {
+        [EntryMode::Regular, EntryMode::Executable, EntryMode::Symlink, EntryMode::Directory]
+            .into_iter()
+            .find(|entry_mode| entry_mode.as_str() == mode)
+    }
+
+    /// Mode of a file on disk, `metadata` being read without following symbolic links to
+    /// detect them
+    ///
+    /// On systems without executable bits, files are always `Regular`.
+    pub fn from_metadata(metadata: &Metadata) -> EntryMode {
+        if metadata.file_type().is_symlink() {
+            EntryMode::Symlink
+        } else if metadata.is_dir() {
+            EntryMode::Directory
+        } else if is_executable(metadata) {
+            EntryMode::Executable
+        } else {
+            EntryMode::Regular
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::models::entry_mode::EntryMode;
+
+    #[test]
+    fn should_parse_modes() {
+        for mode in [EntryMode::Regular, EntryMode::Executable, EntryMode::Symlink, EntryMode::Directory] {
+            assert_eq!(EntryMode::parse(mode.as_str()), Some(mode));
+        }
+        assert_eq!(EntryMode::parse("100600"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_get_mode_from_metadata() {
+        use std::os::unix::fs::PermissionsExt;
+        fs::create_dir_all("tmp_entry_mode").unwrap();
+        fs::write("tmp_entry_mode/script.sh", "#!/bin/sh").unwrap();
+        fs::set_permissions("tmp_entry_mode/script.sh", fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write("tmp_entry_mode/file.txt", "file").unwrap();
+        std::os::unix::fs::symlink("file.txt", "tmp_entry_mode/link").unwrap();
+
+        let mode = |name: &str| EntryMode::from_metadata(&fs::symlink_metadata(format!("tmp_entry_mode/{}", name)).unwrap());
+        let modes = [mode("script.sh"), mode("file.txt"), mode("link"), mode("")];
+        fs::remove_dir_all("tmp_entry_mode").unwrap();
+
+        assert_eq!(modes, [EntryMode::Executable, EntryMode::Regular, EntryMode::Symlink, EntryMode::Directory]);
+    }
+}
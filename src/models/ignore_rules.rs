@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use crate::error::RepTreeError;
+use crate::utils::{matches_glob, matches_glob_anchored};
+
+pub const IGNORE_FILE: &str = ".ditignore";
+
+/// A line of an ignore file, with the syntax of gitignore
+///
+/// - A line starting with `!` re-includes the paths excluded by previous rules.
+/// - A pattern ending with `/` only matches directories.
+/// - A pattern containing `/` elsewhere is anchored: it is matched against the whole path
+///   relative to the directory of the ignore file. Other patterns match a file name at any level.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IgnoreRule {
+    text: String,
+    pattern: String,
+    negated: bool,
+    directory_only: bool,
+    anchored: bool,
+    // Directory the rule applies to, relative to the root
+    base: PathBuf,
+    source: PathBuf,
+    line: usize
+}
+
+/// Remove trailing spaces, unless they are escaped with `\`
+fn trim_trailing_spaces(line: &str) -> &str {
+    let trimmed = line.trim_end_matches(' ');
+    if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+/// Remove the `.` components of a path, which don't change the file it designates
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+fn to_slash_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl IgnoreRule {
+
+    /// Parse a line of an ignore file
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - line of the ignore file
+    /// * `base` - directory the rule applies to, relative to the root
+    /// * `source` - ignore file containing the line
+    /// * `line_number` - number of the line in `source`, starting at 1
+    ///
+    /// # Returns
+    ///
+    /// The rule, or `None` if the line is blank or a comment
+    pub fn parse(line: &str, base: &Path, source: &Path, line_number: usize) -> Option<IgnoreRule> {
+        let text = trim_trailing_spaces(line.strip_suffix('\r').unwrap_or(line));
+        if text.is_empty() || text.starts_with('#') {
+            return None;
+        }
+        let (negated, pattern) = if text.starts_with("\\#") || text.starts_with("\\!") {
+            (false, &text[1..])
+        } else {
+            match text.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, text)
+            }
+        };
+        let (directory_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern)
+        };
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        if pattern.is_empty() {
+            return None;
+        }
+        Some(IgnoreRule {
+            text: text.to_string(),
+            pattern: pattern.to_string(),
+            negated,
+            directory_only,
+            anchored,
+            base: base.to_path_buf(),
+            source: source.to_path_buf(),
+            line: line_number
+        })
+    }
+
+    /// The line as written in the ignore file, without trailing spaces
+    pub fn get_text(&self) -> String {
+        self.text.to_string()
+    }
+
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    pub fn is_directory_only(&self) -> bool {
+        self.directory_only
+    }
+
+    pub fn get_source(&self) -> PathBuf {
+        self.source.clone()
+    }
+
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+
+    /// Check if the rule matches `path`, relative to the root, ignoring whether it is negated
+    pub fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.directory_only && !is_dir {
+            return false;
+        }
+        let relative = match path.strip_prefix(&self.base) {
+            Ok(relative) if !relative.as_os_str().is_empty() => to_slash_path(relative),
+            _ => return false
+        };
+        if self.anchored {
+            matches_glob_anchored(&self.pattern, &relative)
+        } else {
+            matches_glob(&self.pattern, &relative)
+        }
+    }
+}
+
+/// Read the rules of an ignore file, a missing file giving no rules
+fn read_rules(source: &Path, base: &Path) -> Result<Vec<IgnoreRule>, RepTreeError> {
+    if !source.is_file() {
+        return Ok(vec![]);
+    }
+    let content = fs::read_to_string(source).map_err(RepTreeError::IoError)?;
+    Ok(content.lines()
+        .enumerate()
+        .filter_map(|(i, line)| IgnoreRule::parse(line, base, source, i + 1))
+        .collect())
+}
+
+/// Default global exclude file, `$XDG_CONFIG_HOME/dit/ignore` or `$HOME/.config/dit/ignore`
+pub fn get_global_exclude_file() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME").filter(|directory| !directory.is_empty()) {
+        Some(directory) => Some(PathBuf::from(directory).join("dit").join("ignore")),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("dit").join("ignore"))
+    }
+}
+
+/// Rules of the ignore files of a working directory
+///
+/// Each directory may contain a `.ditignore` file whose rules apply to the paths under it,
+/// files being read the first time a path under their directory is checked. Rules of a deeper
+/// file take precedence over the rules of its parents, and later rules of a file over earlier
+/// ones, the rules of the global exclude file coming last. As with gitignore, a path can't be
+/// re-included once one of its parent directories is excluded.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IgnoreRules {
+    root: PathBuf,
+    global_rules: Vec<IgnoreRule>,
+    // Rules of the ignore file of each directory read, by directory relative to the root
+    rules: BTreeMap<PathBuf, Vec<IgnoreRule>>
+}
+
+impl IgnoreRules {
+
+    /// Create IgnoreRules for the working directory `root`, checked paths being relative to it
+    pub fn new(root: &Path) -> IgnoreRules {
+        IgnoreRules {
+            root: root.to_path_buf(),
+            global_rules: vec![],
+            rules: BTreeMap::new()
+        }
+    }
+
+    /// Create IgnoreRules for the working directory `root` with the rules of the default global
+    /// exclude file, see `get_global_exclude_file`
+    ///
+    /// The global exclude file is only read by the rules created this way or given to
+    /// `add_global_exclude_file`, `new` ignoring it so that the result of a call doesn't depend
+    /// on the configuration of the user running it.
+    pub fn with_global_exclude_file(root: &Path) -> Result<IgnoreRules, RepTreeError> {
+        let mut ignore_rules = IgnoreRules::new(root);
+        if let Some(file) = get_global_exclude_file() {
+            ignore_rules.add_global_exclude_file(&file)?;
+        }
+        Ok(ignore_rules)
+    }
+
+    /// Add the rules of a global exclude file, applying to the whole working directory with
+    /// the lowest precedence. A missing file adds no rules.
+    pub fn add_global_exclude_file(&mut self, file: &Path) -> Result<(), RepTreeError> {
+        let rules = read_rules(file, Path::new(""))?;
+        self.global_rules.extend(rules);
+        Ok(())
+    }
+
+    fn load_directory(&mut self, directory: &Path) -> Result<(), RepTreeError> {
+        if !self.rules.contains_key(directory) {
+            let rules = read_rules(&self.root.join(directory).join(IGNORE_FILE), directory)?;
+            self.rules.insert(directory.to_path_buf(), rules);
+        }
+        Ok(())
+    }
+
+    /// Last rule with the highest precedence matching `path`, without checking its parents
+    fn find_rule(&self, path: &Path, is_dir: bool) -> Option<&IgnoreRule> {
+        path.ancestors()
+            .skip(1)
+            .filter_map(|directory| self.rules.get(directory))
+            .chain(std::iter::once(&self.global_rules))
+            .find_map(|rules| rules.iter().rev().find(|rule| rule.matches(path, is_dir)))
+    }
+
+    /// Rule deciding whether `path` is ignored
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path relative to the root
+    /// * `is_dir` - whether `path` is a directory
+    ///
+    /// # Returns
+    ///
+    /// Result with the rule excluding a parent directory of `path` if any, else the matching
+    /// rule with the highest precedence, `path` being ignored if the rule isn't negated. `None`
+    /// if no rule matches. `RepTreeError` if an ignore file can't be read
+    pub fn get_matching_rule(&mut self, path: &Path, is_dir: bool) -> Result<Option<&IgnoreRule>, RepTreeError> {
+        let directories: Vec<&Path> = path.ancestors().skip(1).collect();
+        for directory in directories.iter() {
+            self.load_directory(directory)?;
+        }
+
+        let rules: &IgnoreRules = self;
+        // The root itself can't be excluded
+        for directory in directories.iter().rev().skip(1) {
+            if let Some(rule) = rules.find_rule(directory, true).filter(|rule| !rule.is_negated()) {
+                return Ok(Some(rule));
+            }
+        }
+        Ok(rules.find_rule(path, is_dir))
+    }
+
+    /// Check if `path`, relative to the root, is ignored
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> Result<bool, RepTreeError> {
+        Ok(self.get_matching_rule(path, is_dir)?.is_some_and(|rule| !rule.is_negated()))
+    }
+
+    /// Path of a file relative to the root, `None` if the file isn't under the root
+    ///
+    /// Relative paths are relative to the current directory, like the root itself when it is
+    /// relative.
+    pub fn get_relative_path(&self, file_path: &Path) -> Option<PathBuf> {
+        let relative = if file_path.is_absolute() == self.root.is_absolute() {
+            normalize(file_path).strip_prefix(normalize(&self.root)).ok()?.to_path_buf()
+        } else {
+            let current_directory = env::current_dir().ok()?;
+            let absolute = |path: &Path| normalize(&current_directory.join(path));
+            absolute(file_path).strip_prefix(absolute(&self.root)).ok()?.to_path_buf()
+        };
+        Some(relative)
+    }
+
+    /// Check if the file at `file_path`, a path on the file system rather than relative to
+    /// the root, is ignored. Files outside of the root are never ignored.
+    pub fn is_ignored_file(&mut self, file_path: &Path, is_dir: bool) -> Result<bool, RepTreeError> {
+        match self.get_relative_path(file_path) {
+            Some(path) if !path.as_os_str().is_empty() => self.is_ignored(&path, is_dir),
+            _ => Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use crate::models::ignore_rules::{IgnoreRule, IgnoreRules};
+
+    #[test]
+    fn should_parse_rules() {
+        let parse = |line: &str| IgnoreRule::parse(line, Path::new(""), Path::new(".ditignore"), 1);
+
+        assert!(parse("").is_none());
+        assert!(parse("# comment").is_none());
+        assert!(parse("\\#file").unwrap().matches(Path::new("#file"), false));
+        assert!(parse("!keep.log").unwrap().is_negated());
+        assert!(!parse("\\!keep.log").unwrap().is_negated());
+        assert!(parse("build/").unwrap().is_directory_only());
+        assert!(parse("trailing\\ ").unwrap().matches(Path::new("trailing "), false));
+        assert_eq!(parse("*.log   ").unwrap().get_text(), "*.log");
+    }
+
+    #[test]
+    fn should_match_rules() {
+        let rule = |line: &str, base: &str| IgnoreRule::parse(line, Path::new(base), Path::new(".ditignore"), 1).unwrap();
+
+        assert!(rule("*.log", "").matches(Path::new("a/b/c.log"), false));
+        assert!(rule("/target", "").matches(Path::new("target"), true));
+        assert!(!rule("/target", "").matches(Path::new("app/target"), true));
+        assert!(rule("doc/*.html", "").matches(Path::new("doc/index.html"), false));
+        assert!(!rule("doc/*.html", "").matches(Path::new("app/doc/index.html"), false));
+        assert!(rule("**/cache", "").matches(Path::new("a/b/cache"), true));
+        assert!(!rule("build/", "").matches(Path::new("build"), false));
+        assert!(rule("*.o", "app").matches(Path::new("app/main.o"), false));
+        assert!(!rule("*.o", "app").matches(Path::new("lib/main.o"), false));
+    }
+
+    #[test]
+    fn should_apply_hierarchical_rules() {
+        let root = Path::new("tmp_ignore_rules");
+        fs::create_dir_all(root.join("app/logs")).unwrap();
+        fs::write(root.join(".ditignore"), "*.log\nbuild/\n/secret.txt\n").unwrap();
+        fs::write(root.join("app/.ditignore"), "!important.log\nlogs/\n").unwrap();
+        fs::write(root.join("global"), "*.swp\n").unwrap();
+
+        let mut rules = IgnoreRules::new(root);
+        rules.add_global_exclude_file(&root.join("global")).unwrap();
+        rules.add_global_exclude_file(&root.join("missing")).unwrap();
+        let ignored: Vec<bool> = [
+            ("debug.log", false),
+            ("app/important.log", false),
+            ("important.log", false),
+            ("build", true),
+            ("build", false),
+            ("secret.txt", false),
+            ("app/secret.txt", false),
+            ("app/logs/important.log", false),
+            ("app/.main.rs.swp", false),
+        ].iter().map(|(path, is_dir)| rules.is_ignored(Path::new(path), *is_dir).unwrap()).collect();
+        let rule = rules.get_matching_rule(Path::new("app/logs/important.log"), false).unwrap().cloned();
+        let file_ignored = rules.is_ignored_file(&root.join("app/debug.log"), false).unwrap();
+        let absolute_ignored = rules.is_ignored_file(&std::env::current_dir().unwrap().join(root).join("./app/logs"), true).unwrap();
+        let outside_ignored = rules.is_ignored_file(Path::new("debug.log"), false).unwrap();
+        fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(ignored, vec![true, false, true, true, false, true, false, true, true]);
+        assert!(file_ignored);
+        assert!(absolute_ignored);
+        assert!(!outside_ignored);
+        let rule = rule.unwrap();
+        assert_eq!((rule.get_text(), rule.get_source(), rule.get_line()), ("logs/".to_string(), PathBuf::from("tmp_ignore_rules/app/.ditignore"), 2));
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::Metadata;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use dit_id_generator::features::generator::generate;
+use crate::error::RepTreeError;
+use crate::utils::{quote_name, unquote_name};
+
+pub const INDEX: &str = "DITINDEX";
+pub const INDEX_VERSION: u32 = 1;
+pub const CHECKSUM: &str = "CHECKSUM";
+
+/// Stat data of a file, with the id of the blob of its content when the data was recorded
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IndexEntry {
+    path: PathBuf,
+    size: u64,
+    mtime_seconds: u64,
+    mtime_nanoseconds: u32,
+    inode: u64,
+    mode: u32,
+    blob_id: String
+}
+
+fn to_timestamp(time: SystemTime) -> (u64, u32) {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| (duration.as_secs(), duration.subsec_nanos()))
+        .unwrap_or((0, 0))
+}
+
+#[cfg(unix)]
+fn inode_and_mode(metadata: &Metadata) -> (u64, u32) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.ino(), metadata.mode())
+}
+
+#[cfg(not(unix))]
+fn inode_and_mode(metadata: &Metadata) -> (u64, u32) {
+    (0, if metadata.permissions().readonly() { 0o100444 } else { 0o100644 })
+}
+
+impl IndexEntry {
+
+    /// Create IndexEntry
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path of the file, relative to the root of the repository
+    /// * `metadata` - metadata of the file
+    /// * `blob_id` - id of the blob of the file content
+    ///
+    /// # Returns
+    ///
+    /// Return an IndexEntry
+    pub fn new(path: PathBuf, metadata: &Metadata, blob_id: String) -> IndexEntry {
+        let (mtime_seconds, mtime_nanoseconds) = to_timestamp(metadata.modified().unwrap_or(UNIX_EPOCH));
+        let (inode, mode) = inode_and_mode(metadata);
+        IndexEntry {
+            path,
+            size: metadata.len(),
+            mtime_seconds,
+            mtime_nanoseconds,
+            inode,
+            mode,
+            blob_id
+        }
+    }
+
+    pub fn get_path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    /// Modification time, as seconds and nanoseconds since the Unix epoch
+    pub fn get_mtime(&self) -> (u64, u32) {
+        (self.mtime_seconds, self.mtime_nanoseconds)
+    }
+
+    pub fn get_inode(&self) -> u64 {
+        self.inode
+    }
+
+    pub fn get_mode(&self) -> u32 {
+        self.mode
+    }
+
+    pub fn get_blob_id(&self) -> String {
+        self.blob_id.to_string()
+    }
+
+    /// Check if `metadata` has the stat data recorded in the entry
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        let other = IndexEntry::new(self.path.clone(), metadata, self.blob_id.clone());
+        *self == other
+    }
+
+    /// Serialize the entry as `<size> <mtime seconds> <mtime nanoseconds> <inode> <mode> <blob id> <path>`
+    pub fn serialize(&self) -> String {
+        format!(
+            "{} {} {} {} {} {} {}",
+            self.size,
+            self.mtime_seconds,
+            self.mtime_nanoseconds,
+            self.inode,
+            self.mode,
+            self.blob_id,
+            quote_name(&self.path.to_string_lossy())
+        )
+    }
+
+    /// Parse an entry written by `IndexEntry::serialize`
+    ///
+    /// # Returns
+    ///
+    /// Result with the `IndexEntry`, or `RepTreeError` if the line is malformed
+    pub fn deserialize(line: &str) -> Result<IndexEntry, RepTreeError> {
+        let error = || RepTreeError::InvalidIndex(format!("Invalid entry: {line}"));
+        let fields: Vec<&str> = line.splitn(7, ' ').collect();
+        let [size, mtime_seconds, mtime_nanoseconds, inode, mode, blob_id, path] = fields[..] else {
+            return Err(error());
+        };
+        Ok(IndexEntry {
+            path: PathBuf::from(unquote_name(path).ok_or_else(error)?),
+            size: size.parse().map_err(|_| error())?,
+            mtime_seconds: mtime_seconds.parse().map_err(|_| error())?,
+            mtime_nanoseconds: mtime_nanoseconds.parse().map_err(|_| error())?,
+            inode: inode.parse().map_err(|_| error())?,
+            mode: mode.parse().map_err(|_| error())?,
+            blob_id: blob_id.to_string()
+        })
+    }
+}
+
+/// Cache of the stat data of the files of a working directory, used to know which files
+/// changed without reading them
+///
+/// A file whose stat data matches its entry is considered unchanged, unless it was modified
+/// at or after the time the index was last written: such a file may have been modified again
+/// within the same timestamp without its stat data changing, so it is "racy" and must be read.
+#[derive(Clone, Debug, Default)]
+pub struct Index {
+    entries: BTreeMap<PathBuf, IndexEntry>,
+    // Modification time of the index file when it was last read or written
+    timestamp: Option<(u64, u32)>
+}
+
+impl Index {
+
+    pub fn new() -> Index {
+        Index::default()
+    }
+
+    /// Read the index file at `path`, a missing file giving an empty index
+    ///
+    /// # Returns
+    ///
+    /// Result with the `Index`, or `RepTreeError` if the file can't be read, has another
+    /// version or a wrong checksum
+    pub fn read_from_file(path: &Path) -> Result<Index, RepTreeError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Index::new()),
+            Err(e) => return Err(RepTreeError::IoError(e)),
+        };
+        let mut index = Index::deserialize(&content)?;
+        index.timestamp = Some(to_timestamp(fs::metadata(path).and_then(|metadata| metadata.modified()).map_err(RepTreeError::IoError)?));
+        Ok(index)
+    }
+
+    /// Write the index file at `path`, replacing it atomically
+    pub fn write_to_file(&mut self, path: &Path) -> Result<(), RepTreeError> {
+        let mut temporary_name = path.file_name().unwrap_or_default().to_os_string();
+        temporary_name.push(".lock");
+        let temporary_path = path.with_file_name(temporary_name);
+
+        fs::write(&temporary_path, self.serialize()).map_err(RepTreeError::IoError)?;
+        fs::rename(&temporary_path, path).map_err(RepTreeError::IoError)?;
+        self.timestamp = Some(to_timestamp(fs::metadata(path).and_then(|metadata| metadata.modified()).map_err(RepTreeError::IoError)?));
+        Ok(())
+    }
+
+    pub fn get_entry(&self, path: &Path) -> Option<&IndexEntry> {
+        self.entries.get(path)
+    }
+
+    /// Entries sorted by path
+    pub fn get_entries(&self) -> Vec<&IndexEntry> {
+        self.entries.values().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Add or replace the entry of `path`
+    pub fn update_entry(&mut self, path: &Path, metadata: &Metadata, blob_id: &str) {
+        self.entries.insert(path.to_path_buf(), IndexEntry::new(path.to_path_buf(), metadata, blob_id.to_string()));
+    }
+
+    pub fn remove_entry(&mut self, path: &Path) -> Option<IndexEntry> {
+        self.entries.remove(path)
+    }
+
+    /// Check if the entry was modified at or after the time the index was written
+    pub fn is_racy(&self, entry: &IndexEntry) -> bool {
+        match self.timestamp {
+            Some(timestamp) => entry.get_mtime() >= timestamp,
+            None => true,
+        }
+    }
+
+    /// Id of the blob of the file at `path` if it is known to be unchanged
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path of the file, relative to the root of the repository
+    /// * `metadata` - current metadata of the file
+    ///
+    /// # Returns
+    ///
+    /// The blob id if the file has an entry matching `metadata` which isn't racy, `None` if
+    /// the file has to be read
+    pub fn get_unchanged_blob_id(&self, path: &Path, metadata: &Metadata) -> Option<String> {
+        let entry = self.entries.get(path)?;
+        if entry.matches(metadata) && !self.is_racy(entry) {
+            Some(entry.get_blob_id())
+        } else {
+            None
+        }
+    }
+
+    /// Serialize the index
+    ///
+    /// The first line is `DITINDEX <version>`, followed by one line per entry, see
+    /// `IndexEntry::serialize`. The last line is `CHECKSUM <id>`, the id being generated from
+    /// all the previous lines.
+    pub fn serialize(&self) -> String {
+        let mut content = format!("{INDEX} {INDEX_VERSION}\n");
+        for entry in self.entries.values() {
+            content.push_str(&entry.serialize());
+            content.push('\n');
+        }
+        let checksum = generate(content.clone());
+        content.push_str(&format!("{CHECKSUM} {checksum}\n"));
+        content
+    }
+
+    /// Parse an index written by `Index::serialize`
+    ///
+    /// # Returns
+    ///
+    /// Result with the `Index`, or `RepTreeError` if the content has another version, a wrong
+    /// checksum or a malformed entry
+    pub fn deserialize(content: &str) -> Result<Index, RepTreeError> {
+        let body_end = content.trim_end_matches('\n').rfind('\n').map(|position| position + 1).unwrap_or(0);
+        let (body, checksum_line) = content.split_at(body_end);
+        let checksum = checksum_line.trim_end_matches('\n')
+            .strip_prefix(&format!("{CHECKSUM} "))
+            .ok_or_else(|| RepTreeError::InvalidIndex("Missing checksum".to_string()))?;
+        if generate(body.to_string()) != checksum {
+            return Err(RepTreeError::InvalidIndex("Wrong checksum".to_string()));
+        }
+
+        let mut lines = body.lines();
+        let header = lines.next().unwrap_or_default();
+        let version = header.strip_prefix(&format!("{INDEX} "))
+            .ok_or_else(|| RepTreeError::InvalidIndex(format!("Invalid header: {header}")))?;
+        if version != INDEX_VERSION.to_string() {
+            return Err(RepTreeError::InvalidIndex(format!("Unsupported version: {version}")));
+        }
+
+        let mut index = Index::new();
+        for line in lines {
+            let entry = IndexEntry::deserialize(line)?;
+            index.entries.insert(entry.get_path(), entry);
+        }
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+
+    use crate::error::RepTreeError;
+    use crate::models::index::Index;
+
+    #[test]
+    fn should_round_trip_and_check_index_file() {
+        let directory = Path::new("tmp_index1");
+        fs::create_dir_all(directory).unwrap();
+        let file = directory.join("file.txt");
+        fs::write(&file, "hello").unwrap();
+        let index_path = directory.join("index");
+
+        let mut index = Index::new();
+        index.update_entry(Path::new("file.txt"), &fs::metadata(&file).unwrap(), "1234");
+        index.update_entry(Path::new("two\nlines.txt"), &fs::metadata(&file).unwrap(), "5678");
+        index.write_to_file(&index_path).unwrap();
+        let read = Index::read_from_file(&index_path).unwrap();
+
+        let content = fs::read_to_string(&index_path).unwrap();
+        fs::write(&index_path, content.replace("1234", "4321")).unwrap();
+        let corrupted = Index::read_from_file(&index_path);
+        fs::write(&index_path, content.replace("DITINDEX 1", "DITINDEX 9")).unwrap();
+        let other_version = Index::read_from_file(&index_path);
+        let missing = Index::read_from_file(&directory.join("missing"));
+        fs::remove_dir_all(directory).unwrap();
+
+        assert_eq!(read.get_entries(), index.get_entries());
+        assert_eq!(read.get_entry(Path::new("file.txt")).unwrap().get_size(), 5);
+        assert!(matches!(corrupted, Err(RepTreeError::InvalidIndex(_))));
+        assert!(matches!(other_version, Err(RepTreeError::InvalidIndex(_))));
+        assert!(missing.unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_only_trust_entries_older_than_index() {
+        let directory = Path::new("tmp_index2");
+        fs::create_dir_all(directory).unwrap();
+        let file = directory.join("file.txt");
+        fs::write(&file, "hello").unwrap();
+        // Modification times set explicitly, whatever the timestamp granularity of the file system
+        let past = SystemTime::now() - Duration::from_secs(10);
+        let set_modified = |time: SystemTime| fs::File::options().write(true).open(&file).unwrap().set_modified(time).unwrap();
+        set_modified(past);
+        let path = PathBuf::from("file.txt");
+
+        let mut index = Index::new();
+        index.update_entry(&path, &fs::metadata(&file).unwrap(), "1234");
+        let before_write = index.get_unchanged_blob_id(&path, &fs::metadata(&file).unwrap());
+        index.write_to_file(&directory.join("index")).unwrap();
+        let after_write = index.get_unchanged_blob_id(&path, &fs::metadata(&file).unwrap());
+        fs::write(&file, "hullo").unwrap();
+        set_modified(past + Duration::from_secs(1));
+        let after_change = index.get_unchanged_blob_id(&path, &fs::metadata(&file).unwrap());
+        fs::remove_dir_all(directory).unwrap();
+
+        assert_eq!(before_write, None);
+        assert_eq!(after_write, Some("1234".to_string()));
+        assert_eq!(after_change, None);
+    }
+}
@@ -1,6 +1,8 @@
-use std::path::{PathBuf};
+use std::path::{Component, Path, PathBuf};
 use dit_id_generator::traits::generator::Generator;
+use crate::error::RepTreeError;
 use crate::models::blob::Blob;
+use crate::models::entry_mode::EntryMode;
 use crate::models::node::Node::{BlobNode, TreeNode};
 use crate::models::tree::Tree;
 
@@ -104,7 +106,7 @@ impl Node {
     /// # Returns
     ///
     /// Return a Node of type BlobNode
-    pub fn create_blob_node(name: String, content: String, path: PathBuf) -> Node {
+    pub fn create_blob_node(name: String, content: impl Into<Vec<u8>>, path: PathBuf) -> Node {
         let mut blob: Blob = Blob::new(name,content);
         blob.set_path(path);
         BlobNode(blob)
@@ -136,7 +138,14 @@ impl Node {
         }
     }
     
-    pub fn get_mut_children(&mut self) -> Option<&mut Vec<Node>> {
+    pub fn get_children_ref(&self) -> Option<&[Node]> {
+        match self {
+            TreeNode(tree) => Some(tree.get_children_ref()),
+            _ => None
+        }
+    }
+
+    pub fn get_mut_children(&mut self) -> Option<&mut [Node]> {
         match self {
             TreeNode(tree) => Some(tree.get_mut_children()),
             _ => None
@@ -157,6 +166,29 @@ impl Node {
         }
     }
     
+    pub fn get_name_ref(&self) -> &str {
+        match self {
+            TreeNode(tree) => tree.get_name_ref(),
+            BlobNode(blob) => blob.get_name_ref()
+        }
+    }
+
+    /// Child named `name` of a `TreeNode` which is a tree or a blob, see `Tree::get_child`
+    pub fn get_child(&self, name: &str, is_tree: bool) -> Option<&Node> {
+        match self {
+            TreeNode(tree) => tree.get_child(name, is_tree),
+            _ => None
+        }
+    }
+
+    /// Mutable version of `get_child`
+    pub fn get_mut_child(&mut self, name: &str, is_tree: bool) -> Option<&mut Node> {
+        match self {
+            TreeNode(tree) => tree.get_mut_child(name, is_tree),
+            _ => None
+        }
+    }
+
     pub fn get_id(&self) -> String {
         match self {
             TreeNode(tree) => tree.get_id(),
@@ -164,6 +196,22 @@ impl Node {
         }
     }
 
+    /// Mode of the node, `Directory` for a `TreeNode`
+    pub fn get_mode(&self) -> EntryMode {
+        match self {
+            TreeNode(_) => EntryMode::Directory,
+            BlobNode(blob) => blob.get_mode()
+        }
+    }
+
+    /// Check if the id of the node has to be generated again because it was modified
+    pub fn is_dirty(&self) -> bool {
+        match self {
+            TreeNode(tree) => tree.is_dirty(),
+            BlobNode(blob) => blob.is_dirty()
+        }
+    }
+
     /// Find child of a `TreeNode`
     ///
     /// # Returns
@@ -181,7 +229,8 @@ impl Node {
     ///
     /// # Returns
     ///
-    /// Return `Option<String>`, `Some(String)` if node is `BlobNode`, otherwise `None`
+    /// Return `Result<Vec<u8>, RepTreeError>`, `Ok(Vec<u8>)` if node is `BlobNode` and its content
+    /// can be read, see `Blob::get_content`, otherwise `Err(RepTreeError)`
     ///
     /// # Examples
     ///
@@ -192,14 +241,27 @@ impl Node {
     /// let b_node = BlobNode(Blob::new(String::from("Oak"), String::from("John Doe")));
     /// let t_node = TreeNode(Tree::new(String::from("Pine"), vec![]));
     ///
-    /// assert_eq!(b_node.get_content(), Some(String::from("John Doe")));
-    /// assert_eq!(t_node.get_content(), None);
+    /// assert_eq!(b_node.get_content().unwrap(), b"John Doe".to_vec());
+    /// assert!(t_node.get_content().is_err());
     /// ```
     ///
-    pub fn get_content(&self) -> Option<String> {
+    pub fn get_content(&self) -> Result<Vec<u8>, RepTreeError> {
         match self {
-            BlobNode(blob) => Some(blob.get_content()),
-            _ => None
+            BlobNode(blob) => blob.get_content(),
+            TreeNode(tree) => Err(RepTreeError::UnexpectedComportment(format!("{} is a directory", tree.get_name())))
+        }
+    }
+
+    /// Read the contents of the unloaded blobs of the node, see `Blob::load_content`
+    pub fn load_contents(&mut self) -> Result<(), RepTreeError> {
+        match self {
+            BlobNode(blob) => blob.load_content(),
+            TreeNode(tree) => {
+                for child in tree.get_mut_children() {
+                    child.load_contents()?;
+                }
+                Ok(())
+            }
         }
     }
     
@@ -209,6 +271,119 @@ impl Node {
             BlobNode(blob) => blob.get_path()
         }
     }
+
+    /// Find a descendant from its path relative to this node
+    ///
+    /// When a tree and a blob share the last name of the path, the blob is returned.
+    ///
+    /// # Returns
+    ///
+    /// `Some(&Node)` if the path exists, otherwise `None`. An empty path returns the node itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::path::{Path, PathBuf};
+    /// # use repository_tree_creator::models::node::Node;
+    /// let main = Node::create_blob_node(String::from("main.rs"), "fn main() {}", PathBuf::new());
+    /// let src = Node::create_tree_node(String::from("src"), vec![main], PathBuf::new());
+    /// let root = Node::create_tree_node(String::from(""), vec![src], PathBuf::new());
+    ///
+    /// assert!(root.get_node_at_path(Path::new("src/main.rs")).unwrap().is_blob());
+    /// assert!(root.get_node_at_path(Path::new("src/lib.rs")).is_none());
+    /// ```
+    pub fn get_node_at_path(&self, path: &Path) -> Option<&Node> {
+        let names: Vec<String> = path_names(path);
+        let mut current = self;
+        for (position, name) in names.iter().enumerate() {
+            let is_last = position == names.len() - 1;
+            let blob = if is_last { current.get_child(name, false) } else { None };
+            current = match blob {
+                Some(blob) => blob,
+                None => current.get_child(name, true)?
+            };
+        }
+        Some(current)
+    }
+
+    /// Mutable version of `get_node_at_path`
+    pub fn get_mut_node_at_path(&mut self, path: &Path) -> Option<&mut Node> {
+        let names: Vec<String> = path_names(path);
+        let mut current = self;
+        for (position, name) in names.iter().enumerate() {
+            let is_last = position == names.len() - 1;
+            let is_tree = !is_last || current.get_child(name, false).is_none();
+            current = current.get_mut_child(name, is_tree)?;
+        }
+        Some(current)
+    }
+
+    /// Add `node` at `path`, creating the missing intermediate `TreeNode`s
+    ///
+    /// The last name of `path` is the name given to `node`. A node with the same name and type
+    /// is replaced.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the node was added, `false` if `path` is empty or goes through a blob
+    pub fn insert_node_at_path(&mut self, path: &Path, mut node: Node) -> bool {
+        let names: Vec<String> = path_names(path);
+        let Some((name, parents)) = names.split_last() else {
+            return false;
+        };
+
+        let mut current = self;
+        for parent in parents.iter() {
+            if current.get_child(parent, true).is_none() {
+                let parent_path = current.get_path().join(parent);
+                if !current.add_node_to_tree_node(Node::create_tree_node(parent.to_string(), vec![], parent_path)) {
+                    return false;
+                }
+            }
+            current = match current.get_mut_child(parent, true) {
+                Some(child) => child,
+                None => return false
+            };
+        }
+
+        let node_path = current.get_path().join(name);
+        match &mut node {
+            TreeNode(tree) => {
+                tree.set_name(name.to_string());
+                tree.set_path(node_path);
+            },
+            BlobNode(blob) => {
+                blob.set_name(name.to_string());
+                blob.set_path(node_path);
+            }
+        }
+        current.add_node_to_tree_node(node)
+    }
+
+    /// Remove the node found at `path`, see `get_node_at_path`
+    ///
+    /// # Returns
+    ///
+    /// `Some(Node)` with the removed node, `None` if nothing exists at `path`
+    pub fn remove_node_at_path(&mut self, path: &Path) -> Option<Node> {
+        let names: Vec<String> = path_names(path);
+        let (name, parents) = names.split_last()?;
+        let parent_path: PathBuf = parents.iter().collect();
+        match self.get_mut_node_at_path(&parent_path)? {
+            TreeNode(tree) => tree.remove_child(name, false).or_else(|| tree.remove_child(name, true)),
+            BlobNode(_) => None
+        }
+    }
+}
+
+/// Split a relative path in the names of its components, ignoring `.` components
+pub(crate) fn path_names(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None
+        })
+        .collect()
 }
 
 impl Generator for Node {
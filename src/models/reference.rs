@@ -0,0 +1,55 @@
+use crate::error::RepTreeError;
+
+pub const HEAD: &str = "HEAD";
+pub const HEADS_PREFIX: &str = "refs/heads/";
+pub const TAGS_PREFIX: &str = "refs/tags/";
+pub const SYMBOLIC_PREFIX: &str = "ref: ";
+
+/// Named pointer stored in the refs directory
+///
+/// A `Direct` reference holds an object id, a `Symbolic` reference holds the name of another
+/// reference, as `HEAD` does when a branch is checked out.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reference {
+    Direct(String),
+    Symbolic(String),
+}
+
+impl Reference {
+
+    /// Serialize the reference as written in its ref file
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use repository_tree_creator::models::reference::Reference;
+    /// assert_eq!(Reference::Direct(String::from("1234")).serialize(), "1234\n");
+    /// assert_eq!(Reference::Symbolic(String::from("refs/heads/main")).serialize(), "ref: refs/heads/main\n");
+    /// ```
+    pub fn serialize(&self) -> String {
+        match self {
+            Reference::Direct(id) => format!("{}\n", id),
+            Reference::Symbolic(name) => format!("{}{}\n", SYMBOLIC_PREFIX, name),
+        }
+    }
+
+    /// Parse the content of a ref file
+    ///
+    /// # Returns
+    ///
+    /// Result with the `Reference`, or `RepTreeError` if the content is empty
+    pub fn deserialize(content: &str) -> Result<Reference, RepTreeError> {
+        let content = content.trim_end();
+        if content.is_empty() {
+            return Err(RepTreeError::UnexpectedComportment("Empty reference".to_string()));
+        }
+        match content.strip_prefix(SYMBOLIC_PREFIX) {
+            Some(name) => Ok(Reference::Symbolic(name.to_string())),
+            None => Ok(Reference::Direct(content.to_string())),
+        }
+    }
+
+    pub fn is_symbolic(&self) -> bool {
+        matches!(self, Reference::Symbolic(_))
+    }
+}
@@ -1,14 +1,22 @@
 use std::path::PathBuf;
 use dit_id_generator::features::generator::generate;
 use dit_id_generator::traits::generator::Generator;
+use crate::models::blob::BLOB;
+use crate::models::entry_mode::EntryMode;
 use crate::models::node::Node;
+use crate::utils::quote_name;
+
+pub const TREE: &str = "TREE";
 
 #[derive(Clone, Debug, Default)]
 pub struct Tree {
     id: String,
     name: String,
+    // Sorted by type then by name, see `serialize`, so that children are found by binary search
     children: Vec<Node>,
-    path: PathBuf
+    path: PathBuf,
+    // Whether the id has to be generated again, see `generate_id`
+    dirty: bool
 }
 
 impl Tree {
@@ -23,7 +31,8 @@ impl Tree {
             id: "".to_string(),
             name: "".to_string(),
             children: vec![],
-            path: PathBuf::new()
+            path: PathBuf::new(),
+            dirty: true
         }
     }
 
@@ -32,7 +41,7 @@ impl Tree {
     /// # Arguments
     ///
     /// * `name` - directory name
-    /// * `children` - list directory's elements
+    /// * `children` - list directory's elements, in any order
     ///
     /// # Returns
     ///
@@ -41,8 +50,9 @@ impl Tree {
         Tree {
             id: "".to_string(),
             name,
-            children,
-            path: PathBuf::new()
+            children: sort_children(children),
+            path: PathBuf::new(),
+            dirty: true
         }
     }
 
@@ -50,6 +60,10 @@ impl Tree {
         self.name.to_string()
     }
 
+    pub fn get_name_ref(&self) -> &str {
+        &self.name
+    }
+
     pub fn set_name(&mut self, name: String) {
         self.name = name;
     }
@@ -58,20 +72,70 @@ impl Tree {
         self.id.to_string()
     }
 
+    /// Set the id, which is generated again by `generate_id` as it may not match the content
     pub fn set_id(&mut self, id: String){
         self.id = id;
+        self.dirty = true;
     }
     
     pub fn get_children(&self) -> Vec<Node> {
         self.children.clone()
     }
     
-    pub fn get_mut_children(&mut self) -> &mut Vec<Node> {
+    pub fn get_children_ref(&self) -> &[Node] {
+        &self.children
+    }
+
+    /// Mutable access to the children, marking the tree as dirty
+    ///
+    /// Children are added and removed with `add_node` and `remove_child`, which keep them
+    /// sorted.
+    pub fn get_mut_children(&mut self) -> &mut [Node] {
+        self.dirty = true;
         &mut self.children
     }
     
     pub fn set_children(&mut self, children: Vec<Node>) {
-        self.children = children;
+        self.dirty = true;
+        self.children = sort_children(children);
+    }
+
+    /// Position of the child named `name` which is a tree or a blob
+    ///
+    /// # Returns
+    ///
+    /// `Ok` with the position of the child, or `Err` with the position where it would be
+    /// inserted
+    fn search_child(&self, name: &str, is_tree: bool) -> Result<usize, usize> {
+        self.children.binary_search_by(|child| (child.is_tree(), child.get_name_ref()).cmp(&(is_tree, name)))
+    }
+
+    /// Child named `name` which is a tree or a blob
+    pub fn get_child(&self, name: &str, is_tree: bool) -> Option<&Node> {
+        self.search_child(name, is_tree).ok().map(|index| &self.children[index])
+    }
+
+    /// Mutable version of `get_child`, marking the tree as dirty
+    pub fn get_mut_child(&mut self, name: &str, is_tree: bool) -> Option<&mut Node> {
+        let index = self.search_child(name, is_tree).ok()?;
+        self.dirty = true;
+        Some(&mut self.children[index])
+    }
+
+    /// Remove the child named `name` which is a tree or a blob
+    ///
+    /// # Returns
+    ///
+    /// `Some(Node)` with the removed child, `None` if there is no such child
+    pub fn remove_child(&mut self, name: &str, is_tree: bool) -> Option<Node> {
+        let index = self.search_child(name, is_tree).ok()?;
+        self.dirty = true;
+        Some(self.children.remove(index))
+    }
+
+    /// Check if the id has to be generated again because the tree was modified
+    pub fn is_dirty(&self) -> bool {
+        self.dirty || self.id.is_empty()
     }
     
     pub fn set_path(&mut self, path: PathBuf) {
@@ -83,7 +147,7 @@ impl Tree {
     }
     
     pub fn find_child(&mut self, node: &Node) -> Option<&mut Node> {
-        self.children.iter_mut().find(|x| x.has_same_name(node) && x.is_tree())
+        self.get_mut_child(node.get_name_ref(), true)
     }
     
     /// Check if two `Tree` instances has same name
@@ -143,48 +207,198 @@ impl Tree {
     /// assert_eq!(t3.get_children().len(), 2);
     /// ```
     pub fn add_node(&mut self, node: Node) {
-
-        if let Some(position) = self.children
-            .iter()
-            .position(|n| Node::has_same_name(n, &node) && Node::is_same_type(n, &node)) {
-            
-            self.children.remove(position);
+        self.dirty = true;
+        match self.search_child(node.get_name_ref(), node.is_tree()) {
+            Ok(index) => self.children[index] = node,
+            Err(index) => self.children.insert(index, node)
         }
-        
-        self.children.push(node);
     }
     
     pub fn replace_node_among_children(&mut self, other: Node) {
-        if let Some(existing_node) = self.children
-            .iter_mut()
-            .find(|x| 
-                  x.get_name() == other.get_name()
-                  && Node::is_same_type(x, &other)) {
+        if let Some(existing_node) = self.get_mut_child(other.get_name_ref(), other.is_tree()) {
             *existing_node = other;
         }
     }
     
     pub fn exist_node_same_name_and_type(&self, other: &Node) -> bool{
-        self.children.iter().any(|n| n.get_name() == other.get_name() && Node::is_tree(n))
+        self.get_child(other.get_name_ref(), true).is_some()
+    }
+
+    /// Canonical serialization of the tree, used both as content of the tree object and to
+    /// generate the tree id
+    ///
+    /// Each child is written on its own line as `<TYPE> <id> <name>`, lines being sorted by type
+    /// then by name, so the result doesn't depend on the order in which children were added.
+    /// Blobs which aren't regular files are written as `<TYPE> <mode> <id> <name>`, see
+    /// `EntryMode`, so that the trees without them keep the same ids.
+    ///
+    /// # Returns
+    ///
+    /// The serialized tree
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use repository_tree_creator::models::blob::Blob;
+    /// # use repository_tree_creator::models::entry_mode::EntryMode;
+    /// # use repository_tree_creator::models::node::Node::{BlobNode, TreeNode};
+    /// # use repository_tree_creator::models::tree::Tree;
+    /// let mut blob = Blob::new(String::from("Ball"), String::from("booing booing"));
+    /// blob.set_id(String::from("1234"));
+    /// let mut tree = Tree::new(String::from("Acorn"), vec![]);
+    /// tree.set_id(String::from("5678"));
+    /// let mut script = Blob::new(String::from("run.sh"), String::from("#!/bin/sh"));
+    /// script.set_id(String::from("9012"));
+    /// script.set_mode(EntryMode::Executable);
+    /// let oak = Tree::new(String::from("Oak"), vec![TreeNode(tree), BlobNode(blob), BlobNode(script)]);
+    ///
+    /// assert_eq!(oak.serialize(), "BLOB 1234 Ball\nBLOB 100755 9012 run.sh\nTREE 5678 Acorn\n");
+    /// ```
+    pub fn serialize(&self) -> String {
+        // Children are already sorted, blobs coming before trees as `BLOB` before `TREE`
+        self.children
+            .iter()
+            .map(|node| {
+                let (node_type, id, mode) = match node {
+                    Node::TreeNode(tree) => (TREE, tree.get_id(), EntryMode::Directory),
+                    Node::BlobNode(blob) => (BLOB, blob.get_id(), blob.get_mode())
+                };
+                match mode {
+                    EntryMode::Regular | EntryMode::Directory => format!("{} {} {}\n", node_type, id, quote_name(node.get_name_ref())),
+                    _ => format!("{} {} {} {}\n", node_type, mode.as_str(), id, quote_name(node.get_name_ref()))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Sort children by type then by name, keeping the last of the children with the same name
+/// and type as `add_node` does
+fn sort_children(children: Vec<Node>) -> Vec<Node> {
+    let mut sorted: Vec<Node> = Vec::with_capacity(children.len());
+    let mut children: Vec<(usize, Node)> = children.into_iter().enumerate().collect();
+    children.sort_by(|(position1, child1), (position2, child2)| {
+        (child1.is_tree(), child1.get_name_ref()).cmp(&(child2.is_tree(), child2.get_name_ref()))
+            .then(position2.cmp(position1))
+    });
+    for (_, child) in children {
+        let is_duplicate = sorted.last()
+            .is_some_and(|last| last.is_tree() == child.is_tree() && last.get_name_ref() == child.get_name_ref());
+        if !is_duplicate {
+            sorted.push(child);
+        }
     }
+    sorted
 }
 
+/// Ids are generated bottom-up, only for the subtrees modified since their id was last
+/// generated: any mutable access to the children of a tree marks it as dirty, so the trees on
+/// the path to a modified node are dirty, while unmodified subtrees keep their id.
 impl Generator for Tree {
     fn generate_id(&mut self) -> String {
-        let mut content = String::from("");
-        return if self.children.len() == 0 {
-            let content = self.get_name().clone();
-            let id = generate(content);
-            self.set_id(id.clone());
-            id
-        } else {
-            for node in self.children.iter_mut() {
-                node.generate_id();
-                content += &*node.get_id()
-            }
-            let id = generate(content);
-            self.set_id(id.clone());
-            id
+        if !self.is_dirty() {
+            return self.get_id();
         }
+        for node in self.children.iter_mut() {
+            node.generate_id();
+        }
+        let id = generate(self.serialize());
+        self.id = id.clone();
+        self.dirty = false;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use dit_id_generator::traits::generator::Generator;
+    use crate::models::blob::Blob;
+    use crate::models::node::Node;
+    use crate::models::node::Node::{BlobNode, TreeNode};
+    use crate::models::tree::Tree;
+
+    fn blob(name: &str, content: &str) -> Blob {
+        Blob::new(name.to_string(), content.to_string())
+    }
+
+    #[test]
+    fn should_not_depend_on_insertion_order() {
+        let mut t1 = Tree::new("".to_string(), vec![BlobNode(blob("a.txt", "a")), BlobNode(blob("b.txt", "b"))]);
+        let mut t2 = Tree::new("".to_string(), vec![BlobNode(blob("b.txt", "b")), BlobNode(blob("a.txt", "a"))]);
+
+        assert_eq!(t1.generate_id(), t2.generate_id());
+    }
+
+    #[test]
+    fn should_change_id_on_rename() {
+        let mut t1 = Tree::new("".to_string(), vec![BlobNode(blob("a.txt", "a"))]);
+        let mut t2 = Tree::new("".to_string(), vec![BlobNode(blob("b.txt", "a"))]);
+
+        assert_ne!(t1.generate_id(), t2.generate_id());
+    }
+
+    #[test]
+    fn should_change_id_on_type_change() {
+        // An empty file and an empty directory have the same id
+        let mut t1 = Tree::new("".to_string(), vec![BlobNode(blob("a", ""))]);
+        let mut t2 = Tree::new("".to_string(), vec![TreeNode(Tree::new("a".to_string(), vec![]))]);
+
+        assert_ne!(t1.generate_id(), t2.generate_id());
+    }
+
+    #[test]
+    fn should_give_same_id_to_empty_trees() {
+        let mut t1 = Tree::new("Oak".to_string(), vec![]);
+        let mut t2 = Tree::new("Pine".to_string(), vec![]);
+
+        assert_eq!(t1.generate_id(), t2.generate_id());
+    }
+
+    #[test]
+    fn should_only_regenerate_modified_subtrees() {
+        let src = Tree::new("src".to_string(), vec![BlobNode(blob("main.rs", "fn main() {}"))]);
+        let docs = Tree::new("docs".to_string(), vec![BlobNode(blob("guide.md", "# Guide"))]);
+        let mut root = TreeNode(Tree::new("".to_string(), vec![TreeNode(src), TreeNode(docs)]));
+        root.generate_id();
+        assert!(!root.is_dirty());
+
+        if let Some(BlobNode(main)) = root.get_mut_node_at_path(Path::new("src/main.rs")) {
+            main.set_content("fn main() { println!(); }");
+        }
+        let is_dirty = |root: &Node, path: &str| root.get_node_at_path(Path::new(path)).unwrap().is_dirty();
+
+        assert!(is_dirty(&root, "") && is_dirty(&root, "src") && is_dirty(&root, "src/main.rs"));
+        assert!(!is_dirty(&root, "docs") && !is_dirty(&root, "docs/guide.md"));
+        let src = Tree::new("src".to_string(), vec![BlobNode(blob("main.rs", "fn main() { println!(); }"))]);
+        let docs = Tree::new("docs".to_string(), vec![BlobNode(blob("guide.md", "# Guide"))]);
+        let mut expected = Tree::new("".to_string(), vec![TreeNode(src), TreeNode(docs)]);
+        assert_eq!(root.generate_id(), expected.generate_id());
+        assert!(!root.is_dirty());
+    }
+
+    #[test]
+    fn should_keep_children_sorted() {
+        let mut tree = Tree::new("".to_string(), vec![
+            BlobNode(blob("b.txt", "first")),
+            TreeNode(Tree::new("a".to_string(), vec![])),
+            BlobNode(blob("b.txt", "last")),
+        ]);
+        for number in (0..100).rev() {
+            tree.add_node(BlobNode(blob(&format!("{:03}.txt", number), "")));
+        }
+        tree.add_node(BlobNode(blob("a", "")));
+        tree.add_node(BlobNode(blob("050.txt", "replaced")));
+        let names: Vec<(bool, String)> = tree.get_children_ref().iter().map(|child| (child.is_tree(), child.get_name())).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+
+        assert_eq!(names, sorted);
+        assert_eq!(names.len(), 103);
+        assert_eq!(tree.get_child("b.txt", false).unwrap().get_content().unwrap(), b"last".to_vec());
+        assert_eq!(tree.get_child("050.txt", false).unwrap().get_content().unwrap(), b"replaced".to_vec());
+        assert!(tree.get_child("a", true).unwrap().is_tree());
+        assert!(tree.remove_child("a", false).is_some());
+        assert!(tree.get_child("a", false).is_none() && tree.get_child("a", true).is_some());
     }
 }
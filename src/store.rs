@@ -0,0 +1,3 @@
+pub mod object_store;
+pub mod file_system_store;
+pub mod memory_store;
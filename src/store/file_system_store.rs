@@ -0,0 +1,137 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use dit_file_encryptor::CompressedFile;
+
+use crate::error::RepTreeError;
+use crate::store::object_store::ObjectStore;
+use crate::utils::open_object_file;
+
+/// `ObjectStore` writing compressed objects on disk.
+///
+/// Objects are fanned out in directories named after the first two characters of their id,
+/// the file name being the rest of the id: object `1234567890` is stored in `<path>/12/34567890`.
+#[derive(Clone, Debug)]
+pub struct FileSystemStore {
+    path: PathBuf
+}
+
+impl FileSystemStore {
+    pub fn new(path: PathBuf) -> FileSystemStore {
+        FileSystemStore {
+            path
+        }
+    }
+
+    pub fn get_path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    /// Return the directory and file name of an object
+    ///
+    /// # Arguments
+    ///
+    /// * `object_id` - id of the object
+    ///
+    /// # Returns
+    ///
+    /// Tuple with the fan-out directory path and the file name
+    fn object_location(&self, object_id: &str) -> (PathBuf, String) {
+        let directory: String = object_id.chars().take(2).collect();
+        let filename: String = object_id.chars().skip(2).collect();
+        (self.path.join(directory), filename)
+    }
+}
+
+impl ObjectStore for FileSystemStore {
+    fn get(&self, object_id: &str) -> Result<Vec<u8>, RepTreeError> {
+        if object_id.len() < 2 {
+            return Err(RepTreeError::UnexpectedComportment(format!("Invalid object id: {object_id}")));
+        }
+        let mut reader = open_object_file(&object_id.to_string(), &self.path).map_err(RepTreeError::IoError)?;
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).map_err(RepTreeError::IoError)?;
+        Ok(content)
+    }
+
+    fn put(&mut self, object_id: &str, content: &[u8]) -> Result<(), RepTreeError> {
+        let (directory, filename) = self.object_location(object_id);
+        if !directory.is_dir() {
+            fs::create_dir_all(&directory).map_err(RepTreeError::IoError)?;
+        }
+
+        let mut writer = CompressedFile::new(directory.join(filename))
+            .open_for_write()
+            .map_err(|_e| {
+                RepTreeError::Encryptor("Error creating writer for compressed file".to_string())
+            })?;
+
+        writer.write_all(content).map_err(RepTreeError::IoError)?;
+        writer.flush().map_err(RepTreeError::IoError)?;
+        Ok(())
+    }
+
+    fn contains(&self, object_id: &str) -> bool {
+        let (directory, filename) = self.object_location(object_id);
+        directory.join(filename).is_file()
+    }
+
+    fn list(&self) -> Result<Vec<String>, RepTreeError> {
+        let mut ids: Vec<String> = Vec::new();
+        if !self.path.is_dir() {
+            return Ok(ids);
+        }
+
+        for directory in fs::read_dir(&self.path).map_err(RepTreeError::IoError)? {
+            let directory = directory.map_err(RepTreeError::IoError)?;
+            let prefix = directory.file_name().to_string_lossy().to_string();
+            if prefix.chars().count() != 2 || !directory.path().is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(directory.path()).map_err(RepTreeError::IoError)? {
+                let file = file.map_err(RepTreeError::IoError)?;
+                if file.path().is_file() {
+                    ids.push(format!("{}{}", prefix, file.file_name().to_string_lossy()));
+                }
+            }
+        }
+
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::store::file_system_store::FileSystemStore;
+    use crate::store::object_store::ObjectStore;
+
+    #[test]
+    fn should_create_details(){
+        let store = FileSystemStore::new(PathBuf::from(""));
+
+        let (directory, filename) = store.object_location("12345667890");
+        assert_eq!(PathBuf::from("12"), directory);
+        assert_eq!("345667890", filename);
+    }
+
+    #[test]
+    fn should_put_get_and_list_objects(){
+        let mut store = FileSystemStore::new(PathBuf::from("tmp_store"));
+        store.put("1234567890", b"Hello").expect("Failed to put object");
+        store.put("ab34567890", b"World").expect("Failed to put object");
+
+        let content = store.get("1234567890");
+        let ids = store.list();
+        let exists = PathBuf::from("tmp_store/12/34567890").is_file();
+        fs::remove_dir_all("tmp_store").unwrap_or_else(|_| println!("Failed to delete temporary directory"));
+
+        assert!(exists, "File should exist");
+        assert_eq!(content.unwrap(), b"Hello");
+        assert_eq!(ids.unwrap(), vec!["1234567890".to_string(), "ab34567890".to_string()]);
+    }
+}
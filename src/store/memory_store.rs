@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::ErrorKind;
+
+use crate::error::RepTreeError;
+use crate::store::object_store::ObjectStore;
+
+/// `ObjectStore` keeping objects in memory, for tests and ephemeral trees.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    objects: HashMap<String, Vec<u8>>
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore {
+            objects: HashMap::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+impl ObjectStore for MemoryStore {
+    fn get(&self, object_id: &str) -> Result<Vec<u8>, RepTreeError> {
+        self.objects
+            .get(object_id)
+            .cloned()
+            .ok_or_else(|| {
+                RepTreeError::IoError(io::Error::new(ErrorKind::NotFound, format!("Error object not found: {object_id}")))
+            })
+    }
+
+    fn put(&mut self, object_id: &str, content: &[u8]) -> Result<(), RepTreeError> {
+        self.objects.insert(object_id.to_string(), content.to_vec());
+        Ok(())
+    }
+
+    fn contains(&self, object_id: &str) -> bool {
+        self.objects.contains_key(object_id)
+    }
+
+    fn list(&self) -> Result<Vec<String>, RepTreeError> {
+        let mut ids: Vec<String> = self.objects.keys().cloned().collect();
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::memory_store::MemoryStore;
+    use crate::store::object_store::ObjectStore;
+
+    #[test]
+    fn should_put_and_get_object() {
+        let mut store = MemoryStore::new();
+        store.put("1234", b"Hello").unwrap();
+
+        assert!(store.contains("1234"));
+        assert!(!store.contains("5678"));
+        assert_eq!(store.get("1234").unwrap(), b"Hello");
+        assert!(store.get("5678").is_err());
+    }
+
+    #[test]
+    fn should_list_objects_sorted() {
+        let mut store = MemoryStore::new();
+        store.put("b2", b"").unwrap();
+        store.put("a1", b"").unwrap();
+        store.put("b2", b"again").unwrap();
+
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.list().unwrap(), vec!["a1".to_string(), "b2".to_string()]);
+    }
+}
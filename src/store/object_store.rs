@@ -0,0 +1,40 @@
+use crate::error::RepTreeError;
+
+/// Storage backend for repository objects, addressed by their id.
+///
+/// Every feature which reads or writes objects goes through this trait, so the storage
+/// (disk, memory, remote...) can be swapped without changing the features.
+pub trait ObjectStore {
+    /// Read the content of an object
+    ///
+    /// # Arguments
+    ///
+    /// * `object_id` - id of the object
+    ///
+    /// # Returns
+    ///
+    /// The object content, or `RepTreeError` if the object doesn't exist or can't be read
+    fn get(&self, object_id: &str) -> Result<Vec<u8>, RepTreeError>;
+
+    /// Write an object, replacing any object already stored with the same id
+    ///
+    /// # Arguments
+    ///
+    /// * `object_id` - id of the object
+    /// * `content` - object content
+    fn put(&mut self, object_id: &str, content: &[u8]) -> Result<(), RepTreeError>;
+
+    /// Check if an object is stored
+    ///
+    /// # Returns
+    ///
+    /// `true` if an object with this id exists, otherwise `false`
+    fn contains(&self, object_id: &str) -> bool;
+
+    /// List ids of every stored object
+    ///
+    /// # Returns
+    ///
+    /// Ids sorted in ascending order, or `RepTreeError` if the store can't be read
+    fn list(&self) -> Result<Vec<String>, RepTreeError>;
+}
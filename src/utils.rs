@@ -34,4 +34,192 @@ pub fn open_object_file(object_id: &String, object_path: &PathBuf) -> Result<Box
         }
     }
     Err(io::Error::new(io::ErrorKind::NotFound, "Error file not found in objects: {object_id}"))
-}
\ No newline at end of file
+}
+
+/// **Description**  
+/// This function checks if a path matches a glob pattern. `*` matches any sequence of
+/// characters except `/`, `**` matches any sequence of characters including `/` and `?` matches
+/// a single character other than `/`. `[abc]`, `[a-z]` and `[!a-z]` match a single character of
+/// a class and `\` makes the next character match literally. A pattern without `/` is matched
+/// against the last component of the path only, so `*.lock` matches `Cargo.lock` as well as
+/// `app/Cargo.lock`.
+///
+/// **Parameters**  
+/// - `pattern`: The glob pattern.  
+/// - `path`: The path to check, components being separated by `/`.  
+///
+/// **Returns**  
+/// - `bool`: `true` if the path matches the pattern.  
+pub fn matches_glob(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    if pattern.contains('/') {
+        matches_glob_bytes(pattern.as_bytes(), path.as_bytes())
+    } else {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        matches_glob_bytes(pattern.as_bytes(), name.as_bytes())
+    }
+}
+
+/// **Description**  
+/// This function checks if a whole path matches a glob pattern, with the syntax of
+/// `matches_glob`, even if the pattern doesn't contain `/`.
+///
+/// **Parameters**  
+/// - `pattern`: The glob pattern.  
+/// - `path`: The path to check, components being separated by `/`.  
+///
+/// **Returns**  
+/// - `bool`: `true` if the path matches the pattern.  
+pub fn matches_glob_anchored(pattern: &str, path: &str) -> bool {
+    matches_glob_bytes(pattern.as_bytes(), path.as_bytes())
+}
+
+/// **Description**  
+/// This function quotes a name written on a line of a file, a tree object, an index or a
+/// journal, as git does for unusual paths. A name containing a line break, or starting with
+/// `"`, is written between `"` with `\`, `"`, `\n` and `\r` escaped. Other names are written as
+/// they are, so that the files written before names were quoted stay valid.
+///
+/// **Parameters**  
+/// - `name`: The name or path to write.  
+///
+/// **Returns**  
+/// - `String`: The name to write on the line, read back with `unquote_name`.  
+pub fn quote_name(name: &str) -> String {
+    if !name.contains(['\n', '\r']) && !name.starts_with('"') {
+        return name.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for character in name.chars() {
+        match character {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            _ => quoted.push(character)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// **Description**  
+/// This function reads a name written by `quote_name`.
+///
+/// **Parameters**  
+/// - `name`: The name as written on the line.  
+///
+/// **Returns**  
+/// - `Option<String>`: The name, or `None` if a quoted name has an invalid escape.  
+pub fn unquote_name(name: &str) -> Option<String> {
+    let quoted = match name.strip_prefix('"').and_then(|name| name.strip_suffix('"')) {
+        Some(quoted) if name.len() >= 2 => quoted,
+        _ => return Some(name.to_string())
+    };
+    let mut unquoted = String::new();
+    let mut characters = quoted.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            unquoted.push(character);
+            continue;
+        }
+        match characters.next()? {
+            '\\' => unquoted.push('\\'),
+            '"' => unquoted.push('"'),
+            'n' => unquoted.push('\n'),
+            'r' => unquoted.push('\r'),
+            _ => return None
+        }
+    }
+    Some(unquoted)
+}
+
+/// Match the first character of `text` against the class starting after the `[` of a
+/// pattern, returning the rest of the pattern after the `]`, or `None` if the class isn't closed
+fn match_class<'a>(class: &'a [u8], text: &[u8]) -> Option<(bool, &'a [u8])> {
+    let (negated, class) = match class {
+        [b'!' | b'^', rest @ ..] => (true, rest),
+        _ => (false, class),
+    };
+    // A `]` right after the `[` is part of the class
+    let end = class.iter().skip(1).position(|c| *c == b']')? + 1;
+    let character = match text {
+        [c, ..] if *c != b'/' => *c,
+        _ => return Some((false, &class[end + 1..])),
+    };
+    let members = &class[..end];
+    let mut i = 0;
+    let mut matched = false;
+    while i < members.len() {
+        if i + 2 < members.len() && members[i + 1] == b'-' {
+            matched |= (members[i]..=members[i + 2]).contains(&character);
+            i += 3;
+        } else {
+            matched |= members[i] == character;
+            i += 1;
+        }
+    }
+    Some((matched != negated, &class[end + 1..]))
+}
+
+fn matches_glob_bytes(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            // `**/` also matches no directory at all
+            matches_glob_bytes(rest, text)
+                || (0..text.len()).any(|i| text[i] == b'/' && matches_glob_bytes(rest, &text[i + 1..]))
+        },
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| matches_glob_bytes(rest, &text[i..])),
+        [b'*', rest @ ..] => {
+            let segment_end = text.iter().position(|c| *c == b'/').unwrap_or(text.len());
+            (0..=segment_end).any(|i| matches_glob_bytes(rest, &text[i..]))
+        },
+        [b'?', rest @ ..] => matches!(text, [c, ..] if *c != b'/') && matches_glob_bytes(rest, &text[1..]),
+        [b'[', class @ ..] => match match_class(class, text) {
+            Some((matched, rest)) => matched && matches_glob_bytes(rest, &text[1..]),
+            None => matches!(text, [b'[', ..]) && matches_glob_bytes(class, &text[1..]),
+        },
+        [b'\\', c, rest @ ..] => matches!(text, [t, ..] if t == c) && matches_glob_bytes(rest, &text[1..]),
+        [c, rest @ ..] => matches!(text, [t, ..] if t == c) && matches_glob_bytes(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{matches_glob, matches_glob_anchored, quote_name, unquote_name};
+
+    #[test]
+    fn should_match_glob() {
+        assert!(matches_glob("*.lock", "Cargo.lock"));
+        assert!(matches_glob("*.lock", "app/Cargo.lock"));
+        assert!(!matches_glob("*.lock", "Cargo.lock.bak"));
+        assert!(matches_glob("docs/*.md", "docs/guide.md"));
+        assert!(!matches_glob("docs/*.md", "docs/api/guide.md"));
+        assert!(matches_glob("docs/**/*.md", "docs/guide.md"));
+        assert!(matches_glob("docs/**/*.md", "docs/api/guide.md"));
+        assert!(matches_glob("/CHANGELOG.?d", "CHANGELOG.md"));
+        assert!(matches_glob("src/**", "src/a/b.rs"));
+        assert!(matches_glob(".*.sw[op]", "src/.main.rs.swp"));
+        assert!(!matches_glob(".*.sw[op]", ".main.rs.swx"));
+        assert!(matches_glob("v[0-9]", "v7"));
+        assert!(matches_glob("v[!0-9]", "vx"));
+        assert!(matches_glob("a[b", "a[b"));
+        assert!(matches_glob("\\*.md", "*.md"));
+        assert!(!matches_glob("\\*.md", "a.md"));
+        assert!(matches_glob_anchored("target", "target"));
+        assert!(!matches_glob_anchored("target", "app/target"));
+    }
+
+    #[test]
+    fn should_quote_names() {
+        assert_eq!(quote_name("main.rs"), "main.rs");
+        assert_eq!(quote_name("a \\ b"), "a \\ b");
+        assert_eq!(quote_name("two\nlines"), "\"two\\nlines\"");
+        assert_eq!(quote_name("\"quoted\""), "\"\\\"quoted\\\"\"");
+        for name in ["main.rs", "two\nlines", "\"quoted\"", "back\\slash\r\n", "\""] {
+            assert_eq!(unquote_name(&quote_name(name)).as_deref(), Some(name));
+        }
+        assert_eq!(unquote_name("\"bad\\escape\""), None);
+    }
+}
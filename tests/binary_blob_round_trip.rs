@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::PathBuf;
+use repository_tree_creator::features::get_repository_tree_from_object_files::get_repository_tree_from_object_files;
+use repository_tree_creator::features::transcript_repository_tree_to_object_files::transcript_repository_to_object_files;
+use repository_tree_creator::models::blob::Blob;
+use repository_tree_creator::models::node::Node::{BlobNode, TreeNode};
+use repository_tree_creator::models::tree::Tree;
+
+const BLOB_ID: &str = "1200000000000000000000000000000000000001";
+const ROOT_ID: &str = "1200000000000000000000000000000000000000";
+
+fn setup(){
+    fs::create_dir_all("tempdir_binary/12").unwrap();
+}
+
+fn teardown(){
+    fs::remove_dir_all("tempdir_binary").unwrap()
+}
+
+#[test]
+fn should_round_trip_binary_blob_through_object_files() {
+    setup();
+
+    let content: Vec<u8> = vec![0x89, b'P', b'N', b'G', 0x00, 0xff, 0xfe, b'\n', 0x80, 0x00];
+    let mut blob = Blob::new("image.png".to_string(), content.clone());
+    blob.set_id(String::from(BLOB_ID));
+    let mut root = Tree::new("".to_string(), vec![BlobNode(blob)]);
+    root.set_id(String::from(ROOT_ID));
+
+    transcript_repository_to_object_files(&TreeNode(root), &PathBuf::from("tempdir_binary")).unwrap();
+
+    let mut restored = Tree::default();
+    get_repository_tree_from_object_files(&mut restored, &String::from(ROOT_ID), &PathBuf::from("tempdir_binary")).unwrap();
+
+    teardown();
+
+    let children = restored.get_children();
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0].get_name(), "image.png");
+    assert_eq!(children[0].get_content().unwrap(), content);
+}
+
+#[test]
+fn should_keep_text_ids_and_tell_binary_apart() {
+    use dit_id_generator::features::generator::generate;
+    use dit_id_generator::traits::generator::Generator;
+
+    let text = "Größe: 10 €\n";
+    let mut text_blob = Blob::new("text.txt".to_string(), text);
+    // Latin-1 bytes of the same text, which aren't UTF-8
+    let latin1: Vec<u8> = text.chars().filter(|c| *c != '€').map(|c| c as u8).collect();
+    let mut latin1_blob = Blob::new("latin1.txt".to_string(), latin1.clone());
+    let mut mapped_blob = Blob::new("mapped.txt".to_string(), latin1.iter().map(|&byte| byte as char).collect::<String>());
+
+    assert_eq!(text_blob.generate_id(), generate(text.to_string()));
+    assert_ne!(latin1_blob.generate_id(), mapped_blob.generate_id());
+}
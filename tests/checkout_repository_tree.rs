@@ -0,0 +1,291 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use dit_id_generator::features::generator::generate;
+use dit_id_generator::traits::generator::Generator;
+use repository_tree_creator::error::RepTreeError;
+use repository_tree_creator::features::transcript_repository_to_files::{checkout_repository_tree, CheckoutOptions, Mode, resume_checkout, rollback_checkout, transcript_repository_tree_to_files};
+use repository_tree_creator::models::blob::Blob;
+use repository_tree_creator::models::checkout_journal::get_default_journal_path;
+use repository_tree_creator::models::node::Node;
+
+fn blob(name: &str, content: &str) -> Node {
+    Node::create_blob_node(name.to_string(), content, PathBuf::from(name))
+}
+
+fn tree(name: &str, children: Vec<Node>) -> Node {
+    let mut node = Node::create_tree_node(name.to_string(), children, PathBuf::from(name));
+    node.generate_id();
+    node
+}
+
+fn setup(name: &'static str) -> &'static Path {
+    fs::create_dir_all(name).unwrap();
+    Path::new(name)
+}
+
+fn teardown(directory: &Path) {
+    fs::remove_dir_all(directory).unwrap()
+}
+
+#[test]
+fn should_only_remove_tracked_files_on_checkout() {
+    let directory = setup("tempdir_checkout");
+    let empty = tree("", vec![]);
+    let current = tree("", vec![
+        blob("README.md", "Hello"),
+        tree("src", vec![blob("main.rs", "fn main() {}"), blob("old.rs", "// old")]),
+        tree("legacy", vec![blob("a.txt", "a")]),
+        tree("gone", vec![tree("deep", vec![blob("b.txt", "b")])]),
+        blob("build", "#!/bin/sh"),
+    ]);
+    let target = tree("", vec![
+        blob("README.md", "Hello, World"),
+        tree("src", vec![blob("main.rs", "fn main() {}")]),
+        tree("build", vec![blob("run.sh", "#!/bin/sh")]),
+        tree("docs", vec![blob("guide.md", "# Guide")]),
+    ]);
+
+    checkout_repository_tree(&empty, &current, directory, &CheckoutOptions::default()).unwrap();
+    fs::write(directory.join("legacy/notes.txt"), "untracked").unwrap();
+    fs::write(directory.join("untracked.txt"), "untracked").unwrap();
+    checkout_repository_tree(&current, &target, directory, &CheckoutOptions::default()).unwrap();
+
+    let read = |path: &str| fs::read_to_string(directory.join(path)).ok();
+    let contents = [read("README.md"), read("src/main.rs"), read("build/run.sh"), read("docs/guide.md")];
+    let removed = [read("src/old.rs"), read("legacy/a.txt")];
+    let kept = [read("legacy/notes.txt"), read("untracked.txt")];
+    let gone = directory.join("gone").exists();
+
+    teardown(directory);
+
+    assert_eq!(contents, [Some("Hello, World".to_string()), Some("fn main() {}".to_string()), Some("#!/bin/sh".to_string()), Some("# Guide".to_string())]);
+    assert_eq!(removed, [None, None]);
+    assert_eq!(kept, [Some("untracked".to_string()), Some("untracked".to_string())]);
+    assert!(!gone);
+}
+
+#[test]
+fn should_refuse_to_overwrite_local_changes() {
+    let directory = setup("tempdir_checkout_local_changes");
+    let current = tree("", vec![
+        blob("a.txt", "a"),
+        blob("b.txt", "b"),
+        blob("c.txt", "c"),
+        tree("dir", vec![blob("d.txt", "d")]),
+    ]);
+    let target = tree("", vec![
+        blob("a.txt", "a2"),
+        blob("b.txt", "b2"),
+        blob("new.txt", "new"),
+        blob("dir", "now a file"),
+    ]);
+    checkout_repository_tree(&tree("", vec![]), &current, directory, &CheckoutOptions::default()).unwrap();
+    // Modified, already up to date, deleted in the target, untracked in the way, and under a removed directory
+    fs::write(directory.join("a.txt"), "local").unwrap();
+    fs::write(directory.join("b.txt"), "b2").unwrap();
+    fs::write(directory.join("c.txt"), "local").unwrap();
+    fs::write(directory.join("new.txt"), "local").unwrap();
+    fs::write(directory.join("dir/d.txt"), "local").unwrap();
+
+    let refused = checkout_repository_tree(&current, &target, directory, &CheckoutOptions::default());
+    let untouched = fs::read_to_string(directory.join("a.txt")).unwrap();
+    let forced = checkout_repository_tree(&current, &target, directory, &CheckoutOptions { force: true, ..CheckoutOptions::default() });
+    let overwritten = fs::read_to_string(directory.join("a.txt")).unwrap();
+
+    teardown(directory);
+
+    match refused {
+        Err(RepTreeError::LocalChanges(paths)) => assert_eq!(paths, vec![
+            PathBuf::from("a.txt"),
+            PathBuf::from("c.txt"),
+            PathBuf::from("dir/d.txt"),
+            PathBuf::from("new.txt"),
+        ]),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(untouched, "local");
+    assert!(forced.is_ok());
+    assert_eq!(overwritten, "a2");
+}
+
+#[test]
+fn should_report_untracked_files_in_the_way() {
+    let directory = setup("tempdir_checkout_obstructions");
+    let current = tree("", vec![
+        blob("a.txt", "a"),
+        tree("build", vec![blob("run.sh", "#!/bin/sh")]),
+    ]);
+    let target = tree("", vec![
+        blob("a.txt", "a2"),
+        blob("build", "now a file"),
+        tree("docs", vec![blob("guide.md", "# Guide")]),
+    ]);
+    checkout_repository_tree(&tree("", vec![]), &current, directory, &CheckoutOptions::default()).unwrap();
+    // Left in a directory replaced by a file, and in place of a directory
+    fs::write(directory.join("build/notes.txt"), "untracked").unwrap();
+    fs::write(directory.join("docs"), "untracked").unwrap();
+
+    let refused = checkout_repository_tree(&current, &target, directory, &CheckoutOptions::default());
+    let forced = checkout_repository_tree(&current, &target, directory, &CheckoutOptions { force: true, ..CheckoutOptions::default() });
+    let untouched = [fs::read_to_string(directory.join("a.txt")).ok(), fs::read_to_string(directory.join("build/run.sh")).ok()];
+
+    teardown(directory);
+
+    for result in [refused, forced] {
+        match result {
+            Err(RepTreeError::LocalChanges(paths)) => assert_eq!(paths, vec![PathBuf::from("build/notes.txt"), PathBuf::from("docs")]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+    assert_eq!(untouched, [Some("a".to_string()), Some("#!/bin/sh".to_string())]);
+}
+
+/// Blob whose content is read from `source`, a checkout writing it failing while `source`
+/// is missing
+fn unloaded_blob(name: &str, content: &str, source: &Path) -> Node {
+    Node::BlobNode(Blob::new_unloaded(name.to_string(), generate(content.to_string()), source.to_path_buf()))
+}
+
+/// Check out `target` over `current`, the checkout being interrupted by a missing source
+/// for its last file
+fn interrupted_checkout(directory: &Path, current: &Node, target: &Node, options: &CheckoutOptions) -> Result<(), RepTreeError> {
+    checkout_repository_tree(&tree("", vec![]), current, directory, &CheckoutOptions::default()).unwrap();
+    checkout_repository_tree(current, target, directory, options)
+}
+
+#[test]
+fn should_rollback_interrupted_checkout() {
+    let directory = setup("tempdir_checkout_rollback");
+    let options = CheckoutOptions { journal: Some(PathBuf::from("tempdir_checkout_rollback_journal")), ..CheckoutOptions::default() };
+    let current = tree("", vec![
+        blob("a.txt", "a"),
+        tree("old", vec![blob("b.txt", "b")]),
+    ]);
+    let target = tree("", vec![
+        blob("a.txt", "a2"),
+        tree("new", vec![blob("c.txt", "c")]),
+        unloaded_blob("z.txt", "z", Path::new("tempdir_checkout_rollback_source")),
+    ]);
+
+    let interrupted = interrupted_checkout(directory, &current, &target, &options);
+    let written = fs::read_to_string(directory.join("a.txt")).ok();
+    let refused = checkout_repository_tree(&current, &target, directory, &options);
+    rollback_checkout(directory, &options).unwrap();
+
+    let read = |path: &str| fs::read_to_string(directory.join(path)).ok();
+    let contents = [read("a.txt"), read("old/b.txt"), read("z.txt")];
+    let new_exists = directory.join("new").exists();
+    let leftovers: Vec<String> = fs::read_dir(directory).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".dit-tmp"))
+        .collect();
+    let journal_exists = PathBuf::from("tempdir_checkout_rollback_journal").exists();
+
+    teardown(directory);
+
+    assert!(interrupted.is_err());
+    assert_eq!(written, Some("a2".to_string()));
+    assert!(matches!(refused, Err(RepTreeError::InterruptedCheckout(_))));
+    assert_eq!(contents, [Some("a".to_string()), Some("b".to_string()), None]);
+    assert!(!new_exists);
+    assert!(leftovers.is_empty());
+    assert!(!journal_exists);
+}
+
+#[test]
+fn should_resume_interrupted_checkout() {
+    let directory = setup("tempdir_checkout_resume");
+    let options = CheckoutOptions { journal: Some(PathBuf::from("tempdir_checkout_resume_journal")), ..CheckoutOptions::default() };
+    let source = Path::new("tempdir_checkout_resume_source");
+    let current = tree("", vec![
+        blob("a.txt", "a"),
+        tree("old", vec![blob("b.txt", "b")]),
+    ]);
+    let target = tree("", vec![
+        blob("a.txt", "a2"),
+        tree("new", vec![blob("c.txt", "c")]),
+        unloaded_blob("z.txt", "z", source),
+    ]);
+
+    let interrupted = interrupted_checkout(directory, &current, &target, &options);
+    let other_trees = resume_checkout(&current, &tree("", vec![]), directory, &options);
+    fs::write(source, "z").unwrap();
+    let resumed = resume_checkout(&current, &target, directory, &options);
+    fs::remove_file(source).unwrap();
+
+    let read = |path: &str| fs::read_to_string(directory.join(path)).ok();
+    let contents = [read("a.txt"), read("old/b.txt"), read("new/c.txt"), read("z.txt")];
+    let journal_exists = PathBuf::from("tempdir_checkout_resume_journal").exists();
+
+    teardown(directory);
+
+    assert!(interrupted.is_err());
+    assert!(other_trees.is_err());
+    assert!(resumed.is_ok());
+    assert_eq!(contents, [Some("a2".to_string()), None, Some("c".to_string()), Some("z".to_string())]);
+    assert!(!journal_exists);
+}
+
+#[test]
+fn should_detect_interrupted_checkout_without_journal_option() {
+    let directory = setup("tempdir_checkout_default_journal");
+    let options = CheckoutOptions::default();
+    let current = tree("", vec![blob("a.txt", "a")]);
+    let target = tree("", vec![
+        blob("a.txt", "a2"),
+        unloaded_blob("z.txt", "z", Path::new("tempdir_checkout_default_journal_source")),
+    ]);
+    let journal_path = get_default_journal_path(directory).unwrap();
+
+    let interrupted = interrupted_checkout(directory, &current, &target, &options);
+    let refused = checkout_repository_tree(&current, &target, directory, &options);
+    let journal_left = journal_path.exists();
+    rollback_checkout(directory, &options).unwrap();
+    let content = fs::read_to_string(directory.join("a.txt")).ok();
+    let journal_exists = journal_path.exists();
+
+    teardown(directory);
+
+    assert!(interrupted.is_err());
+    match refused {
+        Err(RepTreeError::InterruptedCheckout(path)) => assert_eq!(path, journal_path),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(journal_left);
+    assert_eq!(content, Some("a".to_string()));
+    assert!(!journal_exists);
+}
+
+#[test]
+fn should_resume_and_rollback_interrupted_transcription() {
+    let directory = setup("tempdir_transcription_journal");
+    let source = Path::new("tempdir_transcription_journal_source");
+    let root = tree("", vec![blob("a.txt", "a"), unloaded_blob("z.txt", "z", source)]);
+    let other = tree("", vec![blob("b.txt", "b")]);
+    let read = |path: &str| fs::read_to_string(directory.join(path)).ok();
+    let transcript = |root: &Node| transcript_repository_tree_to_files(root, &directory.to_path_buf(), &Mode::Complete);
+    fs::write(directory.join("old.txt"), "old").unwrap();
+
+    let interrupted = transcript(&root);
+    let written = [read("old.txt"), read("a.txt")];
+    let refused = transcript(&other);
+    rollback_checkout(directory, &CheckoutOptions::default()).unwrap();
+    let rolled_back = [read("old.txt"), read("a.txt")];
+
+    let interrupted_again = transcript(&root);
+    fs::write(source, "z").unwrap();
+    let resumed = transcript(&root);
+    fs::remove_file(source).unwrap();
+    let resumed_contents = [read("old.txt"), read("a.txt"), read("z.txt")];
+    let journal_exists = get_default_journal_path(directory).unwrap().exists();
+
+    teardown(directory);
+
+    assert!(interrupted.is_err() && interrupted_again.is_err());
+    assert_eq!(written, [None, Some("a".to_string())]);
+    assert!(matches!(refused, Err(RepTreeError::InterruptedCheckout(_))));
+    assert_eq!(rolled_back, [Some("old".to_string()), None]);
+    assert!(resumed.is_ok());
+    assert_eq!(resumed_contents, [None, Some("a".to_string()), Some("z".to_string())]);
+    assert!(!journal_exists);
+}
@@ -0,0 +1,89 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use dit_id_generator::traits::generator::Generator;
+use repository_tree_creator::features::create_repository_tree::{create_repository_tree_with_options, CreateOptions};
+use repository_tree_creator::features::get_repository_status::{get_repository_status, StatusEntry, StatusKind};
+use repository_tree_creator::features::get_repository_tree_from_object_files::get_repository_tree_from_object_store;
+use repository_tree_creator::features::transcript_repository_to_files::{transcript_repository_tree_to_files, Mode};
+use repository_tree_creator::features::transcript_repository_tree_to_object_files::transcript_repository_to_object_store;
+use repository_tree_creator::models::entry_mode::EntryMode;
+use repository_tree_creator::models::node::Node::TreeNode;
+use repository_tree_creator::models::tree::Tree;
+use repository_tree_creator::store::memory_store::MemoryStore;
+
+fn setup() {
+    fs::create_dir_all("tempdir_modes/source/bin").unwrap();
+    fs::create_dir_all("tempdir_modes/checkout").unwrap();
+    fs::write("tempdir_modes/source/bin/run.sh", "#!/bin/sh\n").unwrap();
+    fs::set_permissions("tempdir_modes/source/bin/run.sh", fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write("tempdir_modes/source/README.md", "Hello\n").unwrap();
+    std::os::unix::fs::symlink("bin/run.sh", "tempdir_modes/source/run").unwrap();
+}
+
+fn teardown() {
+    fs::remove_dir_all("tempdir_modes").unwrap()
+}
+
+#[test]
+fn should_capture_store_and_restore_entry_modes() {
+    setup();
+
+    let root = create_repository_tree_with_options(Tree::default(), vec![PathBuf::from("tempdir_modes/source")], &CreateOptions::default(), None).unwrap();
+    let source = root.get_node_at_path(Path::new("tempdir_modes/source")).unwrap();
+    let mut store = MemoryStore::new();
+    transcript_repository_to_object_store(source, &mut store).unwrap();
+    let mut restored = Tree::new("source".to_string(), vec![]);
+    get_repository_tree_from_object_store(&mut restored, &source.get_id(), &store).unwrap();
+    let mut restored = TreeNode(restored);
+    restored.generate_id();
+    transcript_repository_tree_to_files(&restored, &PathBuf::from("tempdir_modes/checkout"), &Mode::Modify).unwrap();
+
+    let mode = |path: &str| restored.get_node_at_path(Path::new(path)).unwrap().get_mode();
+    let modes = [mode("bin/run.sh"), mode("README.md"), mode("run"), mode("bin")];
+    let permissions = |path: &str| fs::metadata(path).unwrap().permissions().mode() & 0o111;
+    let checked_out = (
+        permissions("tempdir_modes/checkout/source/bin/run.sh"),
+        permissions("tempdir_modes/checkout/source/README.md"),
+        fs::read_link("tempdir_modes/checkout/source/run").unwrap(),
+    );
+
+    teardown();
+
+    assert_eq!(modes, [EntryMode::Executable, EntryMode::Regular, EntryMode::Symlink, EntryMode::Directory]);
+    assert_eq!(restored.get_id(), source.get_id());
+    assert_eq!(checked_out, (0o111, 0, PathBuf::from("bin/run.sh")));
+}
+
+#[test]
+fn should_report_status_of_links_and_modes() {
+    let directory = Path::new("tempdir_modes_status");
+    fs::create_dir_all(directory.join("bin")).unwrap();
+    fs::write(directory.join("bin/run.sh"), "#!/bin/sh\n").unwrap();
+    fs::set_permissions(directory.join("bin/run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(directory.join("README.md"), "Hello\n").unwrap();
+    std::os::unix::fs::symlink("bin/run.sh", directory.join("link")).unwrap();
+    std::os::unix::fs::symlink("bin", directory.join("dirlink")).unwrap();
+
+    let root = create_repository_tree_with_options(Tree::default(), vec![directory.to_path_buf()], &CreateOptions::default(), None).unwrap();
+    let tracked = root.get_node_at_path(directory).unwrap();
+    let clean = get_repository_status(tracked, directory).unwrap();
+    fs::set_permissions(directory.join("README.md"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::set_permissions(directory.join("bin/run.sh"), fs::Permissions::from_mode(0o644)).unwrap();
+    fs::remove_file(directory.join("link")).unwrap();
+    std::os::unix::fs::symlink("README.md", directory.join("link")).unwrap();
+    std::os::unix::fs::symlink("bin", directory.join("untracked")).unwrap();
+    let changed = get_repository_status(tracked, directory).unwrap();
+
+    fs::remove_dir_all(directory).unwrap();
+
+    assert_eq!(clean, vec![]);
+    assert_eq!(changed, vec![
+        StatusEntry { kind: StatusKind::Modified, path: PathBuf::from("README.md") },
+        StatusEntry { kind: StatusKind::Modified, path: PathBuf::from("bin/run.sh") },
+        StatusEntry { kind: StatusKind::Modified, path: PathBuf::from("link") },
+        StatusEntry { kind: StatusKind::Untracked, path: PathBuf::from("untracked") },
+    ]);
+}
@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::PathBuf;
+use dit_id_generator::traits::generator::Generator;
+use repository_tree_creator::features::get_repository_tree_from_object_files::{get_commit_from_object_files, get_repository_tree_from_object_store};
+use repository_tree_creator::features::transcript_repository_tree_to_object_files::{transcript_commit_to_object_files, transcript_repository_to_object_store};
+use repository_tree_creator::models::commit::{Commit, Signature};
+use repository_tree_creator::models::node::Node;
+use repository_tree_creator::models::tree::Tree;
+use repository_tree_creator::store::memory_store::MemoryStore;
+use repository_tree_creator::store::object_store::ObjectStore;
+
+#[test]
+fn should_round_trip_repository_tree_through_memory_store() {
+    let b1 = Node::create_blob_node("main.rs".to_string(), "fn main() {}", PathBuf::from("src/main.rs"));
+    let b2 = Node::create_blob_node("logo.png".to_string(), vec![0x89, 0x00, 0xff], PathBuf::from("logo.png"));
+    let src = Node::create_tree_node("src".to_string(), vec![b1], PathBuf::from("src"));
+    let mut root = Node::create_tree_node("".to_string(), vec![src, b2], PathBuf::new());
+    let root_id = root.generate_id();
+
+    let mut store = MemoryStore::new();
+    transcript_repository_to_object_store(&root, &mut store).unwrap();
+
+    let mut restored = Tree::default();
+    get_repository_tree_from_object_store(&mut restored, &root_id, &store).unwrap();
+
+    assert_eq!(store.list().unwrap().len(), 4);
+    assert_eq!(restored.get_children().len(), 2);
+    let logo = restored.get_children()[0].clone();
+    let mut src = restored.get_children()[1].clone();
+    assert_eq!(logo.get_content().unwrap(), vec![0x89, 0x00, 0xff]);
+    assert_eq!(src.get_name(), "src");
+    assert_eq!(src.get_children().unwrap()[0].get_content().unwrap(), b"fn main() {}");
+    assert_eq!(restored.generate_id(), root_id);
+}
+
+#[test]
+fn should_round_trip_commit_through_object_files() {
+    let author = Signature::new("John Doe".to_string(), "john@doe.com".to_string(), 1700000000);
+    let mut commit = Commit::new("1234".to_string(), vec!["5678".to_string()], author.clone(), author, "Plant an oak".to_string());
+    let commit_id = commit.generate_id();
+
+    transcript_commit_to_object_files(&commit, &PathBuf::from("tempdir_commit")).unwrap();
+    let restored = get_commit_from_object_files(&commit_id, &PathBuf::from("tempdir_commit"));
+
+    fs::remove_dir_all("tempdir_commit").unwrap();
+
+    assert_eq!(restored.unwrap(), commit);
+}
@@ -1,4 +1,4 @@
-use std::fs::{create_dir_all, remove_dir_all};
+use std::fs::{create_dir_all, read_to_string, remove_dir_all};
 use std::path::PathBuf;
 use repository_tree_creator::features::transcript_repository_to_files::{Mode, transcript_repository_tree_to_files};
 use repository_tree_creator::models::blob::Blob;
@@ -29,6 +29,7 @@ fn should_transcript_repository_to_files() {
     assert!(PathBuf::from("tmp1/dir1").is_dir());
     assert!(PathBuf::from("tmp1/dir1/file1").is_file());
     assert!(PathBuf::from("tmp1/dir1/dir2").is_dir());
+    assert_eq!(read_to_string("tmp1/dir1/file1").unwrap(), "content");
     
     teardown();
 }
\ No newline at end of file